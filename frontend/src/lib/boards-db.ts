@@ -346,7 +346,7 @@ export async function fetchPresets(): Promise<void> {
       const presetsData = await response.json();
       // Sort presets by effect type then name (Blackout is already in the server presets)
       const presetsList = presetsData
-          .map((p: any) => ({ id: p.wled_slot, name: p.name, state: p.state }))
+          .map((p: any) => ({ id: p.wled_slot, name: p.name, state: p.state, version: p.version }))
           .sort((a: any, b: any) => {
             // Extract first word (effect type) from preset name
             const typeA = a.name.split(' ')[0];
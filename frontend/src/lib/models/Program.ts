@@ -24,6 +24,8 @@ export interface ProgramData {
   bpm?: number;
   // Grid offset (downbeat position) for beat grid alignment
   gridOffset?: number;
+  // Server version, sent back as If-Match when saving
+  version?: number;
 }
 
 export class Program implements ProgramData {
@@ -47,6 +49,8 @@ export class Program implements ProgramData {
   bpm?: number;
   // Grid offset (downbeat position) for beat grid alignment
   gridOffset?: number;
+  // Server version, sent back as If-Match when saving
+  version: number;
 
   private constructor(data: ProgramData) {
     this.id = data.id;
@@ -65,6 +69,7 @@ export class Program implements ProgramData {
     this.displayOrder = data.displayOrder ?? 0;
     this.bpm = data.bpm;
     this.gridOffset = data.gridOffset;
+    this.version = data.version ?? 0;
   }
 
   /**
@@ -113,6 +118,7 @@ export class Program implements ProgramData {
       displayOrder: data.displayOrder ?? data.display_order ?? 0,
       bpm: data.bpm,
       gridOffset: data.gridOffset ?? data.grid_offset ?? 0,
+      version: data.version ?? 0,
     });
   }
 
@@ -137,6 +143,7 @@ export class Program implements ProgramData {
       display_order: this.displayOrder,
       bpm: this.bpm,
       grid_offset: this.gridOffset,
+      version: this.version,
     };
   }
 }
@@ -73,12 +73,17 @@ export async function saveProgram(program: Program, audioDataUrl: string | null
         const result = await audioResponse.json();
         console.log('[programs-db] Audio uploaded:', result);
 
-        // The backend returns a JSON object with the filename, e.g., { "filename": "..." }
-        if (result.filename) {
-          program.audioId = result.filename;
+        // The backend returns a JSON object with the filename, e.g., { "audio_file": "..." }
+        const filename = result.audio_file ?? result.filename;
+        if (filename) {
+          program.audioId = filename;
         } else {
           throw new Error('Audio upload response did not include a filename.');
         }
+        // Storing the tempo analysis bumps the program's version
+        if (typeof result.program_version === 'number') {
+          program.version = result.program_version;
+        }
       } catch (error) {
         console.error('[programs-db] Error uploading audio:', error);
         programsError.set('Audio upload failed. Program was not saved.');
@@ -93,11 +98,13 @@ export async function saveProgram(program: Program, audioDataUrl: string | null
 
     const response = await fetch(url, {
       method,
-      headers: { 'Content-Type': 'application/json' },
+      headers: isUpdate ? updateHeaders(program.version) : { 'Content-Type': 'application/json' },
       body: JSON.stringify(program.toJson())
     });
 
-    if (!response.ok) {
+    if (isUpdate) {
+      await acceptUpdate(program, response);
+    } else if (!response.ok) {
       throw new Error(`Failed to save program to server: ${response.statusText}`);
     }
 
@@ -119,6 +126,27 @@ export async function saveProgram(program: Program, audioDataUrl: string | null
 }
 
 
+/**
+ * Headers for a PUT made against the version we last loaded
+ */
+function updateHeaders(version: number | undefined): Record<string, string> {
+  return { 'Content-Type': 'application/json', 'If-Match': `"${version ?? 0}"` };
+}
+
+/**
+ * Take the saved version from a PUT response, or explain why it was refused
+ */
+async function acceptUpdate(program: Program, response: Response): Promise<void> {
+  if (response.status === 409) {
+    throw new Error(`Program ${program.id} was changed elsewhere; reload it before saving`);
+  }
+  if (!response.ok) {
+    throw new Error(`Failed to update program: ${response.statusText}`);
+  }
+  const saved = await response.json();
+  program.version = saved.version ?? program.version;
+}
+
 /**
  * Update an existing program (without audio upload)
  */
@@ -128,13 +156,11 @@ export async function updateProgram(program: Program): Promise<void> {
   try {
     const response = await fetch(`${API_URL}/programs/${program.id}`, {
       method: 'PUT',
-      headers: { 'Content-Type': 'application/json' },
+      headers: updateHeaders(program.version),
       body: JSON.stringify(program.toJson())
     });
 
-    if (!response.ok) {
-      throw new Error(`Failed to update program: ${response.statusText}`);
-    }
+    await acceptUpdate(program, response);
 
     // Update local store
     programs.update(currentPrograms => {
@@ -231,13 +257,14 @@ export async function reorderPrograms(reorderedPrograms: Program[]): Promise<voi
               display_order: program.displayOrder,
               bpm: program.bpm,
               grid_offset: program.gridOffset,
+              version: program.version,
             };
 
         return fetch(`${API_URL}/programs/${program.id}`, {
           method: 'PUT',
-          headers: { 'Content-Type': 'application/json' },
+          headers: updateHeaders(program.version),
           body: JSON.stringify(jsonBody)
-        });
+        }).then(response => acceptUpdate(program, response));
       })
     );
 
@@ -18,6 +18,7 @@ export const boardsError: Writable<string | null> = writable(null);
 export interface Preset {
 	id: number;
 	name: string;
+	version: number; // Stored version, sent back as If-Match when updating
 }
 export const presets: Writable<Preset[]> = writable([]);
 
@@ -49,6 +50,13 @@ export interface LoopyProSettings {
 	port: number;
 	mute_audio: boolean;
 	audio_sync_delay_ms: number;
+	// OSC addresses per command; `{track}` is replaced with the program's track
+	addresses?: {
+		play: string;
+		pause: string;
+		resume: string;
+		seek: string;
+	};
 }
 
 export const loopyProSettings: Writable<LoopyProSettings> = writable({
@@ -1,5 +1,5 @@
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Audio file storage and management
 pub struct AudioFile;
@@ -47,14 +47,19 @@ impl AudioFile {
         Ok(filename)
     }
 
-    /// Load audio file as binary data
-    pub fn load(filename: &str, audio_path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    /// Resolve a stored filename to its path inside the audio directory
+    pub fn resolve_path(filename: &str, audio_path: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
         // Validate filename (prevent path traversal)
         if filename.contains("..") || filename.contains("/") || filename.contains("\\") {
             return Err("Invalid filename: path traversal detected".into());
         }
 
-        let file_path = audio_path.join(filename);
+        Ok(audio_path.join(filename))
+    }
+
+    /// Load audio file as binary data
+    pub fn load(filename: &str, audio_path: &Path) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let file_path = Self::resolve_path(filename, audio_path)?;
 
         if !file_path.exists() {
             return Err(format!("Audio file not found: {}", filename).into());
@@ -68,12 +73,7 @@ impl AudioFile {
 
     /// Delete an audio file and its associated peaks file
     pub fn delete(filename: &str, audio_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
-        // Validate filename (prevent path traversal)
-        if filename.contains("..") || filename.contains("/") || filename.contains("\\") {
-            return Err("Invalid filename: path traversal detected".into());
-        }
-
-        let file_path = audio_path.join(filename);
+        let file_path = Self::resolve_path(filename, audio_path)?;
 
         if file_path.exists() {
             fs::remove_file(&file_path)?;
@@ -0,0 +1,440 @@
+use std::fs::File;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Frames per second of the onset envelope
+const ENVELOPE_RATE: f64 = 100.0;
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 200.0;
+/// Centre of the tempo prior; halves/doubles are weighted against it
+const PREFERRED_BPM: f64 = 120.0;
+const BEATS_PER_BAR: usize = 4;
+
+/// Decoded mono audio
+pub struct DecodedAudio {
+    pub samples: Vec<f32>,
+    pub sample_rate: u32,
+}
+
+impl DecodedAudio {
+    pub fn duration(&self) -> f64 {
+        if self.sample_rate == 0 {
+            return 0.0;
+        }
+        self.samples.len() as f64 / self.sample_rate as f64
+    }
+}
+
+/// Tempo and downbeat estimate for an audio file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TempoAnalysis {
+    pub bpm: f64,
+    pub grid_offset: f64,
+    pub confidence: f64,
+    pub duration: f64,
+}
+
+/// Decode an audio file (MP3/WAV/OGG/FLAC) and downmix it to mono
+pub fn decode_file(path: &Path) -> Result<DecodedAudio, Box<dyn std::error::Error>> {
+    let file = File::open(path)?;
+    let mss = MediaSourceStream::new(Box::new(file), Default::default());
+
+    let mut hint = Hint::new();
+    if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
+        hint.with_extension(ext);
+    }
+
+    let probed = symphonia::default::get_probe().format(
+        &hint,
+        mss,
+        &FormatOptions::default(),
+        &MetadataOptions::default(),
+    )?;
+    let mut format = probed.format;
+
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .ok_or("No decodable audio track")?;
+    let track_id = track.id;
+    let mut sample_rate = track.codec_params.sample_rate.unwrap_or(0);
+
+    let mut decoder =
+        symphonia::default::get_codecs().make(&track.codec_params, &DecoderOptions::default())?;
+
+    let mut samples: Vec<f32> = Vec::new();
+    let mut sample_buf: Option<SampleBuffer<f32>> = None;
+
+    loop {
+        let packet = match format.next_packet() {
+            Ok(p) => p,
+            Err(SymphoniaError::IoError(ref e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(SymphoniaError::ResetRequired) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if packet.track_id() != track_id {
+            continue;
+        }
+
+        let decoded = match decoder.decode(&packet) {
+            Ok(d) => d,
+            Err(SymphoniaError::DecodeError(e)) => {
+                tracing::warn!("Skipping undecodable audio packet: {}", e);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let spec = *decoded.spec();
+        sample_rate = spec.rate;
+        let channels = spec.channels.count().max(1);
+
+        let buf = sample_buf.get_or_insert_with(|| {
+            SampleBuffer::<f32>::new(decoded.capacity() as u64, spec)
+        });
+        if buf.capacity() < decoded.capacity() * channels {
+            *buf = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+        }
+        buf.copy_interleaved_ref(decoded);
+
+        for frame in buf.samples().chunks(channels) {
+            samples.push(frame.iter().sum::<f32>() / channels as f32);
+        }
+    }
+
+    if sample_rate == 0 || samples.is_empty() {
+        return Err("Audio file contains no samples".into());
+    }
+
+    Ok(DecodedAudio { samples, sample_rate })
+}
+
+/// Decode and analyse an audio file in one step
+pub fn analyze_file(path: &Path) -> Result<TempoAnalysis, Box<dyn std::error::Error>> {
+    let audio = decode_file(path)?;
+    analyze(&audio).ok_or_else(|| "Audio too short or too quiet to detect tempo".into())
+}
+
+/// Onset strength envelope sampled at `ENVELOPE_RATE`
+///
+/// Half-wave rectified difference of log frame energy, mean-removed.
+pub fn onset_envelope(audio: &DecodedAudio) -> Vec<f64> {
+    let hop = (audio.sample_rate as f64 / ENVELOPE_RATE).round().max(1.0) as usize;
+
+    let log_energy: Vec<f64> = audio
+        .samples
+        .chunks(hop)
+        .map(|frame| {
+            let energy = frame.iter().map(|s| (*s as f64) * (*s as f64)).sum::<f64>() / frame.len() as f64;
+            (1.0 + 1000.0 * energy).ln()
+        })
+        .collect();
+
+    let mut envelope: Vec<f64> = std::iter::once(0.0)
+        .chain(log_energy.windows(2).map(|w| (w[1] - w[0]).max(0.0)))
+        .collect();
+
+    let mean = envelope.iter().sum::<f64>() / envelope.len().max(1) as f64;
+    for v in &mut envelope {
+        *v = (*v - mean).max(0.0);
+    }
+    envelope
+}
+
+/// Estimate tempo and the first downbeat from decoded audio
+pub fn analyze(audio: &DecodedAudio) -> Option<TempoAnalysis> {
+    let envelope = onset_envelope(audio);
+    let (bpm, confidence) = estimate_bpm(&envelope)?;
+    let grid_offset = estimate_downbeat(&envelope, bpm);
+
+    Some(TempoAnalysis {
+        bpm: (bpm * 100.0).round() / 100.0,
+        grid_offset: (grid_offset * 1000.0).round() / 1000.0,
+        confidence,
+        duration: audio.duration(),
+    })
+}
+
+/// Autocorrelation tempo estimate, weighted towards `PREFERRED_BPM`
+fn estimate_bpm(envelope: &[f64]) -> Option<(f64, f64)> {
+    let min_lag = (ENVELOPE_RATE * 60.0 / MAX_BPM).floor() as usize;
+    let max_lag = (ENVELOPE_RATE * 60.0 / MIN_BPM).ceil() as usize;
+    if envelope.len() < max_lag * 4 {
+        return None;
+    }
+
+    let energy: f64 = envelope.iter().map(|v| v * v).sum();
+    if energy <= f64::EPSILON {
+        return None;
+    }
+
+    let autocorr = |lag: usize| -> f64 {
+        envelope
+            .iter()
+            .zip(envelope[lag..].iter())
+            .map(|(a, b)| a * b)
+            .sum::<f64>()
+    };
+
+    let raw: Vec<f64> = (0..=max_lag + 1).map(|lag| if lag < min_lag - 1 { 0.0 } else { autocorr(lag) }).collect();
+
+    let mut best_lag = 0;
+    let mut best_score = f64::MIN;
+    for (lag, value) in raw.iter().enumerate().take(max_lag + 1).skip(min_lag) {
+        let bpm = ENVELOPE_RATE * 60.0 / lag as f64;
+        let octaves = (bpm / PREFERRED_BPM).log2();
+        let weight = (-0.5 * (octaves / 0.9).powi(2)).exp();
+        let score = value * weight;
+        if score > best_score {
+            best_score = score;
+            best_lag = lag;
+        }
+    }
+
+    // Parabolic interpolation around the peak for sub-frame resolution
+    let (y0, y1, y2) = (raw[best_lag - 1], raw[best_lag], raw[best_lag + 1]);
+    let denom = y0 - 2.0 * y1 + y2;
+    let shift = if denom.abs() > f64::EPSILON {
+        (0.5 * (y0 - y2) / denom).clamp(-0.5, 0.5)
+    } else {
+        0.0
+    };
+    let lag = best_lag as f64 + shift;
+
+    let confidence = (raw[best_lag] / energy).clamp(0.0, 1.0);
+    Some((ENVELOPE_RATE * 60.0 / lag, confidence))
+}
+
+/// Find the beat phase, then pick the strongest beat in the bar as the downbeat
+fn estimate_downbeat(envelope: &[f64], bpm: f64) -> f64 {
+    let period = ENVELOPE_RATE * 60.0 / bpm;
+    let phase_steps = period.ceil() as usize;
+
+    let comb = |start: f64, step: f64| -> f64 {
+        let mut sum = 0.0;
+        let mut pos = start;
+        while (pos.round() as usize) < envelope.len() {
+            sum += envelope[pos.round() as usize];
+            pos += step;
+        }
+        sum
+    };
+
+    let beat_phase = (0..phase_steps)
+        .map(|p| (p as f64, comb(p as f64, period)))
+        .fold((0.0, f64::MIN), |best, cur| if cur.1 > best.1 { cur } else { best })
+        .0;
+
+    let downbeat = (0..BEATS_PER_BAR)
+        .map(|b| {
+            let start = beat_phase + b as f64 * period;
+            (start, comb(start, period * BEATS_PER_BAR as f64))
+        })
+        .fold((beat_phase, f64::MIN), |best, cur| if cur.1 > best.1 { cur } else { best })
+        .0;
+
+    downbeat / ENVELOPE_RATE
+}
+
+/// Contiguous stretch of the song with a roughly constant energy level
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Section {
+    pub start: f64,
+    pub end: f64,
+    /// Mean RMS energy relative to the loudest section (0.0 - 1.0)
+    pub energy: f64,
+}
+
+/// RMS energy per envelope frame
+pub fn energy_envelope(audio: &DecodedAudio) -> Vec<f64> {
+    let hop = (audio.sample_rate as f64 / ENVELOPE_RATE).round().max(1.0) as usize;
+    audio
+        .samples
+        .chunks(hop)
+        .map(|frame| {
+            (frame.iter().map(|s| (*s as f64) * (*s as f64)).sum::<f64>() / frame.len() as f64).sqrt()
+        })
+        .collect()
+}
+
+/// Onset times in seconds, picked as local maxima above an adaptive threshold
+pub fn detect_onsets(envelope: &[f64]) -> Vec<f64> {
+    const WINDOW: usize = 10;
+    const MIN_GAP: usize = 5;
+
+    let mut onsets = Vec::new();
+    let mut last: Option<usize> = None;
+
+    for i in 1..envelope.len().saturating_sub(1) {
+        let v = envelope[i];
+        if v <= envelope[i - 1] || v < envelope[i + 1] {
+            continue;
+        }
+
+        let lo = i.saturating_sub(WINDOW);
+        let hi = (i + WINDOW + 1).min(envelope.len());
+        let local_mean = envelope[lo..hi].iter().sum::<f64>() / (hi - lo) as f64;
+        if v <= local_mean * 1.5 || v <= f64::EPSILON {
+            continue;
+        }
+
+        if last.is_some_and(|l| i - l < MIN_GAP) {
+            continue;
+        }
+        last = Some(i);
+        onsets.push(i as f64 / ENVELOPE_RATE);
+    }
+
+    onsets
+}
+
+/// Split a song into sections at large, sustained changes in energy
+///
+/// Boundaries are snapped to the nearest onset within 250ms so that cues land on
+/// the hit that starts the section.
+pub fn detect_sections(audio: &DecodedAudio, min_section_secs: f64) -> Vec<Section> {
+    let energy = energy_envelope(audio);
+    let duration = audio.duration();
+    if energy.is_empty() {
+        return Vec::new();
+    }
+
+    let window = ((min_section_secs / 2.0) * ENVELOPE_RATE).max(1.0) as usize;
+    let min_gap = (min_section_secs * ENVELOPE_RATE) as usize;
+
+    // Novelty: difference between mean energy after and before each frame
+    let mut prefix = vec![0.0; energy.len() + 1];
+    for (i, e) in energy.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + e;
+    }
+    let mean = |a: usize, b: usize| (prefix[b] - prefix[a]) / (b - a).max(1) as f64;
+
+    let novelty: Vec<f64> = (0..energy.len())
+        .map(|i| {
+            if i < window || i + window > energy.len() {
+                0.0
+            } else {
+                (mean(i, i + window) - mean(i - window, i)).abs()
+            }
+        })
+        .collect();
+
+    let peak = novelty.iter().cloned().fold(0.0, f64::max);
+    let threshold = peak * 0.3;
+
+    let mut boundaries: Vec<usize> = Vec::new();
+    let mut candidates: Vec<usize> = (0..novelty.len()).filter(|&i| novelty[i] > threshold).collect();
+    candidates.sort_by(|a, b| novelty[*b].total_cmp(&novelty[*a]));
+    for i in candidates {
+        if i < min_gap || energy.len() - i < min_gap {
+            continue;
+        }
+        if boundaries.iter().all(|&b| b.abs_diff(i) >= min_gap) {
+            boundaries.push(i);
+        }
+    }
+    boundaries.sort_unstable();
+
+    let onsets = detect_onsets(&onset_envelope(audio));
+    let snap = |t: f64| -> f64 {
+        onsets
+            .iter()
+            .filter(|o| (*o - t).abs() <= 0.25)
+            .min_by(|a, b| (*a - t).abs().total_cmp(&(*b - t).abs()))
+            .copied()
+            .unwrap_or(t)
+    };
+
+    let mut edges: Vec<f64> = vec![0.0];
+    edges.extend(boundaries.iter().map(|&b| snap(b as f64 / ENVELOPE_RATE)));
+    edges.push(duration);
+
+    let mut sections: Vec<Section> = edges
+        .windows(2)
+        .map(|w| {
+            let a = ((w[0] * ENVELOPE_RATE) as usize).min(energy.len());
+            let b = ((w[1] * ENVELOPE_RATE) as usize).min(energy.len());
+            Section { start: w[0], end: w[1], energy: mean(a, b) }
+        })
+        .collect();
+
+    let loudest = sections.iter().map(|s| s.energy).fold(0.0, f64::max);
+    if loudest > f64::EPSILON {
+        for section in &mut sections {
+            section.energy /= loudest;
+        }
+    }
+
+    sections
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn click_track(bpm: f64, offset: f64, seconds: f64, accent_every: usize) -> DecodedAudio {
+        let sample_rate = 22_050;
+        let mut samples = vec![0.0f32; (seconds * sample_rate as f64) as usize];
+        let beat = 60.0 / bpm;
+        let mut t = offset;
+        let mut n = 0;
+        while t < seconds {
+            let start = (t * sample_rate as f64) as usize;
+            let amp = if n % accent_every == 0 { 1.0 } else { 0.4 };
+            for i in 0..400 {
+                if let Some(s) = samples.get_mut(start + i) {
+                    *s = amp * (1.0 - i as f32 / 400.0) * if i % 2 == 0 { 1.0 } else { -1.0 };
+                }
+            }
+            t += beat;
+            n += 1;
+        }
+        DecodedAudio { samples, sample_rate }
+    }
+
+    #[test]
+    fn test_detects_click_track_bpm() {
+        let audio = click_track(128.0, 0.25, 20.0, 4);
+        let analysis = analyze(&audio).expect("analysis");
+        assert!((analysis.bpm - 128.0).abs() < 1.5, "bpm was {}", analysis.bpm);
+    }
+
+    #[test]
+    fn test_detects_accented_downbeat() {
+        let audio = click_track(120.0, 0.3, 20.0, 4);
+        let analysis = analyze(&audio).expect("analysis");
+        assert!((analysis.grid_offset - 0.3).abs() < 0.03, "offset was {}", analysis.grid_offset);
+    }
+
+    #[test]
+    fn test_splits_sections_on_energy_change() {
+        let sample_rate = 22_050;
+        let mut samples = Vec::new();
+        for (secs, amp) in [(12.0, 0.05f32), (12.0, 0.8), (12.0, 0.1)] {
+            let n = (secs * sample_rate as f64) as usize;
+            samples.extend((0..n).map(|i| if i % 2 == 0 { amp } else { -amp }));
+        }
+        let audio = DecodedAudio { samples, sample_rate };
+
+        let sections = detect_sections(&audio, 8.0);
+        assert_eq!(sections.len(), 3, "sections: {:?}", sections);
+        assert!((sections[1].start - 12.0).abs() < 0.5);
+        assert!((sections[2].start - 24.0).abs() < 0.5);
+        assert!(sections[1].energy > sections[0].energy && sections[1].energy > sections[2].energy);
+    }
+
+    #[test]
+    fn test_silence_has_no_tempo() {
+        let audio = DecodedAudio { samples: vec![0.0; 22_050 * 10], sample_rate: 22_050 };
+        assert!(analyze(&audio).is_none());
+    }
+}
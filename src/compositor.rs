@@ -0,0 +1,541 @@
+use std::collections::HashMap;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::effects_engine::{BoardTarget, EffectSource, EngineCommand};
+use crate::pattern_engine::{PatternCommand, PatternSource};
+use crate::tempo::TapTempo;
+use crate::timing_metrics::TimingMetrics;
+use crate::transport::{OutputKey, SharedTransport, TransportPool};
+
+const TICK_DURATION: Duration = Duration::from_millis(25);
+const BLACKOUT_REPEATS: usize = 5;
+const MAX_LEDS: usize = 128;
+
+/// One output's pixels, in the E1.31 RGBW channel layout
+///
+/// Frames keep their contents between ticks, so an effect that has nothing
+/// new to draw can leave the frame as it is.
+pub struct LedFrame {
+    universe: u16,
+    led_count: usize,
+    dmx: [u8; 512],
+}
+
+impl LedFrame {
+    pub fn new(universe: u16, led_count: usize) -> Self {
+        Self {
+            universe,
+            led_count,
+            dmx: [0u8; 512],
+        }
+    }
+
+    pub fn universe(&self) -> u16 {
+        self.universe
+    }
+
+    pub fn led_count(&self) -> usize {
+        self.led_count
+    }
+
+    /// Set every LED to one colour
+    pub fn fill(&mut self, r: u8, g: u8, b: u8) {
+        self.dmx = [0u8; 512];
+        for i in 0..self.led_count.min(MAX_LEDS) {
+            let offset = i * 4;
+            self.dmx[offset] = r;
+            self.dmx[offset + 1] = g;
+            self.dmx[offset + 2] = b;
+        }
+    }
+
+    pub fn set_leds(&mut self, leds: &[[u8; 3]]) {
+        self.dmx = [0u8; 512];
+        for (i, led) in leds.iter().take(MAX_LEDS).enumerate() {
+            let offset = i * 4;
+            self.dmx[offset..offset + 3].copy_from_slice(led);
+        }
+    }
+
+    pub fn set_dmx(&mut self, dmx: &[u8; 512]) {
+        self.dmx = *dmx;
+    }
+
+    pub fn clear(&mut self) {
+        self.dmx = [0u8; 512];
+    }
+
+    pub fn dmx(&self) -> &[u8; 512] {
+        &self.dmx
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceKind {
+    Effect,
+    Pattern,
+}
+
+struct Output {
+    /// None when rendering offline
+    transport: Option<SharedTransport>,
+    frame: LedFrame,
+    owner: SourceKind,
+}
+
+/// Every output the render thread is currently driving
+pub struct Outputs {
+    map: HashMap<OutputKey, Output>,
+    pool: Option<TransportPool>,
+    /// Released outputs still owed repeat blackout packets, one per tick
+    blackouts: HashMap<OutputKey, (SharedTransport, usize)>,
+}
+
+impl Outputs {
+    pub fn frame(&mut self, key: &OutputKey) -> Option<&mut LedFrame> {
+        self.map.get_mut(key).map(|o| &mut o.frame)
+    }
+
+    /// Hand outputs to `owner`, taking transports from the pool; returns the keys it got
+    fn claim(&mut self, boards: &[BoardTarget], owner: SourceKind) -> Vec<OutputKey> {
+        let mut keys = Vec::new();
+        for board in boards {
+            let output = match self.pool {
+                Some(ref pool) => pool.get(&board.ip, board.universe).map(|(key, t)| (key, Some(t))),
+                None => OutputKey::for_board(&board.ip, board.universe).map(|key| (key, None)),
+            };
+            let (key, transport) = match output {
+                Ok(output) => output,
+                Err(e) => {
+                    info!(ip = %board.ip, error = %e, "Failed to get E1.31 transport");
+                    continue;
+                }
+            };
+            if keys.contains(&key) {
+                continue;
+            }
+            // The new owner's frames replace any blackout still pending
+            self.blackouts.remove(&key);
+
+            self.map.insert(
+                key,
+                Output {
+                    transport,
+                    frame: LedFrame::new(board.universe, board.led_count),
+                    owner,
+                },
+            );
+            keys.push(key);
+        }
+        keys
+    }
+
+    /// Black out and drop outputs still owned by `owner`, except those in `keep`
+    ///
+    /// One blackout goes out now; the repeats follow on the next ticks so a
+    /// release never holds up the other source's frames.
+    fn release(&mut self, keys: &[OutputKey], keep: &[OutputKey], owner: SourceKind) {
+        let mut released = 0;
+        for key in keys {
+            if keep.contains(key) || self.map.get(key).map(|o| o.owner) != Some(owner) {
+                continue;
+            }
+            if let Some(mut output) = self.map.remove(key) {
+                output.frame.clear();
+                if let Some(transport) = output.transport {
+                    if let Ok(mut transport) = transport.lock() {
+                        let _ = transport.send_dmx_packet(output.frame.dmx());
+                    }
+                    self.blackouts.insert(*key, (transport, BLACKOUT_REPEATS - 1));
+                }
+                released += 1;
+            }
+        }
+        if released > 0 {
+            info!(outputs = released, "E1.31 blackout sent to released outputs");
+        }
+    }
+
+    /// Send this tick's share of the pending blackout repeats
+    fn send_blackouts(&mut self) {
+        let black = [0u8; 512];
+        self.blackouts.retain(|_, (transport, remaining)| {
+            if let Ok(mut transport) = transport.lock() {
+                let _ = transport.send_dmx_packet(&black);
+            }
+            *remaining -= 1;
+            *remaining > 0
+        });
+    }
+}
+
+/// Fade of a source to black, ending in a stop
+struct Fade {
+    start: Instant,
+    duration: Duration,
+}
+
+impl Fade {
+    fn new(start: Instant, duration: Duration) -> Self {
+        Self { start, duration }
+    }
+
+    fn level(&self, now: Instant) -> f64 {
+        if self.duration.is_zero() {
+            return 0.0;
+        }
+        let progress = now.duration_since(self.start).as_secs_f64() / self.duration.as_secs_f64();
+        (1.0 - progress).clamp(0.0, 1.0)
+    }
+}
+
+/// Commands for the render thread, tagged with the engine they came from
+#[derive(Debug)]
+pub enum CompositorCommand {
+    Effects(EngineCommand),
+    Patterns(PatternCommand),
+}
+
+/// Single frame-clocked render loop that owns every output
+///
+/// The running effect and the running pattern are sources that draw into
+/// per-output frames; each output is sent once per tick. Starting a source
+/// takes its outputs away from the other one, so an effect and a pattern can
+/// run side by side on different boards but never write the same board.
+pub struct Compositor {
+    outputs: Outputs,
+    effect: Option<EffectSource>,
+    pattern: Option<PatternSource>,
+    effect_fade: Option<Fade>,
+    pattern_fade: Option<Fade>,
+    effect_paused: bool,
+    pattern_paused: bool,
+    effect_taps: TapTempo,
+    pattern_taps: TapTempo,
+}
+
+impl Compositor {
+    /// Start the render thread; engines send to the returned channel
+    pub fn spawn(pool: TransportPool, timing_metrics: Option<Arc<TimingMetrics>>) -> mpsc::Sender<CompositorCommand> {
+        let (command_tx, command_rx) = mpsc::channel();
+        thread::spawn(move || {
+            Compositor::with_pool(Some(pool)).run_loop(command_rx, timing_metrics);
+        });
+        command_tx
+    }
+
+    /// Compositor without transports, stepped by the caller on its own clock
+    pub fn offline() -> Self {
+        Compositor::with_pool(None)
+    }
+
+    fn with_pool(pool: Option<TransportPool>) -> Self {
+        Compositor {
+            outputs: Outputs {
+                map: HashMap::new(),
+                pool,
+                blackouts: HashMap::new(),
+            },
+            effect: None,
+            pattern: None,
+            effect_fade: None,
+            pattern_fade: None,
+            effect_paused: false,
+            pattern_paused: false,
+            effect_taps: TapTempo::default(),
+            pattern_taps: TapTempo::default(),
+        }
+    }
+
+    fn run_loop(mut self, command_rx: mpsc::Receiver<CompositorCommand>, timing_metrics: Option<Arc<TimingMetrics>>) {
+        let mut next_tick = Instant::now() + TICK_DURATION;
+        let mut last_tick = Instant::now();
+
+        loop {
+            loop {
+                match command_rx.try_recv() {
+                    Ok(cmd) => self.handle(cmd, Instant::now()),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => return,
+                }
+            }
+
+            let now = Instant::now();
+            if self.effect.is_some() || self.pattern.is_some() {
+                let actual_tick_ms = now.duration_since(last_tick).as_secs_f64() * 1000.0;
+                if let Some(ref metrics) = timing_metrics {
+                    metrics.record_frame_tick(actual_tick_ms);
+                }
+                self.render(now);
+            }
+            self.outputs.send_blackouts();
+            last_tick = now;
+
+            let now = Instant::now();
+            if next_tick > now {
+                thread::sleep(next_tick - now);
+            }
+            next_tick += TICK_DURATION;
+        }
+    }
+
+    /// Apply `cmd` as if it arrived at `now`
+    pub fn handle(&mut self, cmd: CompositorCommand, now: Instant) {
+        match cmd {
+            CompositorCommand::Effects(cmd) => self.handle_effects(cmd, now),
+            CompositorCommand::Patterns(cmd) => self.handle_patterns(cmd, now),
+        }
+    }
+
+    fn handle_effects(&mut self, cmd: EngineCommand, now: Instant) {
+        match cmd {
+            EngineCommand::Start { config, boards, phase } => {
+                info!(
+                    effect = ?config.effect_type,
+                    bpm = config.bpm,
+                    boards = boards.len(),
+                    "Effects engine START"
+                );
+                self.start_effect(&boards, now, |keys| EffectSource::new(config, keys, phase, now));
+            }
+            EngineCommand::PlaySequence { clip, boards, phase } => {
+                info!(
+                    start_frame = clip.start_frame,
+                    end_frame = clip.end_frame,
+                    boards = boards.len(),
+                    "Effects engine SEQUENCE"
+                );
+                self.start_effect(&boards, now, |keys| EffectSource::sequence(clip, keys, phase, now));
+            }
+            EngineCommand::FadeOut { duration } => {
+                if self.effect.is_some() {
+                    info!(duration_ms = duration.as_millis() as u64, "Effects engine FADE OUT");
+                    self.effect_fade = Some(Fade::new(now, duration));
+                }
+            }
+            EngineCommand::Pause => self.pause_effect(true, now),
+            EngineCommand::Resume => self.pause_effect(false, now),
+            EngineCommand::Stop => {
+                info!("Effects engine STOP");
+                self.stop_effect();
+            }
+            EngineCommand::SetBpm { bpm } => {
+                if let Some(ref mut effect) = self.effect {
+                    effect.set_bpm(bpm);
+                }
+            }
+            EngineCommand::Tap { at } => {
+                let bpm = self.effect_taps.tap(at);
+                if let Some(ref mut effect) = self.effect {
+                    if let Some(bpm) = bpm {
+                        effect.set_bpm(bpm);
+                    }
+                    effect.align_to_beat(at);
+                }
+            }
+            EngineCommand::SetParams(params) => {
+                if let Some(ref mut effect) = self.effect {
+                    effect.set_params(params);
+                }
+            }
+        }
+    }
+
+    /// Hand `boards` to a new effect source, replacing the running one
+    fn start_effect(&mut self, boards: &[BoardTarget], now: Instant, source: impl FnOnce(Vec<OutputKey>) -> EffectSource) {
+        let keys = self.outputs.claim(boards, SourceKind::Effect);
+        if let Some(old) = self.effect.take() {
+            self.outputs.release(old.outputs(), &keys, SourceKind::Effect);
+        }
+        self.effect_fade = None;
+        if let Some(ref mut pattern) = self.pattern {
+            pattern.release(&keys);
+            if pattern.is_empty() {
+                self.pattern = None;
+            }
+        }
+        if !keys.is_empty() {
+            let mut effect = source(keys);
+            effect.set_frozen(now, self.effect_paused);
+            self.effect = Some(effect);
+        }
+    }
+
+    fn handle_patterns(&mut self, cmd: PatternCommand, now: Instant) {
+        match cmd {
+            PatternCommand::Start { sequence, palette, colour_mode, envelope, step_effect, boards, is_random, is_ping_pong, phase } => {
+                let targets: Vec<BoardTarget> = boards
+                    .values()
+                    .map(|b| BoardTarget {
+                        ip: b.ip.clone(),
+                        universe: b.universe,
+                        led_count: b.led_count,
+                    })
+                    .collect();
+                let keys = self.outputs.claim(&targets, SourceKind::Pattern);
+                if let Some(old) = self.pattern.take() {
+                    self.outputs.release(&old.outputs(), &keys, SourceKind::Pattern);
+                }
+                self.pattern_fade = None;
+                if let Some(ref mut effect) = self.effect {
+                    effect.release(&keys);
+                    if effect.outputs().is_empty() {
+                        self.effect = None;
+                    }
+                }
+
+                let board_keys: HashMap<String, OutputKey> = boards
+                    .into_iter()
+                    .filter_map(|(id, b)| {
+                        let key = OutputKey::for_board(&b.ip, b.universe).ok()?;
+                        keys.contains(&key).then_some((id, key))
+                    })
+                    .collect();
+                if !board_keys.is_empty() {
+                    let mut pattern = PatternSource::new(
+                        sequence,
+                        palette,
+                        colour_mode,
+                        envelope,
+                        step_effect,
+                        board_keys,
+                        is_random,
+                        is_ping_pong,
+                        phase,
+                        now,
+                    );
+                    pattern.set_frozen(now, self.pattern_paused);
+                    self.pattern = Some(pattern);
+                }
+            }
+            PatternCommand::FadeOut { duration } => {
+                if self.pattern.is_some() {
+                    info!(duration_ms = duration.as_millis() as u64, "Pattern engine FADE OUT");
+                    self.pattern_fade = Some(Fade::new(now, duration));
+                }
+            }
+            PatternCommand::Pause => self.pause_pattern(true, now),
+            PatternCommand::Resume => self.pause_pattern(false, now),
+            PatternCommand::Stop => self.stop_pattern(),
+            PatternCommand::SetBpm { bpm } => {
+                if let Some(ref mut pattern) = self.pattern {
+                    pattern.set_bpm(bpm);
+                }
+            }
+            PatternCommand::Tap { at } => match self.pattern {
+                Some(ref mut pattern) => pattern.tap(&mut self.pattern_taps, at),
+                None => {
+                    self.pattern_taps.tap(at);
+                }
+            },
+            PatternCommand::SetParams(params) => {
+                if let Some(ref mut pattern) = self.pattern {
+                    pattern.set_params(params);
+                }
+            }
+        }
+    }
+
+    fn pause_effect(&mut self, paused: bool, now: Instant) {
+        info!(paused = paused, "Effects engine clock");
+        self.effect_paused = paused;
+        if let Some(ref mut effect) = self.effect {
+            effect.set_frozen(now, paused);
+        }
+    }
+
+    fn pause_pattern(&mut self, paused: bool, now: Instant) {
+        info!(paused = paused, "Pattern engine clock");
+        self.pattern_paused = paused;
+        if let Some(ref mut pattern) = self.pattern {
+            pattern.set_frozen(now, paused);
+        }
+    }
+
+    fn stop_effect(&mut self) {
+        self.effect_fade = None;
+        if let Some(old) = self.effect.take() {
+            self.outputs.release(old.outputs(), &[], SourceKind::Effect);
+        }
+    }
+
+    fn stop_pattern(&mut self) {
+        self.pattern_fade = None;
+        if let Some(old) = self.pattern.take() {
+            self.outputs.release(&old.outputs(), &[], SourceKind::Pattern);
+        }
+    }
+
+    fn render(&mut self, now: Instant) {
+        self.draw(now, |output, dmx| {
+            if let Some(ref transport) = output.transport {
+                if let Ok(mut transport) = transport.lock() {
+                    let _ = transport.send_dmx_packet(dmx);
+                }
+            }
+        });
+    }
+
+    /// Draw one tick at `now` and return every output's frame
+    pub fn render_offline(&mut self, now: Instant) -> Vec<(u16, [u8; 512])> {
+        let mut frames = Vec::new();
+        self.draw(now, |output, dmx| frames.push((output.frame.universe(), *dmx)));
+        frames
+    }
+
+    /// Advance the sources to `now` and hand each output's levelled frame to `emit`
+    fn draw(&mut self, now: Instant, mut emit: impl FnMut(&Output, &[u8; 512])) {
+        if let Some(ref mut effect) = self.effect {
+            effect.render(now, &mut self.outputs);
+        }
+        if let Some(ref mut pattern) = self.pattern {
+            pattern.render(now, &mut self.outputs);
+        }
+
+        let effect_fade = self.effect_fade.as_ref().map(|f| f.level(now)).unwrap_or(1.0);
+        let pattern_fade = self.pattern_fade.as_ref().map(|f| f.level(now)).unwrap_or(1.0);
+        let effect_brightness = self.effect.as_ref().map(|e| e.brightness()).unwrap_or(1.0) * effect_fade;
+        let pattern_brightness = self.pattern.as_ref().map(|p| p.brightness()).unwrap_or(1.0) * pattern_fade;
+
+        for output in self.outputs.map.values() {
+            let brightness = match output.owner {
+                SourceKind::Effect => effect_brightness,
+                SourceKind::Pattern => pattern_brightness,
+            };
+            let mut dmx = *output.frame.dmx();
+            if brightness < 1.0 {
+                for v in dmx.iter_mut() {
+                    *v = (*v as f64 * brightness) as u8;
+                }
+            }
+            emit(output, &dmx);
+        }
+
+        if effect_fade <= 0.0 {
+            self.stop_effect();
+        }
+        if pattern_fade <= 0.0 {
+            self.stop_pattern();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_layout_matches_transport() {
+        let mut frame = LedFrame::new(1, 2);
+        frame.fill(10, 20, 30);
+        assert_eq!(&frame.dmx()[0..8], &[10, 20, 30, 0, 10, 20, 30, 0]);
+        assert_eq!(frame.dmx()[8], 0);
+
+        frame.set_leds(&[[1, 2, 3]]);
+        assert_eq!(&frame.dmx()[0..8], &[1, 2, 3, 0, 0, 0, 0, 0]);
+    }
+}
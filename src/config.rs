@@ -4,12 +4,16 @@ use std::fs;
 use std::io::Write;
 use std::path::PathBuf;
 
+use crate::effects::EffectType;
+
 #[derive(Debug, Clone)]
 pub struct StoragePaths {
     pub programs: PathBuf,
+    pub setlists: PathBuf,
     pub audio: PathBuf,
     pub presets: PathBuf,
     pub history: PathBuf,
+    pub revisions: PathBuf,
 }
 
 impl Default for StoragePaths {
@@ -18,6 +22,9 @@ impl Default for StoragePaths {
             programs: env::var("WLED_PROGRAMS_PATH")
                 .unwrap_or_else(|_| "programs".to_string())
                 .into(),
+            setlists: env::var("WLED_SETLISTS_PATH")
+                .unwrap_or_else(|_| "setlists".to_string())
+                .into(),
             audio: env::var("WLED_AUDIO_PATH")
                 .unwrap_or_else(|_| "audio".to_string())
                 .into(),
@@ -27,6 +34,9 @@ impl Default for StoragePaths {
             history: env::var("WLED_HISTORY_PATH")
                 .unwrap_or_else(|_| "history".to_string())
                 .into(),
+            revisions: env::var("WLED_REVISIONS_PATH")
+                .unwrap_or_else(|_| "revisions".to_string())
+                .into(),
         }
     }
 }
@@ -34,19 +44,23 @@ impl Default for StoragePaths {
 impl StoragePaths {
     pub fn init(&self) -> Result<(), Box<dyn std::error::Error>> {
         fs::create_dir_all(&self.programs)?;
+        fs::create_dir_all(&self.setlists)?;
         fs::create_dir_all(&self.audio)?;
         fs::create_dir_all(&self.presets)?;
         fs::create_dir_all(&self.history)?;
+        fs::create_dir_all(&self.revisions)?;
         tracing::info!("Storage paths initialized:");
         tracing::info!("  Programs: {:?}", self.programs);
+        tracing::info!("  Setlists: {:?}", self.setlists);
         tracing::info!("  Audio: {:?}", self.audio);
         tracing::info!("  Presets: {:?}", self.presets);
         tracing::info!("  History: {:?}", self.history);
+        tracing::info!("  Revisions: {:?}", self.revisions);
         Ok(())
     }
 
     pub fn is_available(&self) -> bool {
-        self.programs.exists() && self.audio.exists() && self.presets.exists() && self.history.exists()
+        self.programs.exists() && self.setlists.exists() && self.audio.exists() && self.presets.exists() && self.history.exists() && self.revisions.exists()
     }
 }
 
@@ -61,6 +75,10 @@ pub struct Config {
     pub effect_presets: Vec<EffectPreset>,
     #[serde(default)]
     pub pattern_presets: Vec<PatternPreset>,
+    #[serde(default)]
+    pub marker_mappings: Vec<MarkerMapping>,
+    #[serde(default)]
+    pub sequence_presets: Vec<SequencePreset>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
@@ -73,6 +91,8 @@ pub struct LoopyProConfig {
     pub mute_audio: bool,
     #[serde(default)]
     pub audio_sync_delay_ms: i64,
+    #[serde(default)]
+    pub addresses: LoopyProAddresses,
 }
 
 impl Default for LoopyProConfig {
@@ -82,6 +102,33 @@ impl Default for LoopyProConfig {
             port: default_loopy_port(),
             mute_audio: false,
             audio_sync_delay_ms: 0,
+            addresses: LoopyProAddresses::default(),
+        }
+    }
+}
+
+/// OSC addresses sent to Loopy Pro, with `{track}` standing for the program's track
+///
+/// Only `play` is known to work with Loopy Pro out of the box; the others follow
+/// the same pattern and may need remapping to the project's OSC bindings. An
+/// empty address turns that command off.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+#[serde(default)]
+pub struct LoopyProAddresses {
+    pub play: String,
+    pub pause: String,
+    pub resume: String,
+    /// Sent with the position in seconds as a float argument
+    pub seek: String,
+}
+
+impl Default for LoopyProAddresses {
+    fn default() -> Self {
+        Self {
+            play: "/Play/0:{track}".to_string(),
+            pause: "/Pause/0:{track}".to_string(),
+            resume: "/Resume/0:{track}".to_string(),
+            seek: "/Seek/0:{track}".to_string(),
         }
     }
 }
@@ -129,6 +176,37 @@ pub struct EffectPreset {
     pub color: [u8; 3],
 }
 
+/// Preset and targets for DAW markers, matched by marker name or MIDI note
+///
+/// Names match case-insensitively, either whole or as a prefix, so "Chorus"
+/// also catches "Chorus 2". Cues go to the program's default target when
+/// `targets` is empty.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct MarkerMapping {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub marker: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<u8>,
+    pub preset_name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<String>,
+}
+
+/// A frame range of an uploaded FSEQ file, played as a cue
+///
+/// Universe `n` takes channels `(n - 1) * 512 + 1` onwards, the E1.31 layout
+/// xLights uses by default. `end_frame` is exclusive; the clip runs to the
+/// end of the file when it is omitted.
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct SequencePreset {
+    pub name: String,
+    pub file: String,
+    #[serde(default)]
+    pub start_frame: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_frame: Option<u32>,
+}
+
 fn default_transition() -> Option<u8> {
     None
 }
@@ -193,6 +271,8 @@ pub enum PatternType {
     OutsideIn,
     CenterOut,
     Random,
+    /// Explicit board sets from the preset's `steps`
+    Custom,
 }
 
 impl Default for PatternType {
@@ -201,9 +281,209 @@ impl Default for PatternType {
     }
 }
 
+/// How a pattern picks colours from its palette
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ColourMode {
+    /// Every step uses the first colour
+    #[default]
+    Single,
+    /// Successive steps of a cycle take successive colours
+    PerStep,
+    /// The colour advances once per cycle (each ping-pong bounce, each random beat)
+    PerCycle,
+}
+
+/// Shape of a decay from full brightness towards black
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum DecayCurve {
+    /// The look of the original fixed patterns: a 1.0 / 0.4 / 0.1 trail, a
+    /// linear flash, and a wave release that drops by 0.6 every quarter of
+    /// the release time and cuts to black after the third drop
+    #[default]
+    Classic,
+    Linear,
+    Exponential,
+}
+
+/// Level reached by an exponential decay at the end of its span
+const EXPONENTIAL_FLOOR: f64 = 0.05;
+
+/// Trail of the classic curve; it stops after these steps
+const CLASSIC_TRAIL: [f64; 3] = [1.0, 0.4, 0.1];
+
+/// Per-drop factor of the classic wave release
+const CLASSIC_RELEASE_DROP: f64 = 0.6;
+
+impl DecayCurve {
+    /// Level at `progress` (0.0 - 1.0) through the decay; black from 1.0 on
+    pub fn level(&self, progress: f64) -> f64 {
+        if progress >= 1.0 {
+            return 0.0;
+        }
+        let progress = progress.max(0.0);
+        match self {
+            DecayCurve::Classic | DecayCurve::Linear => 1.0 - progress,
+            DecayCurve::Exponential => EXPONENTIAL_FLOOR.powf(progress),
+        }
+    }
+
+    /// Level of step `idx` behind the head of a `len` step trail
+    pub fn trail_level(&self, idx: usize, len: usize) -> f64 {
+        match self {
+            DecayCurve::Classic => CLASSIC_TRAIL.get(idx).copied().unwrap_or(0.0),
+            _ => self.level(idx as f64 / len as f64),
+        }
+    }
+
+    /// Level at `progress` through the release at the end of a wave
+    pub fn release_level(&self, progress: f64) -> f64 {
+        match self {
+            DecayCurve::Classic => {
+                let drops = (progress.max(0.0) * 4.0).floor() as i32;
+                if drops >= 3 {
+                    0.0
+                } else {
+                    CLASSIC_RELEASE_DROP.powi(drops)
+                }
+            }
+            _ => self.level(progress),
+        }
+    }
+}
+
+/// Brightness envelope of a pattern
+///
+/// Times are in milliseconds at the pattern's own tempo, so they stretch
+/// and shrink with tempo changes like the steps do.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+#[serde(default)]
+pub struct PatternEnvelope {
+    /// Fraction of the cycle the wave takes to cross all steps
+    pub wave_portion: f64,
+    /// Fade-in of a newly lit step or flash
+    pub attack_ms: u64,
+    /// Time at full level after the last step (or the flash) before the release
+    pub hold_ms: u64,
+    /// Fade to black at the end of a wave or flash
+    pub release_ms: u64,
+    /// Number of steps lit at once, counting the head
+    pub trail_length: usize,
+    /// Shape of the trail and of the release
+    pub decay: DecayCurve,
+}
+
+impl Default for PatternEnvelope {
+    fn default() -> Self {
+        Self {
+            wave_portion: 0.5,
+            attack_ms: 0,
+            hold_ms: 0,
+            release_ms: 120,
+            trail_length: 3,
+            decay: DecayCurve::Classic,
+        }
+    }
+}
+
+impl PatternEnvelope {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.wave_portion > 0.0 && self.wave_portion <= 1.0) {
+            return Err("wave_portion must be greater than 0 and at most 1".to_string());
+        }
+        if self.trail_length == 0 {
+            return Err("trail_length must be at least 1".to_string());
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct PatternPreset {
     pub name: String,
     pub pattern: PatternType,
     pub colour: [u8; 3],
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub colours: Vec<[u8; 3]>,
+    #[serde(default)]
+    pub colour_mode: ColourMode,
+    /// Step order for `PatternType::Custom`, e.g. `[["a", "d"], ["b"], ["c", "e"]]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<Vec<String>>,
+    #[serde(default)]
+    pub envelope: PatternEnvelope,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step_effect: Option<PatternStepEffect>,
+}
+
+impl PatternPreset {
+    /// Colours to cycle through; falls back to the single `colour`
+    pub fn palette(&self) -> Vec<[u8; 3]> {
+        if self.colours.is_empty() {
+            vec![self.colour]
+        } else {
+            self.colours.clone()
+        }
+    }
+
+    /// Check the preset against the members of the target it will run on
+    pub fn validate(&self, members: &[String]) -> Result<(), String> {
+        self.validate_steps(members)?;
+        self.envelope
+            .validate()
+            .map_err(|e| format!("Pattern '{}': {}", self.name, e))?;
+        if let Some(step_effect) = &self.step_effect {
+            step_effect
+                .effect_type()
+                .map_err(|e| format!("Pattern '{}': {}", self.name, e))?;
+        }
+        Ok(())
+    }
+
+    /// Custom patterns need at least one step, no empty steps, and only boards from the target
+    fn validate_steps(&self, members: &[String]) -> Result<(), String> {
+        if self.pattern != PatternType::Custom {
+            return Ok(());
+        }
+        if self.steps.is_empty() {
+            return Err(format!("Custom pattern '{}' has no steps", self.name));
+        }
+        for (idx, step) in self.steps.iter().enumerate() {
+            if step.is_empty() {
+                return Err(format!("Custom pattern '{}': step {} is empty", self.name, idx + 1));
+            }
+            if let Some(unknown) = step.iter().find(|id| !members.contains(id)) {
+                return Err(format!(
+                    "Custom pattern '{}': step {} references '{}', which is not in the target",
+                    self.name,
+                    idx + 1,
+                    unknown
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Effect a pattern plays on each step's boards instead of a flat colour
+///
+/// The effect takes the step's palette colour and runs for the length of the step.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct PatternStepEffect {
+    pub effect_type: String,
+    /// 0.0 - 1.0; the effect's own default when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub intensity: Option<f64>,
+}
+
+impl PatternStepEffect {
+    pub fn effect_type(&self) -> Result<EffectType, String> {
+        if let Some(i) = self.intensity {
+            if !(0.0..=1.0).contains(&i) {
+                return Err(format!("step effect intensity must be between 0 and 1, got {}", i));
+            }
+        }
+        self.effect_type.parse()
+    }
 }
@@ -1,14 +1,17 @@
 use std::collections::HashMap;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::mpsc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::config::PatternType;
+use crate::compositor::SourceKind;
+use crate::config::{ColourMode, PatternEnvelope, PatternStepEffect, PatternType};
 use crate::effects_engine::{BoardTarget, EffectConfig, EffectsEngine, EngineCommand};
+use crate::fseq::SequenceClip;
 use crate::pattern::generate_sequence;
 use crate::pattern_engine::{BoardInfo, PatternCommand, PatternEngine};
+use crate::program::CueRelease;
 use crate::timing_metrics::TimingMetrics;
 
 const COARSE_THRESHOLD: Duration = Duration::from_millis(100);
@@ -19,7 +22,11 @@ const FINE_SLEEP: Duration = Duration::from_millis(5);
 #[derive(Debug, Clone)]
 pub struct PatternCueConfig {
     pub pattern_type: PatternType,
-    pub color: [u8; 3],
+    pub custom_steps: Vec<Vec<String>>,
+    pub envelope: PatternEnvelope,
+    pub step_effect: Option<PatternStepEffect>,
+    pub palette: Vec<[u8; 3]>,
+    pub colour_mode: ColourMode,
     pub member_ids: Vec<String>,
     pub board_info: HashMap<String, BoardInfo>,
     pub bpm: f64,
@@ -33,25 +40,237 @@ pub enum CueType {
         boards: Vec<BoardTarget>,
     },
     Pattern(PatternCueConfig),
+    /// Frames of an FSEQ file, played through the effect source
+    Sequence {
+        clip: SequenceClip,
+        boards: Vec<BoardTarget>,
+    },
+}
+
+impl CueType {
+    fn kind(&self) -> SourceKind {
+        match self {
+            CueType::Effect { .. } | CueType::Sequence { .. } => SourceKind::Effect,
+            CueType::Pattern(_) => SourceKind::Pattern,
+        }
+    }
+
+    fn board_ips(&self) -> Vec<String> {
+        match self {
+            CueType::Effect { boards, .. } | CueType::Sequence { boards, .. } => {
+                boards.iter().map(|b| b.ip.clone()).collect()
+            }
+            CueType::Pattern(pcfg) => pcfg.board_info.values().map(|b| b.ip.clone()).collect(),
+        }
+    }
+
+    /// The same look on only the boards in `ips`
+    fn restricted_to(&self, ips: &[String]) -> CueType {
+        let mut cue_type = self.clone();
+        match cue_type {
+            CueType::Effect { ref mut boards, .. } | CueType::Sequence { ref mut boards, .. } => {
+                boards.retain(|b| ips.contains(&b.ip))
+            }
+            CueType::Pattern(ref mut pcfg) => {
+                pcfg.board_info.retain(|_, b| ips.contains(&b.ip));
+                let board_info = &pcfg.board_info;
+                pcfg.member_ids.retain(|id| board_info.contains_key(id));
+            }
+        }
+        cue_type
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct ScheduledCue {
     pub fire_at: Duration,
+    /// Seconds the look has already been running when it fires, for looks
+    /// that started before the playback start position
+    pub phase: f64,
     pub label: String,
     pub cue_type: CueType,
+    pub release: Option<ScheduledRelease>,
+}
+
+/// When and how a cue lets go of its boards
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduledRelease {
+    pub at: Duration,
+    pub action: CueRelease,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum CueEvent {
+    // Releases sort first so a cue firing at the same moment wins
+    Release,
+    Fire,
+}
+
+/// Which cue's look each board shows
+///
+/// Follows the compositor: a new effect replaces the running effect on every
+/// board, and a new pattern the running pattern.
+#[derive(Debug, Default)]
+struct Looks {
+    owners: HashMap<String, usize>,
+    /// Cues whose looks each cue covered when it fired
+    previous: HashMap<usize, Vec<usize>>,
+}
+
+impl Looks {
+    fn fire(&mut self, cues: &[ScheduledCue], index: usize) {
+        let ips = cues[index].cue_type.board_ips();
+        let mut previous: Vec<usize> = ips.iter().filter_map(|ip| self.owners.get(ip).copied()).collect();
+        previous.sort_unstable();
+        previous.dedup();
+        self.previous.insert(index, previous);
+        self.take(cues, index, &ips);
+    }
+
+    fn take(&mut self, cues: &[ScheduledCue], index: usize, ips: &[String]) {
+        let kind = cues[index].cue_type.kind();
+        self.owners.retain(|_, owner| cues[*owner].cue_type.kind() != kind);
+        for ip in ips {
+            self.owners.insert(ip.clone(), index);
+        }
+    }
+
+    fn owned_by(&self, index: usize) -> Vec<String> {
+        self.owners
+            .iter()
+            .filter(|(_, owner)| **owner == index)
+            .map(|(ip, _)| ip.clone())
+            .collect()
+    }
+
+    fn clear(&mut self, index: usize) {
+        self.owners.retain(|_, owner| *owner != index);
+    }
+
+    /// Looks to bring back when cue `index` releases, each limited to the
+    /// boards that nothing newer has taken over since
+    fn restore(&mut self, cues: &[ScheduledCue], index: usize) -> Vec<(usize, Vec<String>)> {
+        let previous = self.previous.get(&index).cloned().unwrap_or_default();
+        let mut restored = Vec::new();
+        for prev in previous {
+            let ips: Vec<String> = cues[prev]
+                .cue_type
+                .board_ips()
+                .into_iter()
+                .filter(|ip| match self.owners.get(ip) {
+                    None => true,
+                    Some(owner) => *owner == index || *owner == prev,
+                })
+                .collect();
+            if !ips.is_empty() {
+                self.take(cues, prev, &ips);
+                restored.push((prev, ips));
+            }
+        }
+        restored
+    }
+}
+
+/// Every fire and release of `cues`, in the order they play
+fn timeline(cues: &[ScheduledCue]) -> Vec<(Duration, CueEvent, usize)> {
+    let mut events = Vec::new();
+    for (index, cue) in cues.iter().enumerate() {
+        events.push((cue.fire_at, CueEvent::Fire, index));
+        if let Some(ref release) = cue.release {
+            events.push((release.at, CueEvent::Release, index));
+        }
+    }
+    events.sort();
+    events
+}
+
+/// A schedule played against the caller's clock instead of the wall clock
+///
+/// For offline rendering: no pauses, loops or drift, just the same fires and
+/// releases the scheduler thread would send.
+pub struct CueSequence {
+    cues: Vec<ScheduledCue>,
+    events: Vec<(Duration, CueEvent, usize)>,
+    next: usize,
+    looks: Looks,
+}
+
+impl CueSequence {
+    pub fn new(cues: Vec<ScheduledCue>) -> Self {
+        Self {
+            events: timeline(&cues),
+            cues,
+            next: 0,
+            looks: Looks::default(),
+        }
+    }
+
+    /// Time of the last fire or release
+    pub fn end(&self) -> Duration {
+        self.events.last().map(|e| e.0).unwrap_or_default()
+    }
+
+    /// Play the next event if it is due by `until`; returns when it was due
+    pub fn step(&mut self, until: Duration, effects_engine: &EffectsEngine, pattern_engine: &PatternEngine) -> Option<Duration> {
+        let &(at, event, index) = self.events.get(self.next).filter(|e| e.0 <= until)?;
+        self.next += 1;
+
+        let cue = &self.cues[index];
+        match event {
+            CueEvent::Fire => {
+                CueScheduler::start_look(&cue.cue_type, cue.phase, effects_engine, pattern_engine);
+                self.looks.fire(&self.cues, index);
+            }
+            CueEvent::Release => {
+                if let Some(release) = cue.release {
+                    CueScheduler::release(&self.cues, index, release.action, &mut self.looks, effects_engine, pattern_engine);
+                }
+            }
+        }
+        Some(at)
+    }
 }
 
 pub enum SchedulerCommand {
     Start {
         cues: Vec<ScheduledCue>,
-        playback_start: Instant,
+        run: u64,
     },
 }
 
+/// Time base of the running schedule; pausing freezes it
+#[derive(Debug, Clone, Copy)]
+struct ScheduleClock {
+    anchor: Instant,
+    paused_at: Option<Instant>,
+}
+
+impl ScheduleClock {
+    fn elapsed(&self, now: Instant) -> Duration {
+        self.paused_at
+            .unwrap_or(now)
+            .saturating_duration_since(self.anchor)
+    }
+
+    /// Move the clock from the end of `span` back to its start
+    fn wrap(&mut self, span: LoopSpan) {
+        self.anchor += span.end.saturating_sub(span.start);
+    }
+}
+
+/// Part of the schedule to repeat until the loop is released
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoopSpan {
+    pub start: Duration,
+    pub end: Duration,
+}
+
 pub struct CueScheduler {
     command_tx: mpsc::Sender<SchedulerCommand>,
-    stop_flag: Arc<AtomicBool>,
+    /// Id of the run allowed to fire; bumping it stops the current run
+    run: Arc<AtomicU64>,
+    clock: Arc<Mutex<ScheduleClock>>,
+    armed_loop: Arc<Mutex<Option<LoopSpan>>>,
 }
 
 impl CueScheduler {
@@ -62,149 +281,233 @@ impl CueScheduler {
         on_complete: Option<Arc<dyn Fn() + Send + Sync>>,
     ) -> Self {
         let (command_tx, command_rx) = mpsc::channel();
-        let stop_flag = Arc::new(AtomicBool::new(false));
-        let stop_flag_clone = stop_flag.clone();
+        let run = Arc::new(AtomicU64::new(0));
+        let clock = Arc::new(Mutex::new(ScheduleClock {
+            anchor: Instant::now(),
+            paused_at: None,
+        }));
+        let armed_loop = Arc::new(Mutex::new(None));
+        let run_clone = run.clone();
+        let clock_clone = clock.clone();
+        let armed_loop_clone = armed_loop.clone();
         let on_complete_clone = on_complete.clone();
 
         thread::spawn(move || {
-            Self::run_scheduler(command_rx, effects_engine, pattern_engine, stop_flag_clone, timing_metrics, on_complete_clone);
+            Self::run_scheduler(
+                command_rx,
+                effects_engine,
+                pattern_engine,
+                run_clone,
+                clock_clone,
+                armed_loop_clone,
+                timing_metrics,
+                on_complete_clone,
+            );
         });
 
         Self {
             command_tx,
-            stop_flag,
+            run,
+            clock,
+            armed_loop,
         }
     }
 
-    pub fn start(&self, cues: Vec<ScheduledCue>, playback_start: Instant) -> Result<(), String> {
-        self.stop_flag.store(false, Ordering::Relaxed);
+    /// Run `cues` against a clock that reads zero at `playback_start`
+    pub fn start(&self, cues: Vec<ScheduledCue>, playback_start: Instant, paused: bool) -> Result<(), String> {
+        *self.lock_clock() = ScheduleClock {
+            anchor: playback_start,
+            paused_at: paused.then_some(playback_start),
+        };
+        *self.lock_loop() = None;
+        let run = self.run.fetch_add(1, Ordering::SeqCst) + 1;
         self.command_tx
-            .send(SchedulerCommand::Start {
-                cues,
-                playback_start,
-            })
+            .send(SchedulerCommand::Start { cues, run })
             .map_err(|e| e.to_string())
     }
 
     pub fn stop(&self) {
-        self.stop_flag.store(true, Ordering::Relaxed);
+        self.run.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Hold the schedule; cues due at the current position still fire
+    pub fn pause(&self) {
+        let mut clock = self.lock_clock();
+        if clock.paused_at.is_none() {
+            clock.paused_at = Some(Instant::now());
+        }
+    }
+
+    pub fn resume(&self) {
+        let mut clock = self.lock_clock();
+        if let Some(paused_at) = clock.paused_at.take() {
+            clock.anchor += Instant::now().saturating_duration_since(paused_at);
+        }
+    }
+
+    /// Time into the running schedule, not counting pauses
+    pub fn elapsed(&self) -> Duration {
+        self.lock_clock().elapsed(Instant::now())
+    }
+
+    /// Repeat `span` once the schedule reaches its end, until released
+    pub fn arm_loop(&self, span: LoopSpan) -> Result<(), String> {
+        if self.elapsed() >= span.end {
+            return Err("Playback is already past the end of the loop".to_string());
+        }
+        *self.lock_loop() = Some(span);
+        Ok(())
+    }
+
+    /// Let the current pass of the loop play out and carry on from its end
+    pub fn release_loop(&self) {
+        *self.lock_loop() = None;
+    }
+
+    fn lock_clock(&self) -> std::sync::MutexGuard<'_, ScheduleClock> {
+        self.clock.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_loop(&self) -> std::sync::MutexGuard<'_, Option<LoopSpan>> {
+        self.armed_loop.lock().unwrap_or_else(|e| e.into_inner())
     }
 
+    /// Wait for the schedule clock to reach `at`; false if `interrupted` first
+    fn wait_until(clock: &Mutex<ScheduleClock>, at: Duration, interrupted: impl Fn() -> bool) -> bool {
+        loop {
+            let schedule_clock = *clock.lock().unwrap_or_else(|e| e.into_inner());
+            let elapsed = schedule_clock.elapsed(Instant::now());
+            if elapsed >= at {
+                return true;
+            }
+
+            let remaining = at - elapsed;
+
+            if schedule_clock.paused_at.is_some() || remaining > COARSE_THRESHOLD {
+                thread::sleep(COARSE_SLEEP);
+            } else if remaining > FINE_THRESHOLD {
+                thread::sleep(FINE_SLEEP);
+            } else {
+                let target_time = schedule_clock.anchor + at;
+                while Instant::now() < target_time {
+                    std::hint::spin_loop();
+                }
+                return !interrupted();
+            }
+
+            if interrupted() {
+                return false;
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn run_scheduler(
         command_rx: mpsc::Receiver<SchedulerCommand>,
         effects_engine: Arc<EffectsEngine>,
         pattern_engine: Arc<PatternEngine>,
-        stop_flag: Arc<AtomicBool>,
+        run: Arc<AtomicU64>,
+        clock: Arc<Mutex<ScheduleClock>>,
+        armed_loop: Arc<Mutex<Option<LoopSpan>>>,
         timing_metrics: Option<Arc<TimingMetrics>>,
         on_complete: Option<Arc<dyn Fn() + Send + Sync>>,
     ) {
+        let read_clock = || *clock.lock().unwrap_or_else(|e| e.into_inner());
+        let read_loop = || *armed_loop.lock().unwrap_or_else(|e| e.into_inner());
+
         loop {
             match command_rx.recv() {
-                Ok(SchedulerCommand::Start {
-                    cues,
-                    playback_start,
-                }) => {
-                    let mut sorted_cues = cues;
-                    sorted_cues.sort_by_key(|c| c.fire_at);
+                Ok(SchedulerCommand::Start { cues, run: run_id }) => {
+                    let stopped = || run.load(Ordering::SeqCst) != run_id;
+                    let events = timeline(&cues);
 
                     println!(
-                        "🎬 Cue scheduler: {} cues loaded (anchor age: {:.1}ms)",
-                        sorted_cues.len(),
-                        playback_start.elapsed().as_secs_f64() * 1000.0
+                        "🎬 Cue scheduler: {} cues loaded, {} with releases (anchor age: {:.1}ms)",
+                        cues.len(),
+                        events.len() - cues.len(),
+                        read_clock().anchor.elapsed().as_secs_f64() * 1000.0
                     );
 
-                    'cue_loop: for cue in sorted_cues {
-                        if stop_flag.load(Ordering::Relaxed) {
+                    let mut looks = Looks::default();
+
+                    let mut next = 0;
+                    loop {
+                        if stopped() {
                             break;
                         }
 
-                        let target_time = playback_start + cue.fire_at;
-
-                        loop {
-                            let now = Instant::now();
-                            if now >= target_time {
-                                break;
-                            }
-
-                            let remaining = target_time - now;
-
-                            if remaining > COARSE_THRESHOLD {
-                                thread::sleep(COARSE_SLEEP);
-                                if stop_flag.load(Ordering::Relaxed) {
-                                    break 'cue_loop;
-                                }
-                            } else if remaining > FINE_THRESHOLD {
-                                thread::sleep(FINE_SLEEP);
-                                if stop_flag.load(Ordering::Relaxed) {
-                                    break 'cue_loop;
-                                }
-                            } else {
-                                break;
+                        let upcoming = events.get(next).map(|e| e.0);
+                        if let Some(span) = read_loop().filter(|l| upcoming.is_none_or(|at| at >= l.end)) {
+                            // Everything before the loop end has fired; go round again
+                            if Self::wait_until(&clock, span.end, || stopped() || read_loop() != Some(span)) {
+                                clock.lock().unwrap_or_else(|e| e.into_inner()).wrap(span);
+                                next = events.partition_point(|e| e.0 < span.start);
+                                println!(
+                                    "🔁 Loop: {:.2}s → {:.2}s",
+                                    span.end.as_secs_f64(),
+                                    span.start.as_secs_f64()
+                                );
                             }
+                            continue;
                         }
 
-                        while Instant::now() < target_time {
-                            std::hint::spin_loop();
-                        }
-
-                        if stop_flag.load(Ordering::Relaxed) {
-                            break 'cue_loop;
+                        let Some(&(at, event, index)) = events.get(next) else {
+                            break;
+                        };
+                        // A loop armed meanwhile may end before this event
+                        if !Self::wait_until(&clock, at, || stopped() || read_loop().is_some_and(|l| l.end < at)) {
+                            continue;
                         }
+                        next += 1;
 
-                        let drift_ms = (Instant::now() - target_time).as_secs_f64() * 1000.0;
+                        let cue = &cues[index];
 
-                        if let Some(ref metrics) = timing_metrics {
-                            metrics.record_cue_drift(drift_ms, &cue.label);
-                        }
+                        let drift_ms = read_clock().elapsed(Instant::now()).saturating_sub(at).as_secs_f64() * 1000.0;
 
-                        match &cue.cue_type {
-                            CueType::Pattern(pcfg) => {
+                        if event == CueEvent::Release {
+                            if let Some(ref release) = cue.release {
                                 println!(
-                                    "🌊 PATTERN '{}' fired @ {:.2}s (drift: {:.1}ms)",
+                                    "🔚 RELEASE '{}' ({:?}) @ {:.2}s (drift: {:.1}ms)",
                                     cue.label,
-                                    cue.fire_at.as_secs_f64(),
+                                    release.action,
+                                    at.as_secs_f64(),
                                     drift_ms
                                 );
-
-                                let _ = effects_engine.send_command(EngineCommand::Stop);
-
-                                let sequence = generate_sequence(
-                                    &pcfg.member_ids,
-                                    &pcfg.pattern_type,
-                                    pcfg.bpm,
-                                    pcfg.sync_rate,
-                                );
-
-                                let is_random = pcfg.pattern_type == PatternType::Random;
-                                let is_ping_pong = pcfg.pattern_type == PatternType::PingPong;
-
-                                let _ = pattern_engine.send_command(PatternCommand::Start {
-                                    sequence,
-                                    color: pcfg.color,
-                                    boards: pcfg.board_info.clone(),
-                                    is_random,
-                                    is_ping_pong,
-                                });
+                                Self::release(&cues, index, release.action, &mut looks, &effects_engine, &pattern_engine);
                             }
-                            CueType::Effect { config, boards } => {
-                                println!(
-                                    "🎯 CUE '{}' fired @ {:.2}s (drift: {:.1}ms)",
-                                    cue.label,
-                                    cue.fire_at.as_secs_f64(),
-                                    drift_ms
-                                );
+                            continue;
+                        }
 
-                                let _ = pattern_engine.send_command(PatternCommand::Stop);
+                        if let Some(ref metrics) = timing_metrics {
+                            metrics.record_cue_drift(drift_ms, &cue.label);
+                        }
 
-                                let _ = effects_engine.send_command(EngineCommand::Start {
-                                    config: config.clone(),
-                                    boards: boards.clone(),
-                                });
-                            }
+                        match &cue.cue_type {
+                            CueType::Pattern(_) => println!(
+                                "🌊 PATTERN '{}' fired @ {:.2}s (drift: {:.1}ms)",
+                                cue.label,
+                                cue.fire_at.as_secs_f64(),
+                                drift_ms
+                            ),
+                            CueType::Effect { .. } => println!(
+                                "🎯 CUE '{}' fired @ {:.2}s (drift: {:.1}ms)",
+                                cue.label,
+                                cue.fire_at.as_secs_f64(),
+                                drift_ms
+                            ),
+                            CueType::Sequence { .. } => println!(
+                                "🎞️ SEQUENCE '{}' fired @ {:.2}s (drift: {:.1}ms)",
+                                cue.label,
+                                cue.fire_at.as_secs_f64(),
+                                drift_ms
+                            ),
                         }
+
+                        Self::start_look(&cue.cue_type, cue.phase, &effects_engine, &pattern_engine);
+                        looks.fire(&cues, index);
                     }
 
-                    if stop_flag.load(Ordering::Relaxed) {
+                    if stopped() {
                         println!("⏹️ Cue scheduler: stopped");
                     } else {
                         println!("✅ All cues fired");
@@ -217,4 +520,199 @@ impl CueScheduler {
             }
         }
     }
+
+    fn start_look(cue_type: &CueType, phase: f64, effects_engine: &EffectsEngine, pattern_engine: &PatternEngine) {
+        match cue_type {
+            CueType::Pattern(pcfg) => {
+                let sequence = generate_sequence(
+                    &pcfg.member_ids,
+                    &pcfg.pattern_type,
+                    &pcfg.custom_steps,
+                    pcfg.bpm,
+                    pcfg.sync_rate,
+                );
+
+                let is_random = pcfg.pattern_type == PatternType::Random;
+                let is_ping_pong = pcfg.pattern_type == PatternType::PingPong;
+
+                let _ = pattern_engine.send_command(PatternCommand::Start {
+                    sequence,
+                    palette: pcfg.palette.clone(),
+                    colour_mode: pcfg.colour_mode,
+                    envelope: pcfg.envelope.clone(),
+                    step_effect: pcfg.step_effect.clone(),
+                    boards: pcfg.board_info.clone(),
+                    is_random,
+                    is_ping_pong,
+                    phase,
+                });
+            }
+            CueType::Effect { config, boards } => {
+                let _ = effects_engine.send_command(EngineCommand::Start {
+                    config: config.clone(),
+                    boards: boards.clone(),
+                    phase,
+                });
+            }
+            CueType::Sequence { clip, boards } => {
+                let _ = effects_engine.send_command(EngineCommand::PlaySequence {
+                    clip: clip.clone(),
+                    boards: boards.clone(),
+                    phase,
+                });
+            }
+        }
+    }
+
+    /// End cue `index`'s look, unless newer cues have taken all its boards
+    fn release(
+        cues: &[ScheduledCue],
+        index: usize,
+        action: CueRelease,
+        looks: &mut Looks,
+        effects_engine: &EffectsEngine,
+        pattern_engine: &PatternEngine,
+    ) {
+        if looks.owned_by(index).is_empty() {
+            return;
+        }
+
+        if action == CueRelease::Restore {
+            let released_at = cues[index].release.map(|r| r.at).unwrap_or_default();
+            for (prev, ips) in looks.restore(cues, index) {
+                println!("  ↩️ Restoring '{}' on {} boards", cues[prev].label, ips.len());
+                // Pick the look up where it would be had it kept running
+                let phase = cues[prev].phase + released_at.saturating_sub(cues[prev].fire_at).as_secs_f64();
+                Self::start_look(&cues[prev].cue_type.restricted_to(&ips), phase, effects_engine, pattern_engine);
+            }
+            if looks.owned_by(index).is_empty() {
+                return;
+            }
+        }
+
+        let kind = cues[index].cue_type.kind();
+        match action {
+            CueRelease::Fade { fade_ms } => {
+                let duration = Duration::from_millis(fade_ms);
+                let _ = match kind {
+                    SourceKind::Effect => effects_engine.send_command(EngineCommand::FadeOut { duration }),
+                    SourceKind::Pattern => pattern_engine.send_command(PatternCommand::FadeOut { duration }),
+                };
+            }
+            CueRelease::Blackout | CueRelease::Restore => {
+                let _ = match kind {
+                    SourceKind::Effect => effects_engine.send_command(EngineCommand::Stop),
+                    SourceKind::Pattern => pattern_engine.send_command(PatternCommand::Stop),
+                };
+            }
+        }
+        looks.clear(index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::effects::EffectType;
+
+    fn effect_cue(ips: &[&str]) -> ScheduledCue {
+        ScheduledCue {
+            fire_at: Duration::ZERO,
+            phase: 0.0,
+            label: "effect".to_string(),
+            cue_type: CueType::Effect {
+                config: EffectConfig {
+                    effect_type: EffectType::Solid,
+                    bpm: 120.0,
+                    color: [255, 0, 0],
+                    sync_rate: 1.0,
+                },
+                boards: ips
+                    .iter()
+                    .map(|ip| BoardTarget {
+                        ip: ip.to_string(),
+                        universe: 1,
+                        led_count: 60,
+                    })
+                    .collect(),
+            },
+            release: None,
+        }
+    }
+
+    fn pattern_cue(ips: &[&str]) -> ScheduledCue {
+        let board_info: HashMap<String, BoardInfo> = ips
+            .iter()
+            .map(|ip| {
+                (
+                    ip.to_string(),
+                    BoardInfo {
+                        ip: ip.to_string(),
+                        universe: 1,
+                        led_count: 60,
+                    },
+                )
+            })
+            .collect();
+        ScheduledCue {
+            fire_at: Duration::ZERO,
+            phase: 0.0,
+            label: "pattern".to_string(),
+            cue_type: CueType::Pattern(PatternCueConfig {
+                pattern_type: PatternType::Wave,
+                custom_steps: vec![],
+                envelope: PatternEnvelope::default(),
+                step_effect: None,
+                palette: vec![[0, 0, 255]],
+                colour_mode: ColourMode::Single,
+                member_ids: ips.iter().map(|ip| ip.to_string()).collect(),
+                board_info,
+                bpm: 120.0,
+                sync_rate: 1.0,
+            }),
+            release: None,
+        }
+    }
+
+    #[test]
+    fn test_newer_effect_takes_over_every_board() {
+        let cues = vec![effect_cue(&["a", "b"]), effect_cue(&["a"])];
+        let mut looks = Looks::default();
+        looks.fire(&cues, 0);
+        looks.fire(&cues, 1);
+
+        assert!(looks.owned_by(0).is_empty());
+        assert_eq!(looks.owned_by(1), vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn test_restore_brings_back_covered_look() {
+        let cues = vec![effect_cue(&["a", "b"]), pattern_cue(&["b"]), pattern_cue(&["c"])];
+        let mut looks = Looks::default();
+        looks.fire(&cues, 0);
+        looks.fire(&cues, 1);
+
+        let restored = looks.restore(&cues, 1);
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].0, 0);
+        assert!(looks.owned_by(1).is_empty());
+
+        // Nothing was covered by the third cue
+        looks.fire(&cues, 2);
+        assert!(looks.restore(&cues, 2).is_empty());
+    }
+
+    #[test]
+    fn test_loop_wrap_rewinds_paused_clock() {
+        let t0 = Instant::now();
+        let mut clock = ScheduleClock {
+            anchor: t0,
+            paused_at: Some(t0 + Duration::from_secs(10)),
+        };
+        clock.wrap(LoopSpan {
+            start: Duration::from_secs(4),
+            end: Duration::from_secs(10),
+        });
+        assert_eq!(clock.elapsed(Instant::now()), Duration::from_secs(4));
+    }
 }
@@ -0,0 +1,273 @@
+use serde::Serialize;
+
+use crate::audio_analysis::Section;
+use crate::config::Config;
+use crate::effects::EffectType;
+use crate::program::{Cue, CueRelease};
+
+const LOW_ENERGY: f64 = 0.4;
+const HIGH_ENERGY: f64 = 0.75;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EnergyLevel {
+    Low,
+    Medium,
+    High,
+}
+
+impl EnergyLevel {
+    fn from_energy(energy: f64) -> Self {
+        if energy < LOW_ENERGY {
+            EnergyLevel::Low
+        } else if energy < HIGH_ENERGY {
+            EnergyLevel::Medium
+        } else {
+            EnergyLevel::High
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SuggestedSection {
+    pub start: f64,
+    pub end: f64,
+    pub energy: f64,
+    pub level: EnergyLevel,
+}
+
+/// Draft cue list for review; never written to the program directly
+#[derive(Debug, Clone, Serialize)]
+pub struct CueSuggestion {
+    pub target: String,
+    pub sections: Vec<SuggestedSection>,
+    pub cues: Vec<Cue>,
+}
+
+/// Preset names from the config, bucketed by how intense they look
+struct PresetPools {
+    ambient: Vec<String>,
+    intense: Vec<String>,
+    patterns: Vec<String>,
+    off: Option<String>,
+}
+
+impl PresetPools {
+    fn from_config(config: &Config) -> Self {
+        let mut pools = PresetPools {
+            ambient: Vec::new(),
+            intense: Vec::new(),
+            patterns: config.pattern_presets.iter().map(|p| p.name.clone()).collect(),
+            off: None,
+        };
+
+        for preset in &config.effect_presets {
+            let Ok(effect_type) = preset.effect_type.parse::<EffectType>() else {
+                continue;
+            };
+            match effect_type {
+                EffectType::Solid if preset.color == [0, 0, 0] => {
+                    pools.off.get_or_insert_with(|| preset.name.clone());
+                }
+                EffectType::Strobe | EffectType::Flash | EffectType::Bursts | EffectType::Lightning => {
+                    pools.intense.push(preset.name.clone())
+                }
+                _ => pools.ambient.push(preset.name.clone()),
+            }
+        }
+
+        pools
+    }
+
+    /// Pick a preset for a section, rotating through each pool so
+    /// consecutive sections of the same level don't repeat a look
+    fn pick(&self, level: EnergyLevel, counter: usize) -> Option<&String> {
+        let pool: Vec<&String> = match level {
+            EnergyLevel::Low => self.ambient.iter().collect(),
+            EnergyLevel::Medium => {
+                if self.patterns.is_empty() {
+                    self.ambient.iter().collect()
+                } else {
+                    self.patterns.iter().collect()
+                }
+            }
+            EnergyLevel::High => self.intense.iter().chain(self.patterns.iter()).collect(),
+        };
+
+        let pool = if pool.is_empty() {
+            self.ambient.iter().chain(self.intense.iter()).chain(self.patterns.iter()).collect()
+        } else {
+            pool
+        };
+
+        if pool.is_empty() {
+            None
+        } else {
+            Some(pool[counter % pool.len()])
+        }
+    }
+}
+
+/// Snap a time to the nearest beat of the program's grid
+fn quantize(time: f64, bpm: Option<f64>, grid_offset: f64) -> f64 {
+    match bpm {
+        Some(bpm) if bpm > 0.0 => {
+            let beat = 60.0 / bpm;
+            let snapped = grid_offset + ((time - grid_offset) / beat).round() * beat;
+            (snapped.max(0.0) * 1000.0).round() / 1000.0
+        }
+        _ => time,
+    }
+}
+
+pub fn suggest_cues(
+    sections: &[Section],
+    config: &Config,
+    target: &str,
+    bpm: Option<f64>,
+    grid_offset: f64,
+) -> CueSuggestion {
+    let pools = PresetPools::from_config(config);
+
+    let mut suggested_sections = Vec::new();
+    let mut cues = Vec::new();
+    let mut counters = [0usize; 3];
+
+    for (idx, section) in sections.iter().enumerate() {
+        let level = EnergyLevel::from_energy(section.energy);
+        suggested_sections.push(SuggestedSection {
+            start: section.start,
+            end: section.end,
+            energy: section.energy,
+            level,
+        });
+
+        let counter = &mut counters[level as usize];
+        if let Some(preset_name) = pools.pick(level, *counter) {
+            *counter += 1;
+            let time = if idx == 0 { 0.0 } else { quantize(section.start, bpm, grid_offset) };
+            cues.push(Cue {
+                time,
+                position: None,
+                label: format!("Section {} - {:?}", idx + 1, level),
+                targets: vec![target.to_string()],
+                preset_name: preset_name.clone(),
+                sync_rate: 1.0,
+                duration: None,
+                release: CueRelease::default(),
+                wait: None,
+                follow: None,
+            });
+        }
+    }
+
+    if let (Some(off), Some(last)) = (&pools.off, sections.last()) {
+        cues.push(Cue {
+            time: last.end,
+            position: None,
+            label: "end".to_string(),
+            targets: vec![target.to_string()],
+            preset_name: off.clone(),
+            sync_rate: 1.0,
+            duration: None,
+            release: CueRelease::default(),
+            wait: None,
+            follow: None,
+        });
+    }
+
+    CueSuggestion {
+        target: target.to_string(),
+        sections: suggested_sections,
+        cues,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(effects: serde_json::Value, patterns: serde_json::Value) -> Config {
+        serde_json::from_value(serde_json::json!({
+            "boards": [{ "id": "left", "ip": "10.0.0.1" }],
+            "effect_presets": effects,
+            "pattern_presets": patterns
+        }))
+        .unwrap()
+    }
+
+    fn section(start: f64, end: f64, energy: f64) -> Section {
+        Section { start, end, energy }
+    }
+
+    #[test]
+    fn test_buckets_presets_by_look() {
+        let config = config(
+            serde_json::json!([
+                { "name": "blackout", "effect_type": "solid", "color": [0, 0, 0] },
+                { "name": "dark", "effect_type": "solid", "color": [0, 0, 0] },
+                { "name": "blue", "effect_type": "pulse", "color": [0, 0, 255] },
+                { "name": "strobe", "effect_type": "strobe", "color": [255, 255, 255] },
+                { "name": "broken", "effect_type": "no_such_effect", "color": [255, 0, 0] }
+            ]),
+            serde_json::json!([{ "name": "chase", "pattern": "wave", "colour": [255, 0, 0] }]),
+        );
+        let pools = PresetPools::from_config(&config);
+
+        assert_eq!(pools.ambient, vec!["blue".to_string()]);
+        assert_eq!(pools.intense, vec!["strobe".to_string()]);
+        assert_eq!(pools.patterns, vec!["chase".to_string()]);
+        // The first black solid is the one used to end the song
+        assert_eq!(pools.off.as_deref(), Some("blackout"));
+
+        assert_eq!(pools.pick(EnergyLevel::Low, 0).map(String::as_str), Some("blue"));
+        assert_eq!(pools.pick(EnergyLevel::Medium, 0).map(String::as_str), Some("chase"));
+        // High energy rotates through the intense looks, then the patterns
+        assert_eq!(pools.pick(EnergyLevel::High, 0).map(String::as_str), Some("strobe"));
+        assert_eq!(pools.pick(EnergyLevel::High, 1).map(String::as_str), Some("chase"));
+    }
+
+    #[test]
+    fn test_empty_pools_fall_back() {
+        let only_strobe = config(
+            serde_json::json!([{ "name": "strobe", "effect_type": "strobe", "color": [255, 255, 255] }]),
+            serde_json::json!([]),
+        );
+        let pools = PresetPools::from_config(&only_strobe);
+        // No ambient look or pattern: every level borrows what there is
+        assert_eq!(pools.pick(EnergyLevel::Low, 0).map(String::as_str), Some("strobe"));
+        assert_eq!(pools.pick(EnergyLevel::Medium, 3).map(String::as_str), Some("strobe"));
+
+        let only_ambient = config(
+            serde_json::json!([{ "name": "blue", "effect_type": "pulse", "color": [0, 0, 255] }]),
+            serde_json::json!([]),
+        );
+        let pools = PresetPools::from_config(&only_ambient);
+        assert_eq!(pools.pick(EnergyLevel::Medium, 0).map(String::as_str), Some("blue"));
+        assert_eq!(pools.pick(EnergyLevel::High, 0).map(String::as_str), Some("blue"));
+
+        let nothing = config(serde_json::json!([]), serde_json::json!([]));
+        let suggestion = suggest_cues(&[section(0.0, 10.0, 0.9)], &nothing, "left", None, 0.0);
+        assert_eq!(suggestion.sections.len(), 1);
+        assert!(suggestion.cues.is_empty());
+    }
+
+    #[test]
+    fn test_suggests_cue_per_section_and_blackout() {
+        let config = config(
+            serde_json::json!([
+                { "name": "off", "effect_type": "solid", "color": [0, 0, 0] },
+                { "name": "blue", "effect_type": "pulse", "color": [0, 0, 255] },
+                { "name": "strobe", "effect_type": "strobe", "color": [255, 255, 255] }
+            ]),
+            serde_json::json!([]),
+        );
+        let sections = [section(0.3, 8.1, 0.2), section(8.1, 16.4, 0.9), section(16.4, 20.0, 0.5)];
+        let suggestion = suggest_cues(&sections, &config, "left", Some(120.0), 0.0);
+
+        let cues: Vec<(f64, &str)> = suggestion.cues.iter().map(|c| (c.time, c.preset_name.as_str())).collect();
+        // First cue at the top of the song, later ones snapped to the half-second beat
+        assert_eq!(cues, vec![(0.0, "blue"), (8.0, "strobe"), (16.5, "blue"), (20.0, "off")]);
+        assert_eq!(suggestion.sections[1].level, EnergyLevel::High);
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use super::Effect;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 use rand::Rng;
 
 struct BurstsState {
@@ -21,6 +21,7 @@ pub struct Bursts {
     color: [u8; 3],
     beat_duration: f64,
     burst_size: usize,
+    burst_count: usize,
     states: HashMap<u16, BurstsState>,
 }
 
@@ -30,17 +31,19 @@ impl Bursts {
             color,
             beat_duration: 60.0 / bpm,
             burst_size: 8,
+            burst_count: 3,
             states: HashMap::new(),
         }
     }
 }
 
 impl Effect for Bursts {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
+        let led_count = frame.led_count();
         let current_beat = (elapsed / self.beat_duration) as u64;
         let beat_position = (elapsed % self.beat_duration) / self.beat_duration;
 
-        let universe = transport.universe();
+        let universe = frame.universe();
         let state = self.states.entry(universe).or_default();
 
         for led in &mut state.leds {
@@ -52,7 +55,7 @@ impl Effect for Bursts {
         if current_beat != state.last_beat {
             state.last_beat = current_beat;
             let mut rng = rand::rng();
-            for _ in 0..3 {
+            for _ in 0..self.burst_count {
                 let pos = rng.random_range(0..led_count.saturating_sub(self.burst_size).max(1));
                 for i in 0..self.burst_size {
                     if pos + i < led_count {
@@ -74,6 +77,14 @@ impl Effect for Bursts {
             dmx_data[offset + 3] = 0;
         }
 
-        let _ = transport.send_dmx_packet(&dmx_data);
+        frame.set_dmx(&dmx_data);
+    }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
+    }
+
+    fn set_intensity(&mut self, intensity: f64) {
+        self.burst_count = (1.0 + 4.0 * intensity).round() as usize;
     }
 }
@@ -1,6 +1,6 @@
 use std::collections::HashSet;
 use crate::effects::Effect;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 
 pub struct Flash {
     color: [u8; 3],
@@ -21,24 +21,28 @@ impl Flash {
 }
 
 impl Effect for Flash {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
-        let universe = transport.universe();
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
+        let universe = frame.universe();
         if self.done_universes.contains(&universe) {
             return;
         }
 
         if elapsed < self.flash_duration {
-            let _ = transport.send_raw_leds(led_count, self.color[0], self.color[1], self.color[2]);
+            frame.fill(self.color[0], self.color[1], self.color[2]);
         } else if elapsed < self.flash_duration + self.fade_duration {
             let fade_progress = (elapsed - self.flash_duration) / self.fade_duration;
             let brightness = (1.0 - fade_progress).powi(2);
             let r = (self.color[0] as f64 * brightness) as u8;
             let g = (self.color[1] as f64 * brightness) as u8;
             let b = (self.color[2] as f64 * brightness) as u8;
-            let _ = transport.send_raw_leds(led_count, r, g, b);
+            frame.fill(r, g, b);
         } else {
-            let _ = transport.send_raw_leds(led_count, 0, 0, 0);
+            frame.fill(0, 0, 0);
             self.done_universes.insert(universe);
         }
     }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
+    }
 }
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use crate::effects::Effect;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 use rand::Rng;
 
 #[derive(Default)]
@@ -45,7 +45,8 @@ impl Lightning {
 }
 
 impl Effect for Lightning {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
+        let led_count = frame.led_count();
         let pulse = self.calculate_pulse(elapsed);
         let pulse_color = [
             (self.color[0] as f64 * pulse) as u8,
@@ -53,7 +54,7 @@ impl Effect for Lightning {
             (self.color[2] as f64 * pulse) as u8,
         ];
 
-        let universe = transport.universe();
+        let universe = frame.universe();
         let state = self.states.entry(universe).or_default();
 
         let mut rng = rand::rng();
@@ -98,6 +99,10 @@ impl Effect for Lightning {
             }
         }
 
-        let _ = transport.send_led_buffer(&led_buffer);
+        frame.set_leds(&led_buffer);
+    }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
     }
 }
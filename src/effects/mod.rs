@@ -20,12 +20,59 @@ pub use strobe::Strobe;
 pub use wipe_center::WipeCenter;
 pub use wipe_up::WipeUp;
 
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 
 pub trait Effect: Send {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize);
+    /// Render into `frame`; the compositor keeps its contents between ticks
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame);
+
+    fn set_color(&mut self, color: [u8; 3]);
+
+    /// Effect-specific amount (0.0 - 1.0): density, flash length, decay, etc.
+    fn set_intensity(&mut self, _intensity: f64) {}
+
+    /// Forget what has already been drawn so the next tick redraws everything
+    fn invalidate(&mut self) {}
+}
+
+/// Live parameter changes for a running effect or pattern; `None` leaves a value as is
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EffectParams {
+    #[serde(default, alias = "colour")]
+    pub color: Option<[u8; 3]>,
+    /// 0.0 - 1.0
+    #[serde(default)]
+    pub intensity: Option<f64>,
+    /// Multiplier on the current tempo
+    #[serde(default)]
+    pub speed: Option<f64>,
+    /// 0.0 - 1.0, applied to the output
+    #[serde(default)]
+    pub brightness: Option<f64>,
+}
+
+impl EffectParams {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(i) = self.intensity {
+            if !(0.0..=1.0).contains(&i) {
+                return Err(format!("intensity must be between 0 and 1, got {}", i));
+            }
+        }
+        if let Some(b) = self.brightness {
+            if !(0.0..=1.0).contains(&b) {
+                return Err(format!("brightness must be between 0 and 1, got {}", b));
+            }
+        }
+        if let Some(s) = self.speed {
+            if !(0.1..=8.0).contains(&s) {
+                return Err(format!("speed must be between 0.1 and 8, got {}", s));
+            }
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use crate::effects::Effect;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 use rand::Rng;
 
 struct ActivePuddle {
@@ -30,6 +30,7 @@ pub struct Puddles {
     fade_rate: u8,
     puddle_size: usize,
     fade_in_duration: f64,
+    spawn_scale: f64,
     states: HashMap<u16, PuddlesState>,
 }
 
@@ -40,14 +41,16 @@ impl Puddles {
             fade_rate: 240,
             puddle_size: 8,
             fade_in_duration: 0.15,
+            spawn_scale: 1.0,
             states: HashMap::new(),
         }
     }
 }
 
 impl Effect for Puddles {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
-        let universe = transport.universe();
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
+        let led_count = frame.led_count();
+        let universe = frame.universe();
         let state = self.states.entry(universe).or_default();
 
         let fade = self.fade_rate as u16;
@@ -66,7 +69,7 @@ impl Effect for Puddles {
                 size,
                 age: 0.0,
             });
-            state.next_puddle_time = elapsed + rng.random_range(0.03..0.12);
+            state.next_puddle_time = elapsed + rng.random_range(0.03..0.12) * self.spawn_scale;
         }
 
         let dt = 0.025;
@@ -103,6 +106,14 @@ impl Effect for Puddles {
             dmx_data[offset + 3] = 0;
         }
 
-        let _ = transport.send_dmx_packet(&dmx_data);
+        frame.set_dmx(&dmx_data);
+    }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
+    }
+
+    fn set_intensity(&mut self, intensity: f64) {
+        self.spawn_scale = (2.0 - 2.0 * intensity).max(0.2);
     }
 }
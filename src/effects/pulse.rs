@@ -1,10 +1,11 @@
 use std::collections::HashMap;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 use super::Effect;
 
 pub struct Pulse {
     color: [u8; 3],
     beat_duration: f64,
+    intensity: f64,
     last_brightness_per_universe: HashMap<u16, u8>,
 }
 
@@ -13,19 +14,20 @@ impl Pulse {
         Self {
             color,
             beat_duration: 60.0 / bpm,
+            intensity: 0.5,
             last_brightness_per_universe: HashMap::new(),
         }
     }
 }
 
 impl Effect for Pulse {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
         let beat_position = (elapsed % self.beat_duration) / self.beat_duration;
 
-        let decay_rate = 8.0;
+        let decay_rate = 14.0 - 12.0 * self.intensity;
         let brightness = ((-decay_rate * beat_position).exp() * 255.0) as u8;
 
-        let universe = transport.universe();
+        let universe = frame.universe();
         let last_brightness = self.last_brightness_per_universe.get(&universe).copied().unwrap_or(255);
 
         if brightness == last_brightness {
@@ -37,6 +39,19 @@ impl Effect for Pulse {
         let g = ((self.color[1] as u16 * brightness as u16) / 255) as u8;
         let b = ((self.color[2] as u16 * brightness as u16) / 255) as u8;
 
-        let _ = transport.send_raw_leds(led_count, r, g, b);
+        frame.fill(r, g, b);
+    }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
+        self.invalidate();
+    }
+
+    fn set_intensity(&mut self, intensity: f64) {
+        self.intensity = intensity;
+    }
+
+    fn invalidate(&mut self) {
+        self.last_brightness_per_universe.clear();
     }
 }
@@ -1,5 +1,5 @@
 use std::collections::HashSet;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 use super::Effect;
 
 pub struct Solid {
@@ -17,13 +17,22 @@ impl Solid {
 }
 
 impl Effect for Solid {
-    fn tick(&mut self, _elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
-        let universe = transport.universe();
+    fn tick(&mut self, _elapsed: f64, frame: &mut LedFrame) {
+        let universe = frame.universe();
         if self.sent_universes.contains(&universe) {
             return;
         }
         self.sent_universes.insert(universe);
 
-        let _ = transport.send_raw_leds(led_count, self.color[0], self.color[1], self.color[2]);
+        frame.fill(self.color[0], self.color[1], self.color[2]);
+    }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
+        self.invalidate();
+    }
+
+    fn invalidate(&mut self) {
+        self.sent_universes.clear();
     }
 }
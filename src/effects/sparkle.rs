@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use crate::effects::Effect;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 use rand::Rng;
 
 struct Spark {
@@ -17,6 +17,7 @@ struct SparkleState {
 pub struct Sparkle {
     color: [u8; 3],
     beat_duration: f64,
+    max_spawn: u32,
     states: HashMap<u16, SparkleState>,
 }
 
@@ -25,14 +26,16 @@ impl Sparkle {
         Self {
             color,
             beat_duration: 60.0 / bpm,
+            max_spawn: 3,
             states: HashMap::new(),
         }
     }
 }
 
 impl Effect for Sparkle {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
-        let universe = transport.universe();
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
+        let led_count = frame.led_count();
+        let universe = frame.universe();
         let state = self.states.entry(universe).or_default();
 
         let mut rng = rand::rng();
@@ -44,7 +47,7 @@ impl Effect for Sparkle {
         if current_sub_beat != state.last_spawn_beat {
             state.last_spawn_beat = current_sub_beat;
 
-            let spawn_count = rng.random_range(1..=3);
+            let spawn_count = rng.random_range(1..=self.max_spawn);
             for _ in 0..spawn_count {
                 let pos = rng.random_range(0..led_count);
                 state.sparks.push(Spark {
@@ -92,6 +95,14 @@ impl Effect for Sparkle {
             }
         }
 
-        let _ = transport.send_led_buffer(&led_buffer);
+        frame.set_leds(&led_buffer);
+    }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
+    }
+
+    fn set_intensity(&mut self, intensity: f64) {
+        self.max_spawn = (1.0 + 4.0 * intensity).round() as u32;
     }
 }
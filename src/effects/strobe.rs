@@ -1,10 +1,11 @@
 use std::collections::HashMap;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 use super::Effect;
 
 pub struct Strobe {
     color: [u8; 3],
     beat_duration: f64,
+    intensity: f64,
     last_state_per_universe: HashMap<u16, bool>,
 }
 
@@ -13,20 +14,22 @@ impl Strobe {
         Self {
             color,
             beat_duration: 60.0 / bpm,
+            intensity: 0.5,
             last_state_per_universe: HashMap::new(),
         }
     }
 }
 
 impl Effect for Strobe {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
         let beat_position = (elapsed % self.beat_duration) / self.beat_duration;
 
         let min_on_duration = 0.025;
-        let on_threshold = (min_on_duration / self.beat_duration).max(0.05).min(0.15);
+        let max_on = (0.3 * self.intensity).max(0.02);
+        let on_threshold = (min_on_duration / self.beat_duration).max(0.05).min(max_on);
         let strobe_on = beat_position < on_threshold;
 
-        let universe = transport.universe();
+        let universe = frame.universe();
         let last_state = self.last_state_per_universe.get(&universe).copied().unwrap_or(false);
 
         if strobe_on == last_state {
@@ -40,8 +43,19 @@ impl Effect for Strobe {
             (0, 0, 0)
         };
 
-        for _ in 0..3 {
-            let _ = transport.send_raw_leds(led_count, r, g, b);
-        }
+        frame.fill(r, g, b);
+    }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
+        self.invalidate();
+    }
+
+    fn set_intensity(&mut self, intensity: f64) {
+        self.intensity = intensity;
+    }
+
+    fn invalidate(&mut self) {
+        self.last_state_per_universe.clear();
     }
 }
@@ -1,5 +1,5 @@
 use crate::effects::Effect;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 
 pub struct WipeCenter {
     color: [u8; 3],
@@ -18,7 +18,8 @@ impl WipeCenter {
 }
 
 impl Effect for WipeCenter {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
+        let led_count = frame.led_count();
         let beat_position = (elapsed % self.beat_duration) / self.beat_duration;
 
         let eased = 1.0 - (1.0 - beat_position).powi(2);
@@ -56,6 +57,10 @@ impl Effect for WipeCenter {
             led_buffer.push([r, g, b]);
         }
 
-        let _ = transport.send_led_buffer(&led_buffer);
+        frame.set_leds(&led_buffer);
+    }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
     }
 }
@@ -1,5 +1,5 @@
 use crate::effects::Effect;
-use crate::transport::E131RawTransport;
+use crate::compositor::LedFrame;
 
 pub struct WipeUp {
     color: [u8; 3],
@@ -18,7 +18,8 @@ impl WipeUp {
 }
 
 impl Effect for WipeUp {
-    fn tick(&mut self, elapsed: f64, transport: &mut E131RawTransport, led_count: usize) {
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
+        let led_count = frame.led_count();
         let beat_position = (elapsed % self.beat_duration) / self.beat_duration;
 
         let eased = beat_position * beat_position;
@@ -50,6 +51,10 @@ impl Effect for WipeUp {
             led_buffer.push([r, g, b]);
         }
 
-        let _ = transport.send_led_buffer(&led_buffer);
+        frame.set_leds(&led_buffer);
+    }
+
+    fn set_color(&mut self, color: [u8; 3]) {
+        self.color = color;
     }
 }
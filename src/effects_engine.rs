@@ -1,12 +1,12 @@
 use std::sync::mpsc;
-use std::sync::Arc;
-use std::thread;
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tracing::info;
 
-use crate::effects::{Effect, EffectType};
-use crate::timing_metrics::TimingMetrics;
-use crate::transport::E131RawTransport;
+use crate::compositor::{CompositorCommand, Outputs};
+use crate::effects::{Effect, EffectParams, EffectType};
+use crate::fseq::{SequenceClip, SequencePlayer};
+use crate::tempo::BeatClock;
+use crate::transport::OutputKey;
 
 #[derive(Debug, Clone)]
 pub struct BoardTarget {
@@ -20,6 +20,8 @@ pub struct EffectConfig {
     pub effect_type: EffectType,
     pub bpm: f64,
     pub color: [u8; 3],
+    /// Beats of `bpm` per song beat, used to map live tempo changes onto the effect
+    pub sync_rate: f64,
 }
 
 #[derive(Debug)]
@@ -27,134 +29,114 @@ pub enum EngineCommand {
     Start {
         config: EffectConfig,
         boards: Vec<BoardTarget>,
+        /// Seconds into the effect to start from
+        phase: f64,
     },
+    /// Play a sequence clip on the effect outputs at its own frame rate, whatever the tempo
+    PlaySequence {
+        clip: SequenceClip,
+        boards: Vec<BoardTarget>,
+        /// Seconds into the clip to start from
+        phase: f64,
+    },
+    /// Change the song tempo of the running effect without restarting it
+    SetBpm { bpm: f64 },
+    /// Tap tempo; `at` is when the tap was received
+    Tap { at: Instant },
+    /// Update colour, intensity, speed or brightness of the running effect
+    SetParams(EffectParams),
+    /// Fade the running effect to black over `duration`, then stop it
+    FadeOut { duration: Duration },
+    /// Freeze the effect clock; effects started while paused start frozen
+    Pause,
+    Resume,
     Stop,
 }
 
+/// Handle for the effect source of the compositor
 pub struct EffectsEngine {
-    command_tx: mpsc::Sender<EngineCommand>,
+    compositor_tx: mpsc::Sender<CompositorCommand>,
 }
 
 impl EffectsEngine {
-    pub fn new(timing_metrics: Option<Arc<TimingMetrics>>) -> Self {
-        let (command_tx, command_rx) = mpsc::channel();
-
-        thread::spawn(move || Self::run_loop(command_rx, timing_metrics));
-
-        Self { command_tx }
+    pub fn new(compositor_tx: mpsc::Sender<CompositorCommand>) -> Self {
+        Self { compositor_tx }
     }
 
     pub fn send_command(
         &self,
         cmd: EngineCommand,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.command_tx.send(cmd)?;
+        self.compositor_tx.send(CompositorCommand::Effects(cmd))?;
         Ok(())
     }
-
-    fn run_loop(command_rx: mpsc::Receiver<EngineCommand>, timing_metrics: Option<Arc<TimingMetrics>>) {
-        let mut state: Option<EngineState> = None;
-        let tick_duration = Duration::from_millis(25);
-        let mut next_tick = Instant::now() + tick_duration;
-        let mut last_tick = Instant::now();
-
-        loop {
-            match command_rx.try_recv() {
-                Ok(cmd) => match cmd {
-                    EngineCommand::Start { config, boards } => {
-                        info!(
-                            effect = ?config.effect_type,
-                            bpm = config.bpm,
-                            boards = boards.len(),
-                            "Effects engine START"
-                        );
-                        state = Some(EngineState::new(config, boards, timing_metrics.clone()));
-                        next_tick = Instant::now() + tick_duration;
-                        last_tick = Instant::now();
-                    }
-                    EngineCommand::Stop => {
-                        info!("Effects engine STOP");
-                        if let Some(ref mut s) = state {
-                            s.blackout();
-                        }
-                        state = None;
-                    }
-                },
-                Err(mpsc::TryRecvError::Empty) => {}
-                Err(mpsc::TryRecvError::Disconnected) => break,
-            }
-
-            if let Some(ref mut s) = state {
-                let now = Instant::now();
-                let actual_tick_ms = now.duration_since(last_tick).as_secs_f64() * 1000.0;
-                last_tick = now;
-
-                if let Some(ref metrics) = timing_metrics {
-                    metrics.record_frame_tick(actual_tick_ms);
-                }
-
-                s.tick();
-            }
-
-            let now = Instant::now();
-            if next_tick > now {
-                thread::sleep(next_tick - now);
-            }
-            next_tick += tick_duration;
-        }
-    }
 }
 
-struct EngineState {
+/// The running effect, drawn by the compositor into the outputs it owns
+pub struct EffectSource {
     effect: Box<dyn Effect>,
-    start_time: Instant,
+    /// None for sequence clips, which ignore tempo and speed changes
+    config: Option<EffectConfig>,
+    clock: BeatClock,
     start_system_time: f64,
-    transports: Vec<(E131RawTransport, usize)>,
+    outputs: Vec<OutputKey>,
+    brightness: f64,
     tick_count: u64,
 }
 
-impl EngineState {
-    fn new(config: EffectConfig, boards: Vec<BoardTarget>, timing_metrics: Option<Arc<TimingMetrics>>) -> Self {
-        let mut transports = Vec::new();
-
-        for board in &boards {
-            match E131RawTransport::new(vec![board.ip.clone()], board.universe) {
-                Ok(mut t) => {
-                    info!(
-                        ip = %board.ip,
-                        universe = board.universe,
-                        "E1.31 transport created"
-                    );
-                    if let Some(ref metrics) = timing_metrics {
-                        t.set_timing_metrics(metrics.clone());
-                    }
-                    transports.push((t, board.led_count));
-                }
-                Err(e) => {
-                    info!(ip = %board.ip, error = %e, "Failed to create E1.31 transport");
-                }
-            }
-        }
+impl EffectSource {
+    pub fn new(config: EffectConfig, outputs: Vec<OutputKey>, phase: f64, now: Instant) -> Self {
+        let effect = config.effect_type.create(config.color, config.bpm);
+        Self::with_effect(effect, Some(config), outputs, phase, now)
+    }
+
+    pub fn sequence(clip: SequenceClip, outputs: Vec<OutputKey>, phase: f64, now: Instant) -> Self {
+        Self::with_effect(Box::new(SequencePlayer::new(clip)), None, outputs, phase, now)
+    }
 
+    fn with_effect(
+        effect: Box<dyn Effect>,
+        config: Option<EffectConfig>,
+        outputs: Vec<OutputKey>,
+        phase: f64,
+        now: Instant,
+    ) -> Self {
         let start_system_time = SystemTime::now()
             .duration_since(UNIX_EPOCH)
             .map(|d| d.as_secs_f64())
             .unwrap_or(0.0);
 
-        let effect = config.effect_type.create(config.color, config.bpm);
-
         Self {
             effect,
-            start_time: Instant::now(),
+            config,
+            clock: BeatClock::starting_at(now, phase),
             start_system_time,
-            transports,
+            outputs,
+            brightness: 1.0,
             tick_count: 0,
         }
     }
 
-    fn tick(&mut self) {
+    pub fn outputs(&self) -> &[OutputKey] {
+        &self.outputs
+    }
+
+    /// Give up outputs taken over by another source
+    pub fn release(&mut self, keys: &[OutputKey]) {
+        self.outputs.retain(|k| !keys.contains(k));
+    }
+
+    pub fn brightness(&self) -> f64 {
+        self.brightness
+    }
+
+    pub fn set_frozen(&mut self, now: Instant, frozen: bool) {
+        self.clock.set_frozen(now, frozen);
+    }
+
+    pub fn render(&mut self, now: Instant, outputs: &mut Outputs) {
         self.tick_count += 1;
-        let elapsed = self.start_time.elapsed().as_secs_f64();
+        let elapsed = self.clock.position(now);
 
         if self.tick_count % 500 == 0 {
             let system_now = SystemTime::now()
@@ -171,18 +153,58 @@ impl EngineState {
             );
         }
 
-        for (transport, led_count) in &mut self.transports {
-            self.effect.tick(elapsed, transport, *led_count);
+        for key in &self.outputs {
+            if let Some(frame) = outputs.frame(key) {
+                self.effect.tick(elapsed, frame);
+            }
         }
     }
 
-    fn blackout(&mut self) {
-        for (transport, led_count) in &mut self.transports {
-            for _ in 0..5 {
-                let _ = transport.send_raw_leds(*led_count, 0, 0, 0);
-                std::thread::sleep(std::time::Duration::from_millis(2));
+    /// Song beat length in effect time
+    fn song_beat(config: &EffectConfig) -> f64 {
+        60.0 * config.sync_rate / config.bpm
+    }
+
+    pub fn set_bpm(&mut self, bpm: f64) {
+        let Some(ref config) = self.config else {
+            return;
+        };
+        if config.bpm <= 0.0 || bpm <= 0.0 {
+            return;
+        }
+        let rate = bpm * config.sync_rate / config.bpm;
+        self.clock.set_rate(Instant::now(), rate);
+        info!(bpm = bpm, rate = rate, "Effects engine tempo changed");
+    }
+
+    pub fn align_to_beat(&mut self, at: Instant) {
+        let Some(ref config) = self.config else {
+            return;
+        };
+        if config.bpm <= 0.0 {
+            return;
+        }
+        let beat = Self::song_beat(config);
+        self.clock.align_to_beat(Instant::now(), at, beat);
+    }
+
+    pub fn set_params(&mut self, params: EffectParams) {
+        info!(params = ?params, "Effects engine params changed");
+
+        if let Some(color) = params.color {
+            if let Some(ref mut config) = self.config {
+                config.color = color;
             }
+            self.effect.set_color(color);
+        }
+        if let Some(intensity) = params.intensity {
+            self.effect.set_intensity(intensity);
+        }
+        if let Some(speed) = params.speed.filter(|_| self.config.is_some()) {
+            self.clock.set_speed(Instant::now(), speed);
+        }
+        if let Some(brightness) = params.brightness {
+            self.brightness = brightness;
         }
-        info!(transports = self.transports.len(), "E1.31 blackout sent to all boards");
     }
 }
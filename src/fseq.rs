@@ -0,0 +1,635 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use serde::Serialize;
+
+use crate::audio::AudioFile;
+use crate::compositor::{Compositor, LedFrame};
+use crate::config::{Config, SequencePreset};
+use crate::effects::Effect;
+use crate::cue_scheduler::CueSequence;
+use crate::effects_engine::EffectsEngine;
+use crate::pattern_engine::PatternEngine;
+use crate::program::{Program, ProgramMode};
+use crate::program_engine;
+
+/// Same rate as the live compositor tick
+pub const DEFAULT_FPS: u32 = 40;
+const CHANNELS_PER_UNIVERSE: usize = 512;
+const HEADER_LEN: usize = 32;
+const SPARSE_RANGE_LEN: usize = 6;
+/// FSEQ sparse ranges hold 24-bit channel numbers
+const MAX_CHANNEL: usize = 0xFF_FFFF;
+/// Dark frames after the last cue when the program has no audio length
+const TAIL_SECS: f64 = 2.0;
+/// Longest sequence a render will produce
+const MAX_RENDER_SECS: f64 = 3600.0;
+/// Largest channel data a render or a loaded sequence will hold in memory
+const MAX_DATA_BYTES: usize = 1024 * 1024 * 1024;
+const COMPRESSION_BLOCK_LEN: usize = 8;
+
+/// A program rendered frame by frame for every configured universe
+pub struct Sequence {
+    pub step_ms: u8,
+    /// Sorted; each takes 512 channels of a frame, in this order
+    pub universes: Vec<u16>,
+    pub frame_count: u32,
+    /// `frame_count` frames of `universes.len() * 512` channels
+    pub data: Vec<u8>,
+    /// Audio the player should start alongside
+    pub media_file: Option<String>,
+}
+
+/// Universes of every configured board, as the compositor addresses them
+pub fn universes(config: &Config) -> Vec<u16> {
+    let mut universes: Vec<u16> = config.boards.iter().map(|b| b.universe.unwrap_or(1)).collect();
+    universes.sort_unstable();
+    universes.dedup();
+    universes
+}
+
+/// Run `program` through the effect and pattern sources on a virtual clock
+///
+/// Every board counts as online. The length is `duration` if given, else the
+/// program's audio length, else its last cue plus a couple of dark seconds.
+/// Renders over an hour or over `MAX_DATA_BYTES` of channel data are refused.
+pub fn render(
+    program: &Program,
+    config: &Config,
+    sequences: &SequenceLibrary,
+    fps: u32,
+    duration: Option<f64>,
+) -> Result<Sequence, String> {
+    if program.mode == ProgramMode::CueStack {
+        return Err(format!("{} is a cue stack; its cues have no times to render", program.id));
+    }
+    if !(1..=100).contains(&fps) {
+        return Err(format!("fps must be between 1 and 100, got {}", fps));
+    }
+    let step_ms = (1000 / fps).clamp(1, 255) as u8;
+
+    let universes = universes(config);
+    if universes.is_empty() {
+        return Err("No boards configured".to_string());
+    }
+    if universes.len() > u8::MAX as usize {
+        return Err(format!("FSEQ files hold at most 255 universes, {} configured", universes.len()));
+    }
+    if universes.iter().any(|u| (*u as usize).max(1) * CHANNELS_PER_UNIVERSE > MAX_CHANNEL) {
+        return Err("Universes above 32767 don't fit in an FSEQ channel range".to_string());
+    }
+    let slots: BTreeMap<u16, usize> = universes.iter().enumerate().map(|(i, u)| (*u, i)).collect();
+
+    let mut sequence = CueSequence::new(program_engine::offline_schedule(program, config, sequences));
+    let length = duration
+        .or(program.audio_duration)
+        .unwrap_or_else(|| sequence.end().as_secs_f64() + TAIL_SECS);
+    if !length.is_finite() || length <= 0.0 {
+        return Err(format!("Invalid sequence length {}s", length));
+    }
+    if length > MAX_RENDER_SECS {
+        return Err(format!("Sequence length {}s is over the {}s limit", length, MAX_RENDER_SECS));
+    }
+    let frame_count = (length * 1000.0 / step_ms as f64).ceil() as u32;
+    let frame_len = universes.len() * CHANNELS_PER_UNIVERSE;
+    let data_len = frame_len
+        .checked_mul(frame_count as usize)
+        .filter(|len| *len <= MAX_DATA_BYTES)
+        .ok_or_else(|| {
+            format!(
+                "{} frames x {} universes is over the {} MB render limit",
+                frame_count,
+                universes.len(),
+                MAX_DATA_BYTES / (1024 * 1024)
+            )
+        })?;
+
+    let (compositor_tx, compositor_rx) = mpsc::channel();
+    let effects_engine = EffectsEngine::new(compositor_tx.clone());
+    let pattern_engine = PatternEngine::new(compositor_tx);
+    let mut compositor = Compositor::offline();
+    let base = Instant::now();
+
+    let mut data = Vec::with_capacity(data_len);
+    for frame in 0..frame_count {
+        let at = Duration::from_millis(frame as u64 * step_ms as u64);
+        while let Some(fired_at) = sequence.step(at, &effects_engine, &pattern_engine) {
+            for cmd in compositor_rx.try_iter() {
+                compositor.handle(cmd, base + fired_at);
+            }
+        }
+
+        let start = data.len();
+        data.resize(start + frame_len, 0);
+        for (universe, dmx) in compositor.render_offline(base + at) {
+            let Some(&slot) = slots.get(&universe) else {
+                continue;
+            };
+            // Boards sharing a universe merge highest-takes-precedence, like a DMX merge
+            let channels = &mut data[start + slot * CHANNELS_PER_UNIVERSE..][..CHANNELS_PER_UNIVERSE];
+            for (channel, value) in channels.iter_mut().zip(dmx.iter()) {
+                *channel = (*channel).max(*value);
+            }
+        }
+    }
+
+    Ok(Sequence {
+        step_ms,
+        universes,
+        frame_count,
+        data,
+        media_file: program.audio_file.clone(),
+    })
+}
+
+impl Sequence {
+    /// Uncompressed FSEQ v2 with one sparse range per universe
+    ///
+    /// Universe `n` maps to channels `(n - 1) * 512 + 1` onwards, the usual
+    /// E1.31 layout, so the player's output config needs no remapping.
+    pub fn to_fseq(&self) -> Result<Vec<u8>, String> {
+        let mut variables: Vec<([u8; 2], &str)> = vec![(*b"sp", "rust-wled-server")];
+        if let Some(ref media) = self.media_file {
+            variables.push((*b"mf", media));
+        }
+
+        let variable_offset = HEADER_LEN + SPARSE_RANGE_LEN * self.universes.len();
+        let data_offset = variable_offset + variables.iter().map(|(_, v)| 4 + v.len() + 1).sum::<usize>();
+        let data_offset = u16::try_from(data_offset).map_err(|_| "FSEQ header too long".to_string())?;
+        let channel_count = (self.universes.len() * CHANNELS_PER_UNIVERSE) as u32;
+        let unique_id = chrono::Utc::now().timestamp_micros() as u64;
+
+        let mut out = Vec::with_capacity(data_offset as usize + self.data.len());
+        out.extend_from_slice(b"PSEQ");
+        out.extend_from_slice(&data_offset.to_le_bytes());
+        out.push(0); // minor version
+        out.push(2); // major version
+        out.extend_from_slice(&(variable_offset as u16).to_le_bytes());
+        out.extend_from_slice(&channel_count.to_le_bytes());
+        out.extend_from_slice(&self.frame_count.to_le_bytes());
+        out.push(self.step_ms);
+        out.push(0); // flags
+        out.push(0); // compression: none
+        out.push(0); // compression blocks
+        out.push(self.universes.len() as u8);
+        out.push(0); // reserved
+        out.extend_from_slice(&unique_id.to_le_bytes());
+
+        for universe in &self.universes {
+            let start = (*universe as usize).saturating_sub(1) * CHANNELS_PER_UNIVERSE;
+            out.extend_from_slice(&(start as u32).to_le_bytes()[..3]);
+            out.extend_from_slice(&(CHANNELS_PER_UNIVERSE as u32).to_le_bytes()[..3]);
+        }
+
+        for (code, value) in variables {
+            out.extend_from_slice(&((4 + value.len() + 1) as u16).to_le_bytes());
+            out.extend_from_slice(&code);
+            out.extend_from_slice(value.as_bytes());
+            out.push(0);
+        }
+
+        out.extend_from_slice(&self.data);
+        Ok(out)
+    }
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]])
+}
+
+fn u24_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], 0])
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+}
+
+/// An FSEQ file as exported by xLights, with its channel data uncompressed
+pub struct FseqFile {
+    pub step_ms: u8,
+    pub frame_count: u32,
+    /// Channels stored per frame
+    pub channel_count: u32,
+    /// First channel and length of each stored range, in frame order
+    ranges: Vec<(u32, u32)>,
+    data: Vec<u8>,
+}
+
+impl fmt::Debug for FseqFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FseqFile")
+            .field("step_ms", &self.step_ms)
+            .field("frame_count", &self.frame_count)
+            .field("channel_count", &self.channel_count)
+            .field("ranges", &self.ranges)
+            .finish()
+    }
+}
+
+impl FseqFile {
+    /// Parse a v1 or v2 file; v2 data may be uncompressed, zstd or zlib
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < HEADER_LEN || !(bytes.starts_with(b"PSEQ") || bytes.starts_with(b"FSEQ")) {
+            return Err("Not an FSEQ file".to_string());
+        }
+        let data_offset = u16_at(bytes, 4) as usize;
+        let major = bytes[7];
+        let channel_count = u32_at(bytes, 10);
+        let frame_count = u32_at(bytes, 14);
+        let step_ms = bytes[18];
+        if step_ms == 0 {
+            return Err("FSEQ frame time is 0ms".to_string());
+        }
+        let len = (channel_count as usize)
+            .checked_mul(frame_count as usize)
+            .filter(|len| *len <= MAX_DATA_BYTES)
+            .ok_or_else(|| format!("FSEQ data is over the {} MB limit", MAX_DATA_BYTES / (1024 * 1024)))?;
+        let truncated = || "FSEQ file is truncated".to_string();
+
+        let (ranges, data) = match major {
+            1 => {
+                let data = bytes.get(data_offset..).and_then(|d| d.get(..len)).ok_or_else(truncated)?;
+                (Vec::new(), data.to_vec())
+            }
+            2 => {
+                let compression = bytes[20] & 0x0F;
+                let block_count = bytes[21] as usize | ((bytes[20] as usize & 0xF0) << 4);
+                let range_count = bytes[22] as usize;
+
+                let ranges_at = HEADER_LEN + block_count * COMPRESSION_BLOCK_LEN;
+                let headers = bytes
+                    .get(..ranges_at + range_count * SPARSE_RANGE_LEN)
+                    .ok_or_else(truncated)?;
+                let blocks: Vec<usize> = (0..block_count)
+                    .map(|i| u32_at(headers, HEADER_LEN + i * COMPRESSION_BLOCK_LEN + 4) as usize)
+                    .collect();
+                let ranges: Vec<(u32, u32)> = (0..range_count)
+                    .map(|i| {
+                        let at = ranges_at + i * SPARSE_RANGE_LEN;
+                        (u24_at(headers, at), u24_at(headers, at + 3))
+                    })
+                    .collect();
+                if !ranges.is_empty() && ranges.iter().map(|r| r.1).sum::<u32>() != channel_count {
+                    return Err("FSEQ sparse ranges don't add up to the channel count".to_string());
+                }
+
+                let data = match compression {
+                    0 => bytes.get(data_offset..).and_then(|d| d.get(..len)).ok_or_else(truncated)?.to_vec(),
+                    1 | 2 => {
+                        let mut data = Vec::new();
+                        let mut offset = data_offset;
+                        for block_len in blocks.into_iter().filter(|l| *l > 0) {
+                            let block = bytes.get(offset..offset + block_len).ok_or_else(truncated)?;
+                            offset += block_len;
+                            let limit = (len - data.len().min(len)) as u64;
+                            let read = if compression == 1 {
+                                ruzstd::decoding::StreamingDecoder::new(block)
+                                    .map_err(|e| format!("Invalid zstd block: {}", e))?
+                                    .take(limit)
+                                    .read_to_end(&mut data)
+                            } else {
+                                flate2::read::ZlibDecoder::new(block).take(limit).read_to_end(&mut data)
+                            };
+                            read.map_err(|e| format!("Failed to decompress FSEQ data: {}", e))?;
+                        }
+                        if data.len() < len {
+                            return Err(truncated());
+                        }
+                        data
+                    }
+                    other => return Err(format!("Unknown FSEQ compression type {}", other)),
+                };
+                (ranges, data)
+            }
+            other => return Err(format!("Unsupported FSEQ version {}", other)),
+        };
+
+        Ok(Self {
+            step_ms,
+            frame_count,
+            channel_count,
+            ranges: if ranges.is_empty() { vec![(0, channel_count)] } else { ranges },
+            data,
+        })
+    }
+
+    pub fn duration(&self) -> f64 {
+        self.frame_count as f64 * self.step_ms as f64 / 1000.0
+    }
+
+    /// `universe`'s channels in `frame`, starting at channel `(universe - 1) * 512`
+    ///
+    /// Channels the file doesn't store come back dark.
+    pub fn universe_frame(&self, frame: u32, universe: u16) -> [u8; CHANNELS_PER_UNIVERSE] {
+        let mut dmx = [0u8; CHANNELS_PER_UNIVERSE];
+        if frame >= self.frame_count {
+            return dmx;
+        }
+        let start = (universe.max(1) as usize - 1) * CHANNELS_PER_UNIVERSE;
+        let end = start + CHANNELS_PER_UNIVERSE;
+        let frame_len = self.channel_count as usize;
+        let channels = &self.data[frame as usize * frame_len..][..frame_len];
+
+        let mut offset = 0;
+        for &(first, count) in &self.ranges {
+            let (first, count) = (first as usize, count as usize);
+            let from = first.max(start);
+            let to = (first + count).min(end);
+            if from < to {
+                dmx[from - start..to - start].copy_from_slice(&channels[offset + from - first..offset + to - first]);
+            }
+            offset += count;
+        }
+        dmx
+    }
+}
+
+/// Frames `start_frame..end_frame` of a sequence file, ready to play
+#[derive(Debug, Clone)]
+pub struct SequenceClip {
+    pub file: Arc<FseqFile>,
+    pub start_frame: u32,
+    pub end_frame: u32,
+}
+
+impl SequenceClip {
+    /// The frame showing `elapsed` seconds into the clip, or None once it has ended
+    pub fn frame_at(&self, elapsed: f64) -> Option<u32> {
+        let offset = (elapsed.max(0.0) * 1000.0 / self.file.step_ms as f64) as u64;
+        let frame = self.start_frame as u64 + offset;
+        (frame < self.end_frame as u64).then_some(frame as u32)
+    }
+}
+
+/// Plays a clip into each output's universe, going dark after its last frame
+pub struct SequencePlayer {
+    clip: SequenceClip,
+}
+
+impl SequencePlayer {
+    pub fn new(clip: SequenceClip) -> Self {
+        Self { clip }
+    }
+}
+
+impl Effect for SequencePlayer {
+    fn tick(&mut self, elapsed: f64, frame: &mut LedFrame) {
+        match self.clip.frame_at(elapsed) {
+            Some(n) => frame.set_dmx(&self.clip.file.universe_frame(n, frame.universe())),
+            None => frame.clear(),
+        }
+    }
+
+    fn set_color(&mut self, _color: [u8; 3]) {}
+}
+
+#[derive(Debug, Serialize)]
+pub struct SequenceInfo {
+    pub name: String,
+    pub size: u64,
+    pub step_ms: u8,
+    pub frame_count: u32,
+    pub channel_count: u32,
+    pub duration: f64,
+}
+
+/// Uploaded sequence files, kept next to the audio and parsed on first use
+pub struct SequenceLibrary {
+    dir: PathBuf,
+    cache: Mutex<HashMap<String, (SystemTime, Arc<FseqFile>)>>,
+}
+
+impl SequenceLibrary {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn resolve_path(&self, name: &str) -> Result<PathBuf, String> {
+        if !name.ends_with(".fseq") {
+            return Err(format!("Sequence file must end in .fseq: {}", name));
+        }
+        AudioFile::resolve_path(name, &self.dir).map_err(|e| e.to_string())
+    }
+
+    /// Parse, then store `bytes` as `name`, replacing any file of that name
+    pub fn save(&self, name: &str, bytes: &[u8]) -> Result<SequenceInfo, String> {
+        let path = self.resolve_path(name)?;
+        let file = FseqFile::parse(bytes)?;
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        fs::write(&path, bytes).map_err(|e| e.to_string())?;
+        self.cache.lock().unwrap().remove(name);
+        Ok(Self::info(name, bytes.len() as u64, &file))
+    }
+
+    /// The parsed file, reloaded if it changed on disk since it was cached
+    pub fn load(&self, name: &str) -> Result<Arc<FseqFile>, String> {
+        let path = self.resolve_path(name)?;
+        let modified = fs::metadata(&path)
+            .and_then(|m| m.modified())
+            .map_err(|_| format!("Sequence file not found: {}", name))?;
+
+        if let Some((cached_at, file)) = self.cache.lock().unwrap().get(name) {
+            if *cached_at == modified {
+                return Ok(file.clone());
+            }
+        }
+
+        let bytes = fs::read(&path).map_err(|e| e.to_string())?;
+        let file = Arc::new(FseqFile::parse(&bytes).map_err(|e| format!("{}: {}", name, e))?);
+        tracing::info!("Loaded sequence file: {}, {} frames of {} channels", name, file.frame_count, file.channel_count);
+        self.cache.lock().unwrap().insert(name.to_string(), (modified, file.clone()));
+        Ok(file)
+    }
+
+    /// Load `preset`'s file and check its frame range fits
+    pub fn clip(&self, preset: &SequencePreset) -> Result<SequenceClip, String> {
+        let file = self.load(&preset.file)?;
+        let end_frame = preset.end_frame.unwrap_or(file.frame_count);
+        if end_frame > file.frame_count {
+            return Err(format!(
+                "Sequence preset '{}' ends at frame {} but {} has {} frames",
+                preset.name, end_frame, preset.file, file.frame_count
+            ));
+        }
+        if preset.start_frame >= end_frame {
+            return Err(format!(
+                "Sequence preset '{}' has an empty frame range {}..{}",
+                preset.name, preset.start_frame, end_frame
+            ));
+        }
+        Ok(SequenceClip {
+            file,
+            start_frame: preset.start_frame,
+            end_frame,
+        })
+    }
+
+    pub fn delete(&self, name: &str) -> Result<(), String> {
+        let path = self.resolve_path(name)?;
+        if !path.exists() {
+            return Err(format!("Sequence file not found: {}", name));
+        }
+        fs::remove_file(&path).map_err(|e| e.to_string())?;
+        self.cache.lock().unwrap().remove(name);
+        Ok(())
+    }
+
+    /// Every readable sequence file, by name
+    pub fn list(&self) -> Vec<SequenceInfo> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .filter_map(|e| e.ok()?.file_name().into_string().ok())
+            .filter(|name| name.ends_with(".fseq"))
+            .collect();
+        names.sort();
+
+        names
+            .into_iter()
+            .filter_map(|name| {
+                let file = self.load(&name).ok()?;
+                let size = fs::metadata(self.dir.join(&name)).map(|m| m.len()).unwrap_or(0);
+                Some(Self::info(&name, size, &file))
+            })
+            .collect()
+    }
+
+    fn info(name: &str, size: u64, file: &FseqFile) -> SequenceInfo {
+        SequenceInfo {
+            name: name.to_string(),
+            size,
+            step_ms: file.step_ms,
+            frame_count: file.frame_count,
+            channel_count: file.channel_count,
+            duration: file.duration(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renders_cues_into_universe_slots() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "boards": [
+                { "id": "left", "ip": "10.0.0.1", "universe": 3, "led_count": 2 },
+                { "id": "right", "ip": "10.0.0.2", "universe": 1, "led_count": 2 }
+            ],
+            "effect_presets": [{ "name": "red", "effect_type": "solid", "color": [255, 0, 0] }]
+        }))
+        .unwrap();
+        let program = Program {
+            audio_file: Some("song.mp3".to_string()),
+            ..Program::fixture(
+                "song",
+                serde_json::json!([{ "time": 0.05, "label": "go", "targets": ["left"], "preset_name": "red" }]),
+            )
+        };
+
+        let sequences = SequenceLibrary::new("audio".into());
+        assert!(render(&program, &config, &sequences, 40, Some(7200.0)).is_err());
+        let sequence = render(&program, &config, &sequences, 40, Some(0.1)).unwrap();
+        assert_eq!(sequence.universes, vec![1, 3]);
+        assert_eq!(sequence.frame_count, 4);
+
+        let frame = |n: usize| &sequence.data[n * 1024..(n + 1) * 1024];
+        assert!(frame(1).iter().all(|c| *c == 0));
+        // Universe 3 is the second slot; universe 1 stays dark
+        assert_eq!(&frame(2)[512..520], &[255, 0, 0, 0, 255, 0, 0, 0]);
+        assert!(frame(2)[..512].iter().all(|c| *c == 0));
+
+        let bytes = sequence.to_fseq().unwrap();
+        let data_offset = u16::from_le_bytes([bytes[4], bytes[5]]) as usize;
+        assert_eq!(&bytes[0..4], b"PSEQ");
+        assert_eq!(bytes[7], 2);
+        assert_eq!(u32::from_le_bytes(bytes[10..14].try_into().unwrap()), 1024);
+        assert_eq!(bytes[18], 25);
+        assert_eq!(bytes[22], 2);
+        // Second range starts at channel (3 - 1) * 512
+        assert_eq!(&bytes[38..44], &[0x00, 0x04, 0x00, 0x00, 0x02, 0x00]);
+        assert_eq!(bytes.len(), data_offset + 4 * 1024);
+
+        let file = FseqFile::parse(&bytes).unwrap();
+        assert_eq!((file.step_ms, file.frame_count, file.channel_count), (25, 4, 1024));
+        assert_eq!(&file.universe_frame(2, 3)[..4], &[255, 0, 0, 0]);
+        assert!(file.universe_frame(2, 2).iter().all(|c| *c == 0));
+    }
+
+    #[test]
+    fn test_renders_effects_from_their_cue_time() {
+        let config: Config = serde_json::from_value(serde_json::json!({
+            "boards": [{ "id": "left", "ip": "10.0.0.1", "universe": 1, "led_count": 1 }],
+            "effect_presets": [{ "name": "flash", "effect_type": "flash", "color": [255, 0, 0] }]
+        }))
+        .unwrap();
+        let program = Program::fixture(
+            "song",
+            serde_json::json!([{ "time": 1.0, "label": "hit", "targets": ["left"], "preset_name": "flash" }]),
+        );
+
+        let sequences = SequenceLibrary::new("audio".into());
+        let sequence = render(&program, &config, &sequences, 40, Some(1.5)).unwrap();
+        let red = |frame: usize| sequence.data[frame * 512];
+        // The flash starts fresh at its cue (frame 40), not 1s into its fade
+        assert_eq!(red(39), 0);
+        assert_eq!(red(40), 255);
+        assert!(red(42) > 0 && red(42) < 255);
+        assert_eq!(red(50), 0);
+    }
+
+    #[test]
+    fn test_reads_compressed_sparse_ranges() {
+        // Two frames: channels 512-515 are universe 2's first four, 1022-1025 straddle universes 2 and 3
+        let data: Vec<u8> = (1..=16).collect();
+        let compressed =
+            ruzstd::encoding::compress_to_vec(&data[..], ruzstd::encoding::CompressionLevel::Fastest);
+
+        let data_offset = HEADER_LEN + COMPRESSION_BLOCK_LEN + 2 * SPARSE_RANGE_LEN;
+        let mut bytes = b"PSEQ".to_vec();
+        bytes.extend_from_slice(&(data_offset as u16).to_le_bytes());
+        bytes.extend_from_slice(&[0, 2]);
+        bytes.extend_from_slice(&(data_offset as u16).to_le_bytes());
+        bytes.extend_from_slice(&8u32.to_le_bytes());
+        bytes.extend_from_slice(&2u32.to_le_bytes());
+        bytes.extend_from_slice(&[50, 0, 1, 1, 2, 0]);
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&0u32.to_le_bytes());
+        bytes.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0x00, 0x02, 0x00, 0x04, 0x00, 0x00]);
+        bytes.extend_from_slice(&[0xFE, 0x03, 0x00, 0x04, 0x00, 0x00]);
+        bytes.extend_from_slice(&compressed);
+
+        let file = FseqFile::parse(&bytes).unwrap();
+        assert_eq!(file.duration(), 0.1);
+
+        let universe2 = file.universe_frame(1, 2);
+        assert_eq!(&universe2[..5], &[9, 10, 11, 12, 0]);
+        assert_eq!(&universe2[510..], &[13, 14]);
+        assert_eq!(&file.universe_frame(1, 3)[..3], &[15, 16, 0]);
+        assert!(file.universe_frame(0, 1).iter().all(|c| *c == 0));
+
+        let clip = SequenceClip { file: Arc::new(file), start_frame: 1, end_frame: 2 };
+        assert_eq!(clip.frame_at(0.0), Some(1));
+        assert_eq!(clip.frame_at(0.049), Some(1));
+        assert_eq!(clip.frame_at(0.05), None);
+
+        // A header claiming ~1e18 bytes is refused before anything is decompressed
+        let mut bomb = bytes.clone();
+        bomb[10..14].copy_from_slice(&u32::MAX.to_le_bytes());
+        bomb[14..18].copy_from_slice(&u32::MAX.to_le_bytes());
+        let err = FseqFile::parse(&bomb).unwrap_err();
+        assert!(err.contains("limit"), "{}", err);
+    }
+}
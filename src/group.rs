@@ -26,8 +26,9 @@ pub async fn execute_group_command(
     if use_e131 {
         // Try E1.31 send in separate scope to ensure guard is dropped
         {
-            let mut transports_lock = state.group_e131_transports.write().await;
-            if let Some(e131) = transports_lock.get_mut(group_id) {
+            let group_transport = state.transport_pool.group(group_id);
+            let mut transport_guard = group_transport.as_ref().and_then(|t| t.lock().ok());
+            if let Some(e131) = transport_guard.as_deref_mut() {
                 let universe = e131.universe();
                 let broadcast = e131.broadcast_addr();
                 info!(
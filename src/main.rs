@@ -9,20 +9,32 @@ use tower_http::cors::CorsLayer;
 
 mod actor;
 mod audio;
+mod audio_analysis;
 mod board;
+mod compositor;
 mod config;
 mod cue_scheduler;
+mod cue_suggestion;
 mod effects;
 mod effects_engine;
+mod fseq;
 mod group;
+mod marker_import;
+mod osc;
 mod pattern;
 mod pattern_engine;
 mod playback_history;
 mod preset;
 mod program;
+mod program_check;
 mod program_engine;
+mod program_revisions;
 mod routes;
+mod setlist;
+mod show_bundle;
 mod sse;
+mod tempo;
+mod tempo_map;
 mod timing_metrics;
 mod transport;
 mod types;
@@ -49,6 +61,7 @@ async fn main() {
     }
 
     let (broadcast_tx, _) = broadcast::channel::<SseEvent>(100);
+    let broadcast_tx = Arc::new(broadcast_tx);
 
     let loaded_config = Config::load().unwrap_or(Config {
         boards: vec![],
@@ -56,46 +69,13 @@ async fn main() {
         loopy_pro: config::LoopyProConfig::default(),
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
 
-    let mut group_e131_transports = HashMap::new();
-
-    for (universe_index, group) in loaded_config.groups.iter().enumerate() {
-        let mut group_board_ips: Vec<String> = Vec::new();
-        for member_id in &group.members {
-            if let Some(board) = loaded_config.boards.iter().find(|b| &b.id == member_id) {
-                if !group_board_ips.contains(&board.ip) {
-                    group_board_ips.push(board.ip.clone());
-                }
-            }
-        }
-
-        if !group_board_ips.is_empty() {
-            let universe = group.universe.unwrap_or((universe_index + 1) as u16);
-
-            info!(
-                group_id = %group.id,
-                universe = universe,
-                board_count = group_board_ips.len(),
-                "Initializing E1.31 transport for group: {:?}",
-                group_board_ips
-            );
-
-            match transport::E131RawTransport::new(group_board_ips, universe) {
-                Ok(transport) => {
-                    group_e131_transports.insert(group.id.clone(), transport);
-                    info!(group_id = %group.id, universe = universe, "E1.31 transport initialized");
-                }
-                Err(e) => {
-                    warn!(group_id = %group.id, "Failed to initialize E1.31 transport: {}", e);
-                }
-            }
-        } else {
-            warn!(group_id = %group.id, "No boards found for group - will use WebSocket only");
-        }
-    }
-
-    info!("Initialized {} E1.31 group transport(s)", group_e131_transports.len());
+    let timing_metrics = Arc::new(timing_metrics::TimingMetrics::new());
+    let transport_pool = transport::TransportPool::new(Some(timing_metrics.clone()));
+    transport_pool.rebuild(&loaded_config);
 
     info!("Configuring board E1.31 universes in parallel...");
     let mut config_tasks = Vec::new();
@@ -136,10 +116,11 @@ async fn main() {
         Err(_) => warn!("Universe configuration timed out after 10s - some boards may not be configured"),
     }
 
-    let timing_metrics = Arc::new(timing_metrics::TimingMetrics::new());
     let playback_history = Arc::new(playback_history::PlaybackHistory::new(storage_paths.history.clone()));
-    let effects_engine = Arc::new(effects_engine::EffectsEngine::new(Some(timing_metrics.clone())));
-    let pattern_engine = Arc::new(pattern_engine::PatternEngine::new());
+    let sequences = Arc::new(fseq::SequenceLibrary::new(storage_paths.audio.clone()));
+    let compositor_tx = compositor::Compositor::spawn(transport_pool.clone(), Some(timing_metrics.clone()));
+    let effects_engine = Arc::new(effects_engine::EffectsEngine::new(compositor_tx.clone()));
+    let pattern_engine = Arc::new(pattern_engine::PatternEngine::new(compositor_tx));
 
     let programs_map: HashMap<String, program::Program> =
         match program::Program::load_all(&storage_paths.programs) {
@@ -154,19 +135,45 @@ async fn main() {
         };
     let programs = Arc::new(RwLock::new(programs_map));
 
+    let setlists_map: HashMap<String, setlist::Setlist> =
+        match setlist::Setlist::load_all(&storage_paths.setlists) {
+            Ok(setlists) => {
+                info!("Loaded {} setlist(s) into memory", setlists.len());
+                setlists.into_iter().map(|s| (s.id.clone(), s)).collect()
+            }
+            Err(e) => {
+                warn!("Failed to load setlists: {} - starting with empty map", e);
+                HashMap::new()
+            }
+        };
+    let setlists = Arc::new(RwLock::new(setlists_map));
+
     let config_arc = Arc::new(Mutex::new(loaded_config.clone()));
     let performance_mode = Arc::new(std::sync::atomic::AtomicBool::new(false));
 
     let loopy_ip = loaded_config.loopy_pro.ip.clone();
     let loopy_port = loaded_config.loopy_pro.port;
+    let loopy_addresses = loaded_config.loopy_pro.addresses.clone();
 
-    let on_audio_play: program_engine::AudioPlayCallback = {
+    let on_audio: program_engine::AudioCallback = {
         let ip = loopy_ip.clone();
         let port = loopy_port;
-        Arc::new(move |track: &str| {
-            let address = format!("/Play/0:{}", track);
-            if let Err(e) = routes::send_osc_sync(&ip, port, &address) {
-                eprintln!("Failed to send OSC play: {}", e);
+        Arc::new(move |track: &str, command: program_engine::AudioCommand| {
+            use program_engine::AudioCommand;
+            let (verb, template, args) = match command {
+                AudioCommand::Play => ("play", &loopy_addresses.play, vec![]),
+                AudioCommand::Pause => ("pause", &loopy_addresses.pause, vec![]),
+                AudioCommand::Resume => ("resume", &loopy_addresses.resume, vec![]),
+                AudioCommand::Seek(seconds) => {
+                    ("seek", &loopy_addresses.seek, vec![rosc::OscType::Float(seconds as f32)])
+                }
+            };
+            if template.is_empty() {
+                return;
+            }
+            let address = template.replace("{track}", track);
+            if let Err(e) = routes::send_osc_sync(&ip, port, &address, args) {
+                eprintln!("Failed to send OSC {}: {}", verb, e);
             }
         })
     };
@@ -178,26 +185,31 @@ async fn main() {
         effects_engine.clone(),
         pattern_engine.clone(),
         performance_mode.clone(),
-        Some(on_audio_play),
+        Some(on_audio),
         connected_ips.clone(),
         Some(timing_metrics.clone()),
         Some(playback_history.clone()),
+        programs.clone(),
+        broadcast_tx.clone(),
+        sequences.clone(),
     ));
 
     let state: SharedState = Arc::new(AppState {
         boards: Arc::new(RwLock::new(HashMap::new())),
-        broadcast_tx: Arc::new(broadcast_tx),
+        broadcast_tx,
         storage_paths: Arc::new(storage_paths),
-        group_e131_transports: Arc::new(RwLock::new(group_e131_transports)),
+        transport_pool,
         config: config_arc,
         effects_engine,
         pattern_engine,
         programs,
+        setlists,
         program_engine,
         connected_ips: connected_ips.clone(),
         performance_mode: performance_mode.clone(),
         timing_metrics,
         playback_history,
+        sequences,
     });
 
     match Config::load() {
@@ -238,10 +250,17 @@ async fn main() {
         }
     }
 
+    let osc_port: u16 = std::env::var("OSC_PORT")
+        .ok()
+        .and_then(|p| p.parse().ok())
+        .unwrap_or(9000);
+    tokio::spawn(osc::run(state.clone(), osc_port));
+
     let cors = CorsLayer::new()
         .allow_origin(tower_http::cors::Any)
-        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
-        .allow_headers(tower_http::cors::Any);
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
+        .allow_headers(tower_http::cors::Any)
+        .expose_headers([axum::http::header::ETAG]);
 
     let api_router = routes::build_api_router(state.clone());
 
@@ -0,0 +1,382 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use crate::config::MarkerMapping;
+use crate::program::{Cue, CueDuration, CueRelease};
+use crate::tempo_map::{BeatPosition, TempoMap};
+
+/// MIDI's default tempo when a file has no tempo event: 120 BPM
+const DEFAULT_MICROS_PER_BEAT: f64 = 500_000.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MarkerFormat {
+    /// Audacity label track export: `start<TAB>end<TAB>label`
+    Audacity,
+    /// Reaper "Markers and regions" CSV export
+    ReaperCsv,
+    /// Standard MIDI File marker, cue point and note events
+    Midi,
+}
+
+/// Where a marker sits; Reaper exports bars and beats when the ruler shows them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkerTime {
+    Seconds(f64),
+    Position(BeatPosition),
+}
+
+/// A marker or region read from a DAW export
+#[derive(Debug, Clone, PartialEq)]
+pub struct Marker {
+    pub name: String,
+    pub at: MarkerTime,
+    /// Regions and MIDI notes end here
+    pub end: Option<MarkerTime>,
+    /// MIDI note number, for note events
+    pub note: Option<u8>,
+}
+
+/// Cues made from markers, and the markers no mapping matched
+#[derive(Debug, Clone, Serialize)]
+pub struct MarkerImport {
+    pub cues: Vec<Cue>,
+    pub unmatched: Vec<String>,
+}
+
+pub fn parse(format: MarkerFormat, data: &[u8]) -> Result<Vec<Marker>, String> {
+    match format {
+        MarkerFormat::Audacity => parse_audacity(&text(data)?),
+        MarkerFormat::ReaperCsv => parse_reaper_csv(&text(data)?),
+        MarkerFormat::Midi => parse_midi(data),
+    }
+}
+
+fn text(data: &[u8]) -> Result<String, String> {
+    let text = std::str::from_utf8(data).map_err(|_| "Marker file is not UTF-8 text".to_string())?;
+    Ok(text.trim_start_matches('\u{feff}').to_string())
+}
+
+fn parse_audacity(text: &str) -> Result<Vec<Marker>, String> {
+    let mut markers = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        // Spectral selections add a `\tlow\thigh` line under their label
+        if line.trim().is_empty() || line.starts_with('\\') {
+            continue;
+        }
+        let mut fields = line.splitn(3, '\t');
+        let seconds = |field: Option<&str>| {
+            field
+                .and_then(|f| f.trim().parse::<f64>().ok())
+                .filter(|s| s.is_finite() && *s >= 0.0)
+                .ok_or_else(|| format!("Line {}: expected start<TAB>end<TAB>label", idx + 1))
+        };
+        let start = seconds(fields.next())?;
+        let end = seconds(fields.next())?;
+        markers.push(Marker {
+            name: fields.next().unwrap_or("").trim().to_string(),
+            at: MarkerTime::Seconds(start),
+            end: (end > start).then_some(MarkerTime::Seconds(end)),
+            note: None,
+        });
+    }
+    Ok(markers)
+}
+
+fn parse_reaper_csv(text: &str) -> Result<Vec<Marker>, String> {
+    let mut lines = text.lines().filter(|l| !l.trim().is_empty());
+    let header = split_csv(lines.next().ok_or("Marker CSV is empty")?);
+    let column = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let name_col = column("Name").ok_or("Marker CSV has no Name column")?;
+    let start_col = column("Start").ok_or("Marker CSV has no Start column")?;
+    let end_col = column("End");
+
+    let mut markers = Vec::new();
+    for (idx, line) in lines.enumerate() {
+        let fields = split_csv(line);
+        let field = |col: usize| fields.get(col).map(|f| f.trim()).unwrap_or("");
+        let at = reaper_time(field(start_col)).map_err(|e| format!("Row {}: {}", idx + 1, e))?;
+        let end = match end_col.map(field).filter(|f| !f.is_empty()) {
+            Some(end) => Some(reaper_time(end).map_err(|e| format!("Row {}: {}", idx + 1, e))?),
+            None => None,
+        };
+        markers.push(Marker {
+            name: field(name_col).to_string(),
+            at,
+            end,
+            note: None,
+        });
+    }
+    Ok(markers)
+}
+
+/// `1:02.500`, `0:01:02.500`, `62.5` seconds, or `17.3.50` bar.beat.percent
+fn reaper_time(field: &str) -> Result<MarkerTime, String> {
+    let invalid = || format!("Unsupported time '{}'", field);
+    if field.contains(':') {
+        let mut seconds = 0.0;
+        for part in field.split(':') {
+            seconds = seconds * 60.0 + part.parse::<f64>().map_err(|_| invalid())?;
+        }
+        return Ok(MarkerTime::Seconds(seconds));
+    }
+
+    let parts: Vec<&str> = field.split('.').collect();
+    if parts.len() == 3 {
+        let number = |s: &str| s.parse::<u32>().map_err(|_| invalid());
+        let (bar, beat, percent) = (number(parts[0])?, number(parts[1])?, number(parts[2])?);
+        if bar == 0 || beat == 0 || percent >= 100 {
+            return Err(invalid());
+        }
+        return Ok(MarkerTime::Position(BeatPosition {
+            bar,
+            beat,
+            tick: percent * 480 / 100,
+        }));
+    }
+
+    field
+        .parse::<f64>()
+        .ok()
+        .filter(|s| s.is_finite() && *s >= 0.0)
+        .map(MarkerTime::Seconds)
+        .ok_or_else(invalid)
+}
+
+/// Split a CSV line, honouring double quotes and `""` escapes
+fn split_csv(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut current)),
+            _ => current.push(c),
+        }
+    }
+    fields.push(current);
+    fields
+}
+
+fn parse_midi(data: &[u8]) -> Result<Vec<Marker>, String> {
+    let smf = Smf::parse(data).map_err(|e| format!("Invalid MIDI file: {}", e))?;
+
+    // Absolute ticks for every event; format 1 files keep tempo on the first track
+    let mut tempos: Vec<(u64, f64)> = Vec::new();
+    let mut events = Vec::new();
+    for track in &smf.tracks {
+        let mut tick = 0u64;
+        for event in track {
+            tick += event.delta.as_int() as u64;
+            if let TrackEventKind::Meta(MetaMessage::Tempo(micros)) = event.kind {
+                tempos.push((tick, micros.as_int() as f64));
+            } else {
+                events.push((tick, event.kind));
+            }
+        }
+    }
+    tempos.sort_by_key(|(tick, _)| *tick);
+
+    let seconds_at = |tick: u64| -> f64 {
+        match smf.header.timing {
+            Timing::Metrical(ppq) => {
+                let ppq = ppq.as_int().max(1) as f64;
+                let (mut seconds, mut last_tick, mut micros) = (0.0, 0u64, DEFAULT_MICROS_PER_BEAT);
+                for &(change, tempo) in tempos.iter().take_while(|(change, _)| *change < tick) {
+                    seconds += (change - last_tick) as f64 / ppq * micros / 1_000_000.0;
+                    last_tick = change;
+                    micros = tempo;
+                }
+                seconds + (tick - last_tick) as f64 / ppq * micros / 1_000_000.0
+            }
+            Timing::Timecode(fps, subframes) => tick as f64 / (fps.as_f32() as f64 * subframes.max(1) as f64),
+        }
+    };
+
+    events.sort_by_key(|(tick, _)| *tick);
+    let mut markers: Vec<Marker> = Vec::new();
+    let mut sounding: HashMap<u8, usize> = HashMap::new();
+    for (tick, kind) in events {
+        let seconds = seconds_at(tick);
+        match kind {
+            TrackEventKind::Meta(MetaMessage::Marker(name) | MetaMessage::CuePoint(name)) => markers.push(Marker {
+                name: String::from_utf8_lossy(name).trim().to_string(),
+                at: MarkerTime::Seconds(seconds),
+                end: None,
+                note: None,
+            }),
+            TrackEventKind::Midi { message: MidiMessage::NoteOn { key, vel }, .. } if vel.as_int() > 0 => {
+                let key = key.as_int();
+                sounding.insert(key, markers.len());
+                markers.push(Marker {
+                    name: format!("Note {}", key),
+                    at: MarkerTime::Seconds(seconds),
+                    end: None,
+                    note: Some(key),
+                });
+            }
+            TrackEventKind::Midi {
+                message: MidiMessage::NoteOff { key, .. } | MidiMessage::NoteOn { key, .. },
+                ..
+            } => {
+                if let Some(idx) = sounding.remove(&key.as_int()) {
+                    markers[idx].end = Some(MarkerTime::Seconds(seconds));
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(markers)
+}
+
+impl MarkerTime {
+    fn seconds(&self, tempo_map: &TempoMap) -> Result<f64, String> {
+        match self {
+            MarkerTime::Seconds(seconds) => Ok(*seconds),
+            MarkerTime::Position(position) => tempo_map.seconds_at(position),
+        }
+    }
+}
+
+/// The mapping for `marker`: its note, else an exact name, else the longest name prefix
+fn find_mapping<'a>(marker: &Marker, mappings: &'a [MarkerMapping]) -> Option<&'a MarkerMapping> {
+    if let Some(note) = marker.note {
+        return mappings.iter().find(|m| m.note == Some(note));
+    }
+    let name = marker.name.to_lowercase();
+    mappings
+        .iter()
+        .filter_map(|m| Some((m, m.marker.as_deref()?.to_lowercase())))
+        .filter(|(_, pattern)| !pattern.is_empty() && name.starts_with(pattern.as_str()))
+        .max_by_key(|(_, pattern)| (*pattern == name, pattern.len()))
+        .map(|(m, _)| m)
+}
+
+/// Cues for the markers a mapping matches, in time order
+///
+/// Bar/beat markers keep their position so they follow later tempo edits;
+/// regions and notes become cues with a duration.
+pub fn to_cues(
+    markers: &[Marker],
+    mappings: &[MarkerMapping],
+    default_target: &str,
+    tempo_map: &TempoMap,
+) -> Result<MarkerImport, String> {
+    let mut cues = Vec::new();
+    let mut unmatched = Vec::new();
+    for marker in markers {
+        let Some(mapping) = find_mapping(marker, mappings) else {
+            unmatched.push(marker.name.clone());
+            continue;
+        };
+
+        let time = marker.at.seconds(tempo_map)?;
+        let duration = match marker.end {
+            Some(end) => Some(end.seconds(tempo_map)? - time).filter(|d| *d > 0.0),
+            None => None,
+        };
+        let targets = if mapping.targets.is_empty() {
+            vec![default_target.to_string()]
+        } else {
+            mapping.targets.clone()
+        };
+
+        cues.push(Cue {
+            time,
+            position: match marker.at {
+                MarkerTime::Position(position) => Some(position),
+                MarkerTime::Seconds(_) => None,
+            },
+            label: if marker.name.is_empty() { mapping.preset_name.clone() } else { marker.name.clone() },
+            targets,
+            preset_name: mapping.preset_name.clone(),
+            sync_rate: 1.0,
+            duration: duration.map(CueDuration::Seconds),
+            release: CueRelease::default(),
+            wait: None,
+            follow: None,
+        });
+    }
+    cues.sort_by(|a, b| a.time.total_cmp(&b.time));
+    Ok(MarkerImport { cues, unmatched })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(marker: Option<&str>, note: Option<u8>, preset: &str) -> MarkerMapping {
+        MarkerMapping {
+            marker: marker.map(str::to_string),
+            note,
+            preset_name: preset.to_string(),
+            targets: vec![],
+        }
+    }
+
+    #[test]
+    fn test_text_formats() {
+        let audacity = parse(MarkerFormat::Audacity, b"0.000000\t0.000000\tIntro\n12.5\t20.0\tChorus 2\n\\\t100.0\t2000.0\n").unwrap();
+        assert_eq!(audacity.len(), 2);
+        assert_eq!(audacity[1].at, MarkerTime::Seconds(12.5));
+        assert_eq!(audacity[1].end, Some(MarkerTime::Seconds(20.0)));
+
+        let csv = "#,Name,Start,End,Length,Color\nM1,\"Verse, quiet\",1:02.500,,,\nR1,Bridge,17.3.50,21.1.00,4.0.00,\n";
+        let reaper = parse(MarkerFormat::ReaperCsv, csv.as_bytes()).unwrap();
+        assert_eq!(reaper[0].name, "Verse, quiet");
+        assert_eq!(reaper[0].at, MarkerTime::Seconds(62.5));
+        assert_eq!(reaper[1].at, MarkerTime::Position(BeatPosition { bar: 17, beat: 3, tick: 240 }));
+
+        let mappings = vec![
+            mapping(Some("chorus"), None, "bright"),
+            mapping(Some("verse"), None, "calm"),
+            mapping(Some("bridge"), None, "strobe"),
+        ];
+        let tempo_map = TempoMap::constant(120.0, 0.0);
+        let import = to_cues(&audacity, &mappings, "stage", &tempo_map).unwrap();
+        assert_eq!(import.unmatched, vec!["Intro".to_string()]);
+        assert_eq!(import.cues[0].preset_name, "bright");
+        assert_eq!(import.cues[0].duration, Some(CueDuration::Seconds(7.5)));
+        assert_eq!(import.cues[0].targets, vec!["stage".to_string()]);
+
+        let import = to_cues(&reaper, &mappings, "stage", &tempo_map).unwrap();
+        // Bar 17 beat 3.5 at 120 BPM in 4/4
+        assert_eq!(import.cues[0].time, 33.25);
+        assert!(import.cues[0].position.is_some());
+        assert_eq!(import.cues[1].preset_name, "calm");
+    }
+
+    #[test]
+    fn test_midi_markers_and_notes() {
+        #[rustfmt::skip]
+        let track: &[u8] = &[
+            0x00, 0xFF, 0x51, 0x03, 0x0F, 0x42, 0x40, // 60 BPM
+            0x00, 0xFF, 0x06, 0x05, b'I', b'n', b't', b'r', b'o',
+            0x83, 0x60, 0x90, 0x24, 0x64, // note 36 on after one beat
+            0x83, 0x60, 0x90, 0x24, 0x00, // off a beat later
+            0x00, 0xFF, 0x2F, 0x00,
+        ];
+        let mut smf = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x01\xE0MTrk".to_vec();
+        smf.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        smf.extend_from_slice(track);
+
+        let markers = parse(MarkerFormat::Midi, &smf).unwrap();
+        assert_eq!(markers[0].name, "Intro");
+        assert_eq!(markers[1].note, Some(36));
+        assert_eq!(markers[1].at, MarkerTime::Seconds(1.0));
+        assert_eq!(markers[1].end, Some(MarkerTime::Seconds(2.0)));
+
+        let import = to_cues(&markers, &[mapping(None, Some(36), "kick")], "stage", &TempoMap::constant(120.0, 0.0)).unwrap();
+        assert_eq!(import.cues.len(), 1);
+        assert_eq!(import.cues[0].label, "Note 36");
+        assert_eq!(import.unmatched, vec!["Intro".to_string()]);
+    }
+}
@@ -0,0 +1,185 @@
+use std::time::Instant;
+
+use rosc::{OscMessage, OscPacket, OscType};
+use tokio::net::UdpSocket;
+use tracing::{debug, error, info, warn};
+
+use crate::effects::EffectParams;
+use crate::effects_engine::EngineCommand;
+use crate::pattern_engine::PatternCommand;
+use crate::routes::tempo;
+use crate::types::SharedState;
+
+/// Listen for incoming OSC control messages (TouchOSC, tap pads, etc.)
+///
+/// `/loop/arm <name>` and `/loop/release` drive the playing program's loop regions;
+/// `/cue/go`, `/cue/back` and `/cue/jump <index>` drive a cue-stack program.
+pub async fn run(state: SharedState, port: u16) {
+    let addr = format!("0.0.0.0:{}", port);
+    let socket = match UdpSocket::bind(&addr).await {
+        Ok(s) => {
+            info!("OSC listener running on udp://{}", addr);
+            s
+        }
+        Err(e) => {
+            error!("Failed to bind OSC listener to {}: {}", addr, e);
+            return;
+        }
+    };
+
+    let mut buf = [0u8; rosc::decoder::MTU];
+    loop {
+        let len = match socket.recv_from(&mut buf).await {
+            Ok((len, _)) => len,
+            Err(e) => {
+                warn!("OSC receive error: {}", e);
+                continue;
+            }
+        };
+
+        match rosc::decoder::decode_udp(&buf[..len]) {
+            Ok((_, packet)) => handle_packet(&state, packet).await,
+            Err(e) => warn!("Failed to decode OSC packet: {}", e),
+        }
+    }
+}
+
+async fn handle_packet(state: &SharedState, packet: OscPacket) {
+    match packet {
+        OscPacket::Message(msg) => handle_message(state, msg).await,
+        OscPacket::Bundle(bundle) => {
+            for p in bundle.content {
+                Box::pin(handle_packet(state, p)).await;
+            }
+        }
+    }
+}
+
+async fn handle_message(state: &SharedState, msg: OscMessage) {
+    let received_at = Instant::now();
+
+    let result = match msg.addr.as_str() {
+        "/tempo/bpm" => match arg_f64(&msg.args, 0) {
+            Some(bpm) if bpm > 0.0 => tempo::send_set_bpm(state, bpm),
+            _ => {
+                warn!("OSC {} needs a positive BPM argument", msg.addr);
+                return;
+            }
+        },
+        "/tempo/tap" => {
+            // Pads send a press and a release; only the press is a tap
+            if arg_f64(&msg.args, 0).is_some_and(|v| v <= 0.0) {
+                return;
+            }
+            tempo::send_tap(state, received_at)
+        }
+        "/loop/arm" => {
+            let Some(OscType::String(name)) = msg.args.first() else {
+                warn!("OSC {} needs the loop region name", msg.addr);
+                return;
+            };
+            state
+                .program_engine
+                .arm_loop(name)
+                .await
+                .map_err(|e| (axum::http::StatusCode::CONFLICT, e))
+        }
+        "/loop/release" => {
+            if arg_f64(&msg.args, 0).is_some_and(|v| v <= 0.0) {
+                return;
+            }
+            state
+                .program_engine
+                .release_loop()
+                .await
+                .map_err(|e| (axum::http::StatusCode::CONFLICT, e))
+        }
+        "/cue/go" | "/cue/back" => {
+            if arg_f64(&msg.args, 0).is_some_and(|v| v <= 0.0) {
+                return;
+            }
+            let moved = if msg.addr == "/cue/go" {
+                state.program_engine.go().await
+            } else {
+                state.program_engine.back().await
+            };
+            moved.map(|_| ()).map_err(|e| (axum::http::StatusCode::CONFLICT, e))
+        }
+        "/cue/jump" => {
+            let Some(index) = arg_f64(&msg.args, 0).filter(|i| *i >= 0.0) else {
+                warn!("OSC {} needs the cue index", msg.addr);
+                return;
+            };
+            state
+                .program_engine
+                .jump_to_cue(index as usize)
+                .await
+                .map(|_| ())
+                .map_err(|e| (axum::http::StatusCode::CONFLICT, e))
+        }
+        addr => {
+            let Some((engine, param)) = addr.trim_start_matches('/').split_once('/') else {
+                debug!("Unhandled OSC address: {}", msg.addr);
+                return;
+            };
+            let Some(params) = parse_params(param, &msg.args) else {
+                debug!("Unhandled OSC address or bad arguments: {} {:?}", msg.addr, msg.args);
+                return;
+            };
+            if let Err(e) = params.validate() {
+                warn!("OSC {}: {}", msg.addr, e);
+                return;
+            }
+            let sent = match engine {
+                "effects" => state.effects_engine.send_command(EngineCommand::SetParams(params)),
+                "patterns" => state.pattern_engine.send_command(PatternCommand::SetParams(params)),
+                _ => {
+                    debug!("Unhandled OSC address: {}", msg.addr);
+                    return;
+                }
+            };
+            sent.map_err(|e| (axum::http::StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    };
+
+    if let Err((_, e)) = result {
+        error!("OSC {} failed: {}", msg.addr, e);
+    }
+}
+
+/// Map `/effects/<param>` and `/patterns/<param>` arguments to a parameter update
+///
+/// `color` takes three values, either 0-255 integers or 0.0-1.0 floats (TouchOSC
+/// faders); the others take a single value.
+fn parse_params(param: &str, args: &[OscType]) -> Option<EffectParams> {
+    let mut params = EffectParams::default();
+    match param {
+        "color" | "colour" => {
+            let values: Vec<f64> = (0..3).map(|i| arg_f64(args, i)).collect::<Option<_>>()?;
+            let normalised = args.iter().any(|a| matches!(a, OscType::Float(_) | OscType::Double(_)))
+                && values.iter().all(|v| *v <= 1.0);
+            let scale = if normalised { 255.0 } else { 1.0 };
+            params.color = Some([
+                (values[0] * scale).clamp(0.0, 255.0) as u8,
+                (values[1] * scale).clamp(0.0, 255.0) as u8,
+                (values[2] * scale).clamp(0.0, 255.0) as u8,
+            ]);
+        }
+        "intensity" => params.intensity = Some(arg_f64(args, 0)?),
+        "speed" => params.speed = Some(arg_f64(args, 0)?),
+        "brightness" => params.brightness = Some(arg_f64(args, 0)?),
+        _ => return None,
+    }
+    Some(params)
+}
+
+fn arg_f64(args: &[OscType], idx: usize) -> Option<f64> {
+    match args.get(idx)? {
+        OscType::Float(v) => Some(*v as f64),
+        OscType::Double(v) => Some(*v),
+        OscType::Int(v) => Some(*v as f64),
+        OscType::Long(v) => Some(*v as f64),
+        OscType::Bool(v) => Some(if *v { 1.0 } else { 0.0 }),
+        _ => None,
+    }
+}
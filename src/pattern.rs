@@ -10,9 +10,11 @@ pub struct PatternStep {
 pub struct PatternSequence {
     pub steps: Vec<PatternStep>,
     pub total_duration_ms: u64,
+    pub bpm: f64,
 }
 
-pub fn transform_board_order(members: &[String], pattern: &PatternType) -> Vec<Vec<String>> {
+/// Board sets for each step; `custom_steps` is only used by `PatternType::Custom`
+pub fn transform_board_order(members: &[String], pattern: &PatternType, custom_steps: &[Vec<String>]) -> Vec<Vec<String>> {
     let n = members.len();
     match pattern {
         PatternType::Wave => members.iter().map(|b| vec![b.clone()]).collect(),
@@ -37,7 +39,7 @@ pub fn transform_board_order(members: &[String], pattern: &PatternType) -> Vec<V
             steps
         }
         PatternType::CenterOut => {
-            let mut steps = transform_board_order(members, &PatternType::OutsideIn);
+            let mut steps = transform_board_order(members, &PatternType::OutsideIn, &[]);
             steps.reverse();
             steps
         }
@@ -46,16 +48,22 @@ pub fn transform_board_order(members: &[String], pattern: &PatternType) -> Vec<V
             shuffled.shuffle(&mut rand::rng());
             shuffled
         }
+        PatternType::Custom => custom_steps
+            .iter()
+            .map(|step| step.iter().filter(|b| members.contains(b)).cloned().collect::<Vec<_>>())
+            .filter(|step| !step.is_empty())
+            .collect(),
     }
 }
 
 pub fn generate_sequence(
     members: &[String],
     pattern: &PatternType,
+    custom_steps: &[Vec<String>],
     bpm: f64,
     sync_rate: f64,
 ) -> PatternSequence {
-    let board_groups = transform_board_order(members, pattern);
+    let board_groups = transform_board_order(members, pattern, custom_steps);
     let beat_duration_ms = 60_000.0 / bpm;
     let total_duration_ms = (beat_duration_ms / sync_rate) as u64;
 
@@ -64,5 +72,5 @@ pub fn generate_sequence(
     })
     .collect();
 
-    PatternSequence { steps, total_duration_ms }
+    PatternSequence { steps, total_duration_ms, bpm }
 }
@@ -1,13 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::mpsc;
-use std::thread;
 use std::time::{Duration, Instant};
 
 use rand::Rng;
-use tracing::info;
+use tracing::{info, warn};
 
+use crate::compositor::{CompositorCommand, Outputs};
+use crate::config::{ColourMode, PatternEnvelope, PatternStepEffect};
+use crate::effects::{Effect, EffectParams, EffectType};
 use crate::pattern::PatternSequence;
-use crate::transport::E131RawTransport;
+use crate::tempo::{BeatClock, TapTempo};
+use crate::transport::OutputKey;
 
 #[derive(Debug, Clone)]
 pub struct BoardInfo {
@@ -16,295 +19,628 @@ pub struct BoardInfo {
     pub led_count: usize,
 }
 
-struct PatternState {
-    sequence: PatternSequence,
-    color: [u8; 3],
-    transports: HashMap<String, (E131RawTransport, usize)>,
-    cycle_count: u64,
-    is_random: bool,
-    is_ping_pong: bool,
-    prev_chosen: Option<String>,
+/// Live-adjustable appearance of the running pattern
+#[derive(Debug, Clone)]
+struct PatternLook {
+    palette: Vec<[u8; 3]>,
+    colour_mode: ColourMode,
+    /// Live colour change; stands in for the first palette colour only, so the
+    /// rest of a multi-colour preset keeps cycling
+    colour_override: Option<[u8; 3]>,
+    brightness: f64,
+    intensity: f64,
+}
+
+impl PatternLook {
+    fn new(palette: Vec<[u8; 3]>, colour_mode: ColourMode) -> Self {
+        Self {
+            palette: if palette.is_empty() { vec![[255, 255, 255]] } else { palette },
+            colour_mode,
+            colour_override: None,
+            brightness: 1.0,
+            intensity: 0.5,
+        }
+    }
+
+    /// Palette colour for a step within a cycle
+    fn colour(&self, step_idx: usize, cycle_count: u64) -> [u8; 3] {
+        let idx = match self.colour_mode {
+            ColourMode::Single => 0,
+            ColourMode::PerStep => step_idx,
+            ColourMode::PerCycle => cycle_count as usize,
+        };
+        match (idx % self.palette.len(), self.colour_override) {
+            (0, Some(colour)) => colour,
+            (idx, _) => self.palette[idx],
+        }
+    }
+
+    /// `colour` at `level` (0.0 - 1.0); master brightness is applied on output
+    fn rgb(colour: [u8; 3], scale: f64) -> (u8, u8, u8) {
+        (
+            (colour[0] as f64 * scale) as u8,
+            (colour[1] as f64 * scale) as u8,
+            (colour[2] as f64 * scale) as u8,
+        )
+    }
+}
+
+fn apply_params(clock: &mut BeatClock, look: &mut PatternLook, params: EffectParams) {
+    info!(params = ?params, "Pattern engine params changed");
+    if let Some(color) = params.color {
+        look.colour_override = Some(color);
+    }
+    if let Some(intensity) = params.intensity {
+        look.intensity = intensity;
+    }
+    if let Some(brightness) = params.brightness {
+        look.brightness = brightness;
+    }
+    if let Some(speed) = params.speed {
+        clock.set_speed(Instant::now(), speed);
+    }
+}
+
+/// Effect each step plays instead of a flat colour
+#[derive(Debug, Clone, Copy)]
+struct StepEffect {
+    effect_type: EffectType,
+    intensity: Option<f64>,
+}
+
+impl StepEffect {
+    fn from_preset(step_effect: &PatternStepEffect) -> Option<Self> {
+        match step_effect.effect_type() {
+            Ok(effect_type) => Some(Self { effect_type, intensity: step_effect.intensity }),
+            Err(e) => {
+                warn!("Pattern step effect ignored: {}", e);
+                None
+            }
+        }
+    }
+
+    fn create(&self, colour: [u8; 3], bpm: f64) -> Box<dyn Effect> {
+        let mut effect = self.effect_type.create(colour, bpm);
+        if let Some(intensity) = self.intensity {
+            effect.set_intensity(intensity);
+        }
+        effect
+    }
+}
+
+/// A step whose boards are running their own effect instances
+struct PlayingStep {
+    cycle: u64,
+    position: usize,
+    effects: Vec<(String, Box<dyn Effect>)>,
 }
 
 #[derive(Debug)]
 pub enum PatternCommand {
     Start {
         sequence: PatternSequence,
-        color: [u8; 3],
+        palette: Vec<[u8; 3]>,
+        colour_mode: ColourMode,
+        envelope: PatternEnvelope,
+        step_effect: Option<PatternStepEffect>,
         boards: HashMap<String, BoardInfo>,
         is_random: bool,
         is_ping_pong: bool,
+        /// Seconds into the pattern to start from
+        phase: f64,
     },
+    /// Change the song tempo of the running pattern without restarting it
+    SetBpm { bpm: f64 },
+    /// Tap tempo; `at` is when the tap was received
+    Tap { at: Instant },
+    /// Update colour, intensity, speed or brightness of the running pattern
+    SetParams(EffectParams),
+    /// Fade the running pattern to black over `duration`, then stop it
+    FadeOut { duration: Duration },
+    /// Freeze the pattern clock; patterns started while paused start frozen
+    Pause,
+    Resume,
     Stop,
 }
 
+fn set_bpm(clock: &mut BeatClock, base_bpm: f64, bpm: f64) {
+    if base_bpm <= 0.0 || bpm <= 0.0 {
+        return;
+    }
+    clock.set_rate(Instant::now(), bpm / base_bpm);
+    info!(bpm = bpm, rate = bpm / base_bpm, "Pattern engine tempo changed");
+}
+
+fn tap(clock: &mut BeatClock, tap_tempo: &mut TapTempo, base_bpm: f64, at: Instant) {
+    if let Some(bpm) = tap_tempo.tap(at) {
+        set_bpm(clock, base_bpm, bpm);
+    }
+    if base_bpm > 0.0 {
+        clock.align_to_beat(Instant::now(), at, 60.0 / base_bpm);
+    }
+}
+
+/// Trail levels from the head backwards; intensity 0.5 gives the preset trail
+fn trail_levels(envelope: &PatternEnvelope, intensity: f64) -> Vec<f64> {
+    let len = envelope.trail_length.max(1);
+    (0..len)
+        .map(|idx| {
+            let base = envelope.decay.trail_level(idx, len);
+            if idx == 0 {
+                base
+            } else {
+                (base * 2.0 * intensity).min(1.0)
+            }
+        })
+        .collect()
+}
+
+/// Level of a random-pattern flash `since_ms` after the beat; intensity 0.5
+/// gives the preset release
+fn flash_level(envelope: &PatternEnvelope, intensity: f64, since_ms: f64) -> f64 {
+    let attack_ms = envelope.attack_ms as f64;
+    let hold_end_ms = attack_ms + envelope.hold_ms as f64;
+    let release_ms = envelope.release_ms as f64 * 2.0 * intensity;
+
+    if since_ms < attack_ms {
+        since_ms / attack_ms
+    } else if since_ms < hold_end_ms {
+        1.0
+    } else if release_ms <= 0.0 {
+        0.0
+    } else {
+        envelope.decay.level((since_ms - hold_end_ms) / release_ms)
+    }
+}
+
+/// Level of every step (in play order) at `offset_ms` into a wave cycle
+///
+/// The newest step fades in over the attack with the trail dimming behind
+/// it; after the last step the whole trail holds, then releases to black.
+fn wave_levels(
+    envelope: &PatternEnvelope,
+    intensity: f64,
+    num_steps: usize,
+    step_interval_ms: f64,
+    offset_ms: f64,
+) -> Vec<f64> {
+    let mut levels = vec![0.0; num_steps];
+    if num_steps == 0 {
+        return levels;
+    }
+
+    let fired = if step_interval_ms > 0.0 {
+        ((offset_ms / step_interval_ms).floor() as usize + 1).min(num_steps)
+    } else {
+        1
+    };
+    let head = fired - 1;
+    let head_ms = head as f64 * step_interval_ms;
+    let attack_ms = envelope.attack_ms as f64;
+
+    let attack_gain = if attack_ms > 0.0 {
+        ((offset_ms - head_ms) / attack_ms).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+
+    let mut gain = 1.0;
+    if head == num_steps - 1 {
+        let release_start_ms = head_ms + attack_ms + envelope.hold_ms as f64;
+        if offset_ms >= release_start_ms {
+            let release_ms = envelope.release_ms as f64;
+            gain = if release_ms > 0.0 {
+                envelope.decay.release_level((offset_ms - release_start_ms) / release_ms)
+            } else {
+                0.0
+            };
+        }
+    }
+
+    for (trail_idx, level) in trail_levels(envelope, intensity).into_iter().enumerate().take(head + 1) {
+        let head_gain = if trail_idx == 0 { attack_gain } else { 1.0 };
+        levels[head - trail_idx] = level * gain * head_gain;
+    }
+    levels
+}
+
+/// Step spacing: the largest power-of-two division of the cycle that fits the wave
+fn step_interval_ms(total_ms: f64, wave_duration_ms: f64, num_steps: usize) -> f64 {
+    if num_steps <= 1 {
+        return 0.0;
+    }
+    let max_step_ms = wave_duration_ms / (num_steps as f64);
+    let mut subdivision_ms = total_ms;
+    let mut division = 1;
+    while subdivision_ms > max_step_ms && subdivision_ms > 1.0 {
+        subdivision_ms /= 2.0;
+        division *= 2;
+    }
+    info!(
+        beat_ms = total_ms as u64,
+        division = division,
+        step_ms = subdivision_ms as u64,
+        boards = num_steps,
+        "Pattern wave timing"
+    );
+    subdivision_ms
+}
+
+/// Handle for the pattern source of the compositor
 pub struct PatternEngine {
-    command_tx: mpsc::Sender<PatternCommand>,
+    compositor_tx: mpsc::Sender<CompositorCommand>,
 }
 
 impl PatternEngine {
-    pub fn new() -> Self {
-        let (command_tx, command_rx) = mpsc::channel();
-        thread::spawn(move || Self::run_loop(command_rx));
-        Self { command_tx }
+    pub fn new(compositor_tx: mpsc::Sender<CompositorCommand>) -> Self {
+        Self { compositor_tx }
     }
 
     pub fn send_command(
         &self,
         cmd: PatternCommand,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.command_tx.send(cmd)?;
+        self.compositor_tx.send(CompositorCommand::Patterns(cmd))?;
         Ok(())
     }
+}
 
-    fn run_loop(command_rx: mpsc::Receiver<PatternCommand>) {
-        let mut active: Option<PatternState> = None;
-
-        loop {
-            match command_rx.try_recv() {
-                Ok(PatternCommand::Start { sequence, color, boards, is_random, is_ping_pong }) => {
-                    let mut transports = HashMap::new();
-                    for (board_id, info) in boards {
-                        if let Ok(transport) = E131RawTransport::new(vec![info.ip.clone()], info.universe) {
-                            transports.insert(board_id, (transport, info.led_count));
-                        }
-                    }
-                    if !transports.is_empty() {
-                        active = Some(PatternState {
-                            sequence,
-                            color,
-                            transports,
-                            cycle_count: 0,
-                            is_random,
-                            is_ping_pong,
-                            prev_chosen: None,
-                        });
-                    }
-                }
-                Ok(PatternCommand::Stop) => {
-                    if let Some(ref mut state) = active {
-                        for (transport, led_count) in state.transports.values_mut() {
-                            let _ = transport.send_raw_leds(*led_count, 0, 0, 0);
-                        }
-                    }
-                    active = None;
-                }
-                Err(mpsc::TryRecvError::Disconnected) => break,
-                Err(mpsc::TryRecvError::Empty) => {}
-            }
-            if let Some(ref mut state) = active {
-                let stopped = if state.is_random {
-                    Self::run_random_beat(&state.sequence, state.color, &mut state.transports, &command_rx, &mut state.prev_chosen)
-                } else {
-                    Self::run_one_cycle(&state.sequence, state.color, &mut state.transports, &command_rx, state.cycle_count, state.is_ping_pong)
-                };
-                if stopped {
-                    for (transport, led_count) in state.transports.values_mut() {
-                        let _ = transport.send_raw_leds(*led_count, 0, 0, 0);
-                    }
-                    active = None;
-                } else {
-                    state.cycle_count += 1;
-                }
-            } else {
-                thread::sleep(Duration::from_millis(10));
-            }
+/// The running pattern, drawn by the compositor one frame at a time
+pub struct PatternSource {
+    sequence: PatternSequence,
+    look: PatternLook,
+    envelope: PatternEnvelope,
+    step_effect: Option<StepEffect>,
+    boards: HashMap<String, OutputKey>,
+    is_random: bool,
+    is_ping_pong: bool,
+    clock: BeatClock,
+    step_interval_ms: f64,
+    /// Random pattern: current beat and the board it lit
+    beat: Option<u64>,
+    chosen: Option<String>,
+    playing: Vec<PlayingStep>,
+}
+
+impl PatternSource {
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        sequence: PatternSequence,
+        palette: Vec<[u8; 3]>,
+        colour_mode: ColourMode,
+        envelope: PatternEnvelope,
+        step_effect: Option<PatternStepEffect>,
+        boards: HashMap<String, OutputKey>,
+        is_random: bool,
+        is_ping_pong: bool,
+        phase: f64,
+        now: Instant,
+    ) -> Self {
+        let total_ms = sequence.total_duration_ms as f64;
+        let wave_duration_ms = total_ms * envelope.wave_portion.clamp(0.01, 1.0);
+        let step_interval_ms = step_interval_ms(total_ms, wave_duration_ms, sequence.steps.len());
+
+        Self {
+            look: PatternLook::new(palette, colour_mode),
+            step_effect: step_effect.as_ref().and_then(StepEffect::from_preset),
+            sequence,
+            envelope,
+            boards,
+            is_random,
+            is_ping_pong,
+            clock: BeatClock::starting_at(now, phase),
+            step_interval_ms,
+            beat: None,
+            chosen: None,
+            playing: Vec::new(),
         }
     }
 
-    fn run_random_beat(
-        seq: &PatternSequence,
-        color: [u8; 3],
-        transports: &mut HashMap<String, (E131RawTransport, usize)>,
-        command_rx: &mpsc::Receiver<PatternCommand>,
-        prev_chosen: &mut Option<String>,
-    ) -> bool {
-        const FLASH_DURATION_MS: u64 = 120;
-        const FRAME_MS: u64 = 20;
+    pub fn outputs(&self) -> Vec<OutputKey> {
+        let mut keys: Vec<OutputKey> = self.boards.values().copied().collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
 
-        let board_ids: Vec<String> = transports.keys().cloned().collect();
-        if board_ids.is_empty() {
-            return false;
-        }
+    /// Give up outputs taken over by another source
+    pub fn release(&mut self, keys: &[OutputKey]) {
+        self.boards.retain(|_, key| !keys.contains(key));
+    }
 
-        let beat_duration_ms = seq.total_duration_ms;
-        let beat_start = Instant::now();
+    pub fn is_empty(&self) -> bool {
+        self.boards.is_empty()
+    }
 
-        let available: Vec<&String> = board_ids.iter()
-            .filter(|id| Some((*id).clone()) != *prev_chosen)
-            .collect();
+    pub fn brightness(&self) -> f64 {
+        self.look.brightness
+    }
 
-        let chosen = if available.is_empty() {
-            board_ids[0].clone()
-        } else {
-            let idx = rand::rng().random_range(0..available.len());
-            available[idx].clone()
-        };
+    pub fn set_frozen(&mut self, now: Instant, frozen: bool) {
+        self.clock.set_frozen(now, frozen);
+    }
 
-        for (transport, led_count) in transports.values_mut() {
-            let _ = transport.send_raw_leds(*led_count, 0, 0, 0);
-        }
+    pub fn set_bpm(&mut self, bpm: f64) {
+        set_bpm(&mut self.clock, self.sequence.bpm, bpm);
+    }
 
-        if let Some((transport, led_count)) = transports.get_mut(&chosen) {
-            let _ = transport.send_raw_leds(*led_count, color[0], color[1], color[2]);
-        }
+    pub fn tap(&mut self, tap_tempo: &mut TapTempo, at: Instant) {
+        tap(&mut self.clock, tap_tempo, self.sequence.bpm, at);
+    }
 
-        let fade_frames = FLASH_DURATION_MS / FRAME_MS;
-        for frame in 1..=fade_frames {
-            thread::sleep(Duration::from_millis(FRAME_MS));
+    pub fn set_params(&mut self, params: EffectParams) {
+        apply_params(&mut self.clock, &mut self.look, params);
+    }
 
-            if let Ok(PatternCommand::Stop) = command_rx.try_recv() {
-                return true;
-            }
+    pub fn render(&mut self, now: Instant, outputs: &mut Outputs) {
+        let total_ms = (self.sequence.total_duration_ms as f64).max(1.0);
+        let now_ms = self.clock.position(now) * 1000.0;
+        let cycle = (now_ms / total_ms).floor().max(0.0) as u64;
+        let offset_ms = now_ms - cycle as f64 * total_ms;
 
-            let brightness = 1.0 - (frame as f64 / fade_frames as f64);
-            let r = (color[0] as f64 * brightness) as u8;
-            let g = (color[1] as f64 * brightness) as u8;
-            let b = (color[2] as f64 * brightness) as u8;
+        if self.is_random {
+            self.render_random(cycle, offset_ms, outputs);
+        } else if let Some(step_effect) = self.step_effect {
+            self.render_effect_steps(step_effect, cycle, offset_ms, total_ms, outputs);
+        } else {
+            self.render_wave(cycle, offset_ms, outputs);
+        }
+    }
 
-            if let Some((transport, led_count)) = transports.get_mut(&chosen) {
-                let _ = transport.send_raw_leds(*led_count, r, g, b);
-            }
+    /// Step indices in the order this cycle plays them
+    fn play_order(&self, cycle: u64) -> Vec<usize> {
+        let n = self.sequence.steps.len();
+        if self.is_ping_pong && cycle % 2 == 1 {
+            (0..n).rev().collect()
+        } else {
+            (0..n).collect()
         }
+    }
 
-        if let Some((transport, led_count)) = transports.get_mut(&chosen) {
-            let _ = transport.send_raw_leds(*led_count, 0, 0, 0);
+    fn fill(&self, outputs: &mut Outputs, board_id: &str, (r, g, b): (u8, u8, u8)) {
+        if let Some(frame) = self.boards.get(board_id).and_then(|key| outputs.frame(key)) {
+            frame.fill(r, g, b);
         }
+    }
 
-        *prev_chosen = Some(chosen);
+    fn render_wave(&mut self, cycle: u64, offset_ms: f64, outputs: &mut Outputs) {
+        let order = self.play_order(cycle);
+        let levels = wave_levels(&self.envelope, self.look.intensity, order.len(), self.step_interval_ms, offset_ms);
 
-        let end_time = beat_start + Duration::from_millis(beat_duration_ms);
-        while Instant::now() < end_time {
-            if let Ok(PatternCommand::Stop) = command_rx.try_recv() {
-                return true;
+        for board_id in self.boards.keys() {
+            self.fill(outputs, board_id, (0, 0, 0));
+        }
+        // Oldest first so the head wins where steps share a board
+        for (position, level) in levels.iter().enumerate() {
+            if *level <= 0.0 {
+                continue;
+            }
+            let rgb = PatternLook::rgb(self.look.colour(position, cycle), *level);
+            for board_id in &self.sequence.steps[order[position]].board_ids {
+                self.fill(outputs, board_id, rgb);
             }
-            thread::sleep(Duration::from_millis(5));
         }
-
-        false
     }
 
-    fn run_one_cycle(
-        seq: &PatternSequence,
-        color: [u8; 3],
-        transports: &mut HashMap<String, (E131RawTransport, usize)>,
-        command_rx: &mpsc::Receiver<PatternCommand>,
-        cycle_count: u64,
-        is_ping_pong: bool,
-    ) -> bool {
-        const WAVE_PORTION: f64 = 0.5;
-        const TRAIL_BRIGHTNESS: [f64; 3] = [1.0, 0.4, 0.1];
-
-        for (transport, led_count) in transports.values_mut() {
-            let _ = transport.send_raw_leds(*led_count, 0, 0, 0);
+    fn render_random(&mut self, beat: u64, offset_ms: f64, outputs: &mut Outputs) {
+        if self.beat != Some(beat) {
+            self.beat = Some(beat);
+
+            let mut board_ids: Vec<&String> = self.boards.keys().collect();
+            board_ids.sort();
+            let available: Vec<&String> = board_ids.iter()
+                .copied()
+                .filter(|id| Some(*id) != self.chosen.as_ref())
+                .collect();
+            let chosen = if available.is_empty() {
+                board_ids.first().map(|id| (*id).clone())
+            } else {
+                let idx = rand::rng().random_range(0..available.len());
+                Some(available[idx].clone())
+            };
+
+            self.playing.clear();
+            if let (Some(step_effect), Some(chosen)) = (self.step_effect, &chosen) {
+                let colour = self.look.colour(beat as usize, beat);
+                self.playing.push(PlayingStep {
+                    cycle: beat,
+                    position: 0,
+                    effects: vec![(chosen.clone(), step_effect.create(colour, self.sequence.bpm))],
+                });
+            }
+            self.chosen = chosen;
         }
 
-        let cycle_start = Instant::now();
-        let total_ms = seq.total_duration_ms as f64;
-        let wave_duration_ms = total_ms * WAVE_PORTION;
-        let num_steps = seq.steps.len();
-
-        let step_interval_ms = if num_steps > 1 {
-            let max_step_ms = wave_duration_ms / (num_steps as f64);
-            let mut subdivision_ms = total_ms;
-            let mut division = 1;
-            while subdivision_ms > max_step_ms && subdivision_ms > 1.0 {
-                subdivision_ms /= 2.0;
-                division *= 2;
+        for board_id in self.boards.keys() {
+            if Some(board_id) != self.chosen.as_ref() {
+                self.fill(outputs, board_id, (0, 0, 0));
             }
-            info!(
-                beat_ms = total_ms as u64,
-                division = division,
-                step_ms = subdivision_ms as u64,
-                boards = num_steps,
-                "Pattern wave timing"
-            );
-            subdivision_ms
-        } else {
-            0.0
-        };
+        }
 
-        let mut trail: Vec<Vec<String>> = Vec::new();
+        let Some(chosen) = self.chosen.clone() else {
+            return;
+        };
+        if self.step_effect.is_some() {
+            self.tick_playing(offset_ms, outputs);
+        } else {
+            let level = flash_level(&self.envelope, self.look.intensity, offset_ms);
+            let rgb = PatternLook::rgb(self.look.colour(beat as usize, beat), level);
+            self.fill(outputs, &chosen, rgb);
+        }
+    }
 
-        let reverse_direction = is_ping_pong && cycle_count % 2 == 1;
-        let steps: Vec<_> = if reverse_direction {
-            seq.steps.iter().rev().collect()
+    /// Wave where each step's boards play their own effect for one step
+    fn render_effect_steps(&mut self, step_effect: StepEffect, cycle: u64, offset_ms: f64, total_ms: f64, outputs: &mut Outputs) {
+        let order = self.play_order(cycle);
+        let interval_ms = self.step_interval_ms;
+        let step_len_ms = if interval_ms > 0.0 {
+            interval_ms
         } else {
-            seq.steps.iter().collect()
+            total_ms * self.envelope.wave_portion.clamp(0.01, 1.0)
         };
 
-        for (step_idx, step) in steps.iter().enumerate() {
-            let step_delay_ms = (step_idx as f64 * step_interval_ms) as u64;
-            let target_time = cycle_start + Duration::from_millis(step_delay_ms);
+        let active: Vec<usize> = (0..order.len())
+            .filter(|&position| {
+                let start_ms = position as f64 * interval_ms;
+                offset_ms >= start_ms && offset_ms < start_ms + step_len_ms
+            })
+            .collect();
 
-            loop {
-                let now = Instant::now();
-                if now >= target_time {
-                    break;
-                }
-                let remaining = target_time - now;
-
-                if remaining > Duration::from_millis(10) {
-                    if let Ok(PatternCommand::Stop) = command_rx.try_recv() {
-                        return true;
-                    }
-                    thread::sleep(Duration::from_millis(5));
-                } else if remaining > Duration::from_millis(1) {
-                    thread::sleep(Duration::from_micros(500));
-                } else {
-                    std::hint::spin_loop();
-                }
+        self.playing.retain(|p| p.cycle == cycle && active.contains(&p.position));
+        for &position in &active {
+            if self.playing.iter().any(|p| p.position == position) {
+                continue;
             }
+            let colour = self.look.colour(position, cycle);
+            let effects = self.sequence.steps[order[position]]
+                .board_ids
+                .iter()
+                .filter(|id| self.boards.contains_key(*id))
+                .map(|id| (id.clone(), step_effect.create(colour, self.sequence.bpm)))
+                .collect();
+            self.playing.push(PlayingStep { cycle, position, effects });
+        }
 
-            trail.insert(0, step.board_ids.clone());
-            if trail.len() > TRAIL_BRIGHTNESS.len() {
-                trail.pop();
+        let lit: HashSet<&String> = self.playing.iter()
+            .flat_map(|p| p.effects.iter().map(|(id, _)| id))
+            .collect();
+        for board_id in self.boards.keys() {
+            if !lit.contains(board_id) {
+                self.fill(outputs, board_id, (0, 0, 0));
             }
+        }
 
-            for (trail_idx, boards) in trail.iter().enumerate() {
-                let brightness = TRAIL_BRIGHTNESS[trail_idx];
-                let r = (color[0] as f64 * brightness) as u8;
-                let g = (color[1] as f64 * brightness) as u8;
-                let b = (color[2] as f64 * brightness) as u8;
+        self.tick_playing(offset_ms, outputs);
+    }
 
-                for board_id in boards {
-                    if let Some((transport, led_count)) = transports.get_mut(board_id) {
-                        let _ = transport.send_raw_leds(*led_count, r, g, b);
-                    }
+    fn tick_playing(&mut self, offset_ms: f64, outputs: &mut Outputs) {
+        for step in self.playing.iter_mut() {
+            let start_ms = if self.is_random { 0.0 } else { step.position as f64 * self.step_interval_ms };
+            let elapsed = (offset_ms - start_ms).max(0.0) / 1000.0;
+            for (board_id, effect) in step.effects.iter_mut() {
+                if let Some(frame) = self.boards.get(board_id).and_then(|key| outputs.frame(key)) {
+                    effect.tick(elapsed, frame);
                 }
             }
+        }
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DecayCurve;
+
+    fn envelope(attack_ms: u64, hold_ms: u64, release_ms: u64, decay: DecayCurve) -> PatternEnvelope {
+        PatternEnvelope {
+            attack_ms,
+            hold_ms,
+            release_ms,
+            decay,
+            ..PatternEnvelope::default()
         }
+    }
 
-        for fade_step in 0..3 {
-            thread::sleep(Duration::from_millis(30));
-            let fade_mult = 0.6_f64.powi(fade_step + 1);
+    #[test]
+    fn test_default_trail_dims_behind_head() {
+        let levels = trail_levels(&PatternEnvelope::default(), 0.5);
+        assert_eq!(levels.len(), 3);
+        // The fixed trail of the original wave pattern
+        assert_eq!(levels, vec![1.0, 0.4, 0.1]);
+    }
 
-            for (trail_idx, boards) in trail.iter().enumerate() {
-                let brightness = TRAIL_BRIGHTNESS.get(trail_idx).unwrap_or(&0.0) * fade_mult;
-                let r = (color[0] as f64 * brightness) as u8;
-                let g = (color[1] as f64 * brightness) as u8;
-                let b = (color[2] as f64 * brightness) as u8;
+    #[test]
+    fn test_colour_change_keeps_rest_of_palette() {
+        let mut clock = BeatClock::new(Instant::now());
+        let mut look = PatternLook::new(vec![[255, 0, 0], [0, 255, 0], [0, 0, 255]], ColourMode::PerStep);
+        let params = EffectParams {
+            color: Some([255, 255, 255]),
+            ..EffectParams::default()
+        };
+        apply_params(&mut clock, &mut look, params);
 
-                for board_id in boards {
-                    if let Some((transport, led_count)) = transports.get_mut(board_id) {
-                        let _ = transport.send_raw_leds(*led_count, r, g, b);
-                    }
-                }
-            }
-        }
+        let colours: Vec<[u8; 3]> = (0..4).map(|step| look.colour(step, 0)).collect();
+        assert_eq!(colours, vec![[255, 255, 255], [0, 255, 0], [0, 0, 255], [255, 255, 255]]);
+        assert_eq!(look.palette[0], [255, 0, 0]);
+    }
 
-        for (transport, led_count) in transports.values_mut() {
-            let _ = transport.send_raw_leds(*led_count, 0, 0, 0);
+    #[test]
+    fn test_linear_trail_length() {
+        let env = PatternEnvelope {
+            trail_length: 4,
+            decay: DecayCurve::Linear,
+            ..PatternEnvelope::default()
+        };
+        assert_eq!(trail_levels(&env, 0.5), vec![1.0, 0.75, 0.5, 0.25]);
+    }
+
+    #[test]
+    fn test_flash_envelope_attack_hold_release() {
+        let env = envelope(40, 60, 50, DecayCurve::Linear);
+
+        assert_eq!(flash_level(&env, 0.5, 0.0), 0.0);
+        assert_eq!(flash_level(&env, 0.5, 20.0), 0.5);
+        assert_eq!(flash_level(&env, 0.5, 40.0), 1.0);
+        assert_eq!(flash_level(&env, 0.5, 99.0), 1.0);
+        // Release starts after the 60ms hold
+        assert_eq!(flash_level(&env, 0.5, 120.0), 1.0 - 20.0 / 50.0);
+        assert_eq!(flash_level(&env, 0.5, 150.0), 0.0);
+    }
+
+    #[test]
+    fn test_flash_default_matches_fixed_flash() {
+        let env = PatternEnvelope::default();
+        // The original flash faded linearly over six 20ms frames
+        for frame in 0..=6 {
+            let expected = 1.0 - frame as f64 / 6.0;
+            assert!((flash_level(&env, 0.5, frame as f64 * 20.0) - expected).abs() < 1e-9);
         }
+        assert_eq!(flash_level(&env, 0.5, 130.0), 0.0);
+    }
 
-        let end_time = cycle_start + Duration::from_millis(seq.total_duration_ms);
-        while Instant::now() < end_time {
-            if let Ok(PatternCommand::Stop) = command_rx.try_recv() {
-                return true;
-            }
-            thread::sleep(Duration::from_millis(1));
+    #[test]
+    fn test_wave_default_tail_matches_fixed_fade() {
+        let env = PatternEnvelope::default();
+        // Last of three steps fires at 100ms; the original tail dropped the
+        // trail by 0.6 every 30ms and went black after the third drop
+        let at = |t: f64| wave_levels(&env, 0.5, 3, 50.0, t);
+        assert_eq!(at(110.0), vec![0.1, 0.4, 1.0]);
+        for (t, mult) in [(130.0, 0.6), (160.0, 0.36), (189.0, 0.36)] {
+            let levels = at(t);
+            assert!((levels[2] - mult).abs() < 1e-9);
+            assert!((levels[1] - 0.4 * mult).abs() < 1e-9);
         }
-        false
+        assert!(at(190.0).iter().all(|l| *l == 0.0));
+    }
+
+    #[test]
+    fn test_flash_release_scales_with_intensity() {
+        let env = PatternEnvelope::default();
+        assert!(flash_level(&env, 1.0, 200.0) > 0.0);
+        assert_eq!(flash_level(&env, 1.0, 240.0), 0.0);
+    }
+
+    #[test]
+    fn test_wave_head_attack_and_trail() {
+        let env = envelope(100, 0, 0, DecayCurve::Linear);
+        // Four steps 50ms apart; 20ms after the second step fires
+        let levels = wave_levels(&env, 0.5, 4, 50.0, 70.0);
+        assert!((levels[1] - (20.0 / 100.0)).abs() < 1e-9);
+        assert!((levels[0] - (1.0 - 1.0 / 3.0)).abs() < 1e-9);
+        assert_eq!(&levels[2..], &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_wave_release_ends_black() {
+        let env = envelope(0, 20, 100, DecayCurve::Exponential);
+        // Last of three steps fires at 100ms, holds until 120ms, releases by 220ms
+        let held = wave_levels(&env, 0.5, 3, 50.0, 110.0);
+        assert_eq!(held[2], 1.0);
+        let releasing: Vec<f64> = [130.0, 160.0, 190.0]
+            .iter()
+            .map(|t| wave_levels(&env, 0.5, 3, 50.0, *t)[2])
+            .collect();
+        assert!(releasing.windows(2).all(|w| w[0] > w[1]));
+        assert!(wave_levels(&env, 0.5, 3, 50.0, 220.0).iter().all(|l| *l == 0.0));
     }
 }
@@ -3,6 +3,8 @@ use std::fs;
 use std::path::Path;
 use tracing::{info, warn};
 
+use crate::tempo_map::{BeatPosition, TempoChange, TempoMap};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
     pub id: String,
@@ -15,6 +17,8 @@ pub struct Program {
     pub cues: Vec<Cue>,
     pub created_at: String,
     #[serde(default)]
+    pub version: u64,  // Bumped on every save; sent as the ETag
+    #[serde(default)]
     pub display_order: i32,  // Order for performance page display
     #[serde(skip_serializing_if = "Option::is_none")]
     pub default_target_board: Option<String>,  // Default board/group for new cues
@@ -31,6 +35,27 @@ pub struct Program {
     pub bpm: Option<u16>,  // BPM for speed-synced effects
     #[serde(skip_serializing_if = "Option::is_none")]
     pub grid_offset: Option<f64>,  // Downbeat position for beat grid alignment
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tempo_map: Vec<TempoChange>,  // Tempo/time-signature changes by bar
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub loop_regions: Vec<LoopRegion>,  // Sections that can vamp until released
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_bpm: Option<f64>,  // Detected from uploaded audio
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggested_grid_offset: Option<f64>,  // Detected first downbeat in seconds
+    #[serde(default)]
+    pub mode: ProgramMode,  // Timed to the audio, or a cue stack run by GO
+}
+
+/// How a program's cues are triggered
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ProgramMode {
+    /// Cues fire at their time in the song
+    #[default]
+    Timed,
+    /// Cues fire in list order when the operator hits GO; their times are ignored
+    CueStack,
 }
 
 fn default_transition_type() -> String {
@@ -39,19 +64,150 @@ fn default_transition_type() -> String {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cue {
+    #[serde(default)]
     pub time: f64,
+    /// Bar/beat position on the program's tempo map; takes over from `time` when set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<BeatPosition>,
     pub label: String,
     pub targets: Vec<String>,
     pub preset_name: String,
     #[serde(default = "default_sync_rate")]
     pub sync_rate: f64,
+    /// How long the look lasts; until another cue replaces it when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<CueDuration>,
+    /// What the cue's boards do when `duration` is up
+    #[serde(default)]
+    pub release: CueRelease,
+    /// Cue stacks: seconds between the GO and the cue firing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait: Option<f64>,
+    /// Cue stacks: seconds after this cue fires before the next one goes by itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub follow: Option<f64>,
 }
 
 fn default_sync_rate() -> f64 {
     1.0
 }
 
+/// A section of the song whose cues repeat while its loop is armed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LoopRegion {
+    pub name: String,
+    /// Song time in seconds where each pass starts
+    pub start: f64,
+    /// Song time in seconds where the loop goes back to `start`
+    pub end: f64,
+}
+
+/// Length of a cue, e.g. `{"beats": 2}` or `{"seconds": 1.5}`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CueDuration {
+    Seconds(f64),
+    /// Beats at the program's BPM
+    Beats(f64),
+}
+
+impl CueDuration {
+    pub fn as_secs(&self, bpm: f64) -> f64 {
+        match self {
+            CueDuration::Seconds(secs) => *secs,
+            CueDuration::Beats(beats) => beats * 60.0 / bpm,
+        }
+    }
+}
+
+/// Release action of a cue with a duration, e.g. `{"action": "fade", "fade_ms": 800}`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Default)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum CueRelease {
+    #[default]
+    Blackout,
+    /// Bring back the looks the boards showed before the cue fired
+    Restore,
+    /// Fade the cue's look to black
+    Fade {
+        #[serde(default = "default_fade_ms")]
+        fade_ms: u64,
+    },
+}
+
+fn default_fade_ms() -> u64 {
+    500
+}
+
 impl Program {
+    /// Tempo map from `tempo_map`, `bpm` and `grid_offset`
+    pub fn build_tempo_map(&self) -> Result<TempoMap, String> {
+        TempoMap::new(
+            &self.tempo_map,
+            self.bpm.unwrap_or(120) as f64,
+            self.grid_offset.unwrap_or(0.0),
+        )
+    }
+
+    /// Seconds into the song at which `cue` fires
+    pub fn cue_time(cue: &Cue, tempo_map: &TempoMap) -> Result<f64, String> {
+        match cue.position {
+            Some(ref position) => tempo_map
+                .seconds_at(position)
+                .map_err(|e| format!("Cue '{}': {}", cue.label, e)),
+            None => Ok(cue.time),
+        }
+    }
+
+    /// Cues a cue-stack GO on cue `index` plays, with seconds from the GO to each
+    ///
+    /// The chain runs on through every `follow`. Without `with_wait` the first
+    /// cue fires straight away; without `with_follows` it plays on its own.
+    pub fn cue_stack_chain(&self, index: usize, with_wait: bool, with_follows: bool) -> Vec<(usize, f64)> {
+        let mut chain = Vec::new();
+        let mut at = 0.0;
+        for (idx, cue) in self.cues.iter().enumerate().skip(index) {
+            if idx > index || with_wait {
+                at += cue.wait.unwrap_or(0.0);
+            }
+            chain.push((idx, at));
+            match cue.follow {
+                Some(follow) if with_follows => at += follow,
+                _ => break,
+            }
+        }
+        chain
+    }
+
+    /// Check the tempo map, every bar/beat cue position and the loop regions
+    pub fn validate_timing(&self) -> Result<(), String> {
+        let tempo_map = self.build_tempo_map()?;
+        for cue in &self.cues {
+            Self::cue_time(cue, &tempo_map)?;
+            for (name, secs) in [("wait", cue.wait), ("follow", cue.follow)] {
+                if secs.is_some_and(|s| !(s.is_finite() && s >= 0.0)) {
+                    return Err(format!("Cue '{}': {} must be zero or more seconds", cue.label, name));
+                }
+            }
+        }
+        for (idx, region) in self.loop_regions.iter().enumerate() {
+            if region.name.is_empty() {
+                return Err(format!("Loop region {} needs a name", idx + 1));
+            }
+            if !(region.start.is_finite() && region.end.is_finite() && 0.0 <= region.start && region.start < region.end) {
+                return Err(format!("Loop region '{}' must end after it starts", region.name));
+            }
+            if self.loop_regions[..idx].iter().any(|r| r.name == region.name) {
+                return Err(format!("Two loop regions named '{}'", region.name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn loop_region(&self, name: &str) -> Option<&LoopRegion> {
+        self.loop_regions.iter().find(|r| r.name == name)
+    }
+
     pub fn save_to_file(&self, programs_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
         fs::create_dir_all(programs_path)?;
         let file_path = programs_path.join(format!("{}.json", self.id));
@@ -114,3 +270,47 @@ impl Program {
         Ok(())
     }
 }
+
+#[cfg(test)]
+impl Program {
+    /// A program with `cues` (as JSON) and every other field at its default
+    pub fn fixture(id: &str, cues: serde_json::Value) -> Self {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "song_name": id,
+            "loopy_pro_track": "1",
+            "file_name": "",
+            "audio_file": null,
+            "cues": cues,
+            "created_at": ""
+        }))
+        .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cue_stack_chain_runs_through_follows() {
+        let program = Program {
+            mode: ProgramMode::CueStack,
+            ..Program::fixture(
+                "play",
+                serde_json::json!([
+                    { "label": "house out", "targets": ["stage"], "preset_name": "off" },
+                    { "label": "storm", "targets": ["stage"], "preset_name": "lightning", "wait": 1.0, "follow": 2.5 },
+                    { "label": "calm", "targets": ["stage"], "preset_name": "blue", "wait": 0.5 },
+                    { "label": "end", "targets": ["stage"], "preset_name": "off" }
+                ]),
+            )
+        };
+
+        assert_eq!(program.cue_stack_chain(0, true, true), vec![(0, 0.0)]);
+        assert_eq!(program.cue_stack_chain(1, true, true), vec![(1, 1.0), (2, 4.0)]);
+        assert_eq!(program.cue_stack_chain(1, false, true), vec![(1, 0.0), (2, 3.0)]);
+        assert_eq!(program.cue_stack_chain(1, false, false), vec![(1, 0.0)]);
+        assert!(program.cue_stack_chain(4, true, true).is_empty());
+    }
+}
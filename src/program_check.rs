@@ -0,0 +1,319 @@
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use crate::audio::AudioFile;
+use crate::config::Config;
+use crate::effects::EffectType;
+use crate::fseq::SequenceLibrary;
+use crate::program::{Program, ProgramMode};
+use crate::tempo_map::TempoMap;
+
+/// One problem found in a program
+#[derive(Debug, Clone, Serialize)]
+pub struct Issue {
+    /// Stable identifier, e.g. "unknown_preset"
+    pub code: &'static str,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cue_index: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+}
+
+impl Issue {
+    fn new(code: &'static str, message: String) -> Self {
+        Self {
+            code,
+            message,
+            cue_index: None,
+            target: None,
+        }
+    }
+
+    fn at_cue(mut self, cue_index: usize) -> Self {
+        self.cue_index = Some(cue_index);
+        self
+    }
+
+    fn on_target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+}
+
+/// Result of checking one program; errors break playback, warnings may not
+#[derive(Debug, Clone, Serialize)]
+pub struct ProgramReport {
+    pub program_id: String,
+    pub ok: bool,
+    pub errors: Vec<Issue>,
+    pub warnings: Vec<Issue>,
+}
+
+/// What a program is checked against
+pub struct CheckContext<'a> {
+    pub config: &'a Config,
+    pub online_ips: &'a HashSet<String>,
+    pub programs: &'a HashMap<String, Program>,
+    pub audio_path: &'a Path,
+    pub sequences: &'a SequenceLibrary,
+}
+
+/// Everything the program engine would skip or trip over when playing `program`
+pub fn check_program(program: &Program, ctx: &CheckContext) -> ProgramReport {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    if let Err(e) = program.validate_timing() {
+        errors.push(Issue::new("invalid_timing", e));
+    }
+    let tempo_map = program
+        .build_tempo_map()
+        .unwrap_or_else(|_| TempoMap::constant(program.bpm.unwrap_or(120) as f64, program.grid_offset.unwrap_or(0.0)));
+
+    let mut checked_targets: HashSet<&str> = HashSet::new();
+    for (idx, cue) in program.cues.iter().enumerate() {
+        if cue.targets.is_empty() {
+            errors.push(Issue::new("empty_targets", format!("Cue '{}' has no targets", cue.label)).at_cue(idx));
+        }
+
+        let pattern_preset = ctx.config.pattern_presets.iter().find(|p| p.name == cue.preset_name);
+        let effect_preset = ctx.config.effect_presets.iter().find(|p| p.name == cue.preset_name);
+        let sequence_preset = ctx.config.sequence_presets.iter().find(|p| p.name == cue.preset_name);
+        if pattern_preset.is_none() && effect_preset.is_none() && sequence_preset.is_none() {
+            errors.push(
+                Issue::new(
+                    "unknown_preset",
+                    format!("Cue '{}' uses unknown preset '{}'", cue.label, cue.preset_name),
+                )
+                .at_cue(idx),
+            );
+        } else if let Some(preset) = effect_preset.filter(|_| pattern_preset.is_none()) {
+            if preset.effect_type.parse::<EffectType>().is_err() {
+                errors.push(
+                    Issue::new(
+                        "unknown_preset",
+                        format!("Preset '{}' has unknown effect type '{}'", preset.name, preset.effect_type),
+                    )
+                    .at_cue(idx),
+                );
+            }
+        } else if let Some(preset) = sequence_preset.filter(|_| pattern_preset.is_none() && effect_preset.is_none()) {
+            if let Err(e) = ctx.sequences.clip(preset) {
+                errors.push(Issue::new("invalid_sequence", format!("Cue '{}': {}", cue.label, e)).at_cue(idx));
+            }
+        }
+
+        for target in &cue.targets {
+            let boards = ctx.config.get_target_boards(target);
+            if boards.is_empty() {
+                errors.push(
+                    Issue::new(
+                        "unknown_target",
+                        format!("Cue '{}' targets unknown board or group '{}'", cue.label, target),
+                    )
+                    .at_cue(idx)
+                    .on_target(target),
+                );
+                continue;
+            }
+
+            if let Some(preset) = pattern_preset {
+                let members: Vec<String> = boards.iter().map(|b| b.id.clone()).collect();
+                if let Err(e) = preset.validate(&members) {
+                    errors.push(
+                        Issue::new("invalid_pattern", format!("Cue '{}': {}", cue.label, e))
+                            .at_cue(idx)
+                            .on_target(target),
+                    );
+                }
+            }
+
+            if !checked_targets.insert(target) {
+                continue;
+            }
+            let offline: Vec<&str> = boards
+                .iter()
+                .filter(|b| !ctx.online_ips.contains(&b.ip))
+                .map(|b| b.id.as_str())
+                .collect();
+            if offline.len() == boards.len() {
+                warnings.push(
+                    Issue::new("offline_boards", format!("Target '{}' has no online boards", target))
+                        .on_target(target),
+                );
+            } else if !offline.is_empty() {
+                warnings.push(
+                    Issue::new(
+                        "offline_boards",
+                        format!(
+                            "Target '{}': {}/{} boards offline ({})",
+                            target,
+                            offline.len(),
+                            boards.len(),
+                            offline.join(", ")
+                        ),
+                    )
+                    .on_target(target),
+                );
+            }
+        }
+    }
+
+    // Cue stacks fire in list order, whatever their times
+    if program.mode == ProgramMode::Timed {
+        warnings.extend(overlapping_cues(program, &tempo_map));
+    }
+
+    if let Some(audio_file) = &program.audio_file {
+        let found = AudioFile::resolve_path(audio_file, ctx.audio_path).is_ok_and(|path| path.exists());
+        if !found {
+            errors.push(Issue::new("missing_audio", format!("Audio file '{}' not found", audio_file)));
+        }
+    }
+
+    if let Some(issue) = check_chain(program, ctx.programs) {
+        if issue.code == "broken_next_program" {
+            errors.push(issue);
+        } else {
+            warnings.push(issue);
+        }
+    }
+
+    ProgramReport {
+        program_id: program.id.clone(),
+        ok: errors.is_empty(),
+        errors,
+        warnings,
+    }
+}
+
+/// Cues on the same target that fire together, or while an earlier cue's duration is still running
+fn overlapping_cues(program: &Program, tempo_map: &TempoMap) -> Vec<Issue> {
+    let mut by_target: HashMap<&str, Vec<(f64, usize)>> = HashMap::new();
+    for (idx, cue) in program.cues.iter().enumerate() {
+        let Ok(time) = Program::cue_time(cue, tempo_map) else {
+            continue;
+        };
+        for target in &cue.targets {
+            by_target.entry(target).or_default().push((time, idx));
+        }
+    }
+
+    let mut issues = Vec::new();
+    let mut targets: Vec<_> = by_target.into_iter().collect();
+    targets.sort_by(|a, b| a.0.cmp(b.0));
+    for (target, mut cues) in targets {
+        cues.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        for pair in cues.windows(2) {
+            let ((time, idx), (next_time, next_idx)) = (pair[0], pair[1]);
+            let cue = &program.cues[idx];
+            let next = &program.cues[next_idx];
+            let ends = cue.duration.map(|d| time + d.as_secs(tempo_map.bpm_at(time)));
+
+            let message = if next_time == time {
+                format!("Cues '{}' and '{}' both fire at {:.2}s on '{}'", cue.label, next.label, time, target)
+            } else if ends.is_some_and(|end| end > next_time) {
+                format!(
+                    "Cue '{}' on '{}' is cut short by '{}' at {:.2}s",
+                    cue.label, target, next.label, next_time
+                )
+            } else {
+                continue;
+            };
+            issues.push(Issue::new("overlapping_cues", message).at_cue(next_idx).on_target(target));
+        }
+    }
+    issues
+}
+
+/// A `next_program_id` that points nowhere, or a chain that comes back round
+fn check_chain(program: &Program, programs: &HashMap<String, Program>) -> Option<Issue> {
+    let mut seen = vec![program.id.as_str()];
+    let mut current = program;
+    while let Some(next_id) = current.next_program_id.as_deref().filter(|id| !id.is_empty()) {
+        let Some(next) = programs.get(next_id) else {
+            return Some(Issue::new(
+                "broken_next_program",
+                format!("Program '{}' chains into unknown program '{}'", current.id, next_id),
+            ));
+        };
+        if seen.contains(&next_id) {
+            return Some(Issue::new(
+                "chain_cycle",
+                format!("Auto-play chain loops back to '{}' and will stop there", next_id),
+            ));
+        }
+        seen.push(next_id);
+        current = next;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        serde_json::from_value(serde_json::json!({
+            "boards": [
+                { "id": "left", "ip": "10.0.0.1" },
+                { "id": "right", "ip": "10.0.0.2" }
+            ],
+            "groups": [{ "id": "stage", "members": ["left", "right"] }],
+            "effect_presets": [{ "name": "red", "effect_type": "solid", "color": [255, 0, 0] }]
+        }))
+        .unwrap()
+    }
+
+    fn codes(issues: &[Issue]) -> Vec<&'static str> {
+        issues.iter().map(|i| i.code).collect()
+    }
+
+    #[test]
+    fn test_reports_cue_problems() {
+        let config = config();
+        let online: HashSet<String> = ["10.0.0.1".to_string()].into();
+        let song = Program::fixture(
+            "song",
+            serde_json::json!([
+                { "time": 0.0, "label": "a", "targets": ["stage"], "preset_name": "red" },
+                { "time": 0.0, "label": "b", "targets": ["stage"], "preset_name": "blue" },
+                { "time": 4.0, "label": "c", "targets": ["wings"], "preset_name": "red" },
+                { "time": 8.0, "label": "d", "targets": [], "preset_name": "red" }
+            ]),
+        );
+        let programs = HashMap::from([(song.id.clone(), song.clone())]);
+        let ctx = CheckContext {
+            config: &config,
+            online_ips: &online,
+            programs: &programs,
+            audio_path: Path::new("audio"),
+            sequences: &SequenceLibrary::new("audio".into()),
+        };
+
+        let report = check_program(&song, &ctx);
+        assert!(!report.ok);
+        assert_eq!(codes(&report.errors), vec!["unknown_preset", "unknown_target", "empty_targets"]);
+        assert_eq!(codes(&report.warnings), vec!["offline_boards", "overlapping_cues"]);
+    }
+
+    #[test]
+    fn test_chain_links() {
+        let linked = |id: &str, next: &str| Program {
+            next_program_id: Some(next.to_string()),
+            ..Program::fixture(id, serde_json::json!([]))
+        };
+        let first = linked("first", "second");
+        let second = linked("second", "first");
+        let dangling = linked("dangling", "missing");
+        let programs = HashMap::from([
+            (first.id.clone(), first.clone()),
+            (second.id.clone(), second),
+        ]);
+
+        assert_eq!(check_chain(&first, &programs).map(|i| i.code), Some("chain_cycle"));
+        assert_eq!(check_chain(&dangling, &programs).map(|i| i.code), Some("broken_next_program"));
+    }
+}
@@ -1,25 +1,42 @@
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, RwLock};
 
-use crate::config::{Config, PatternType};
-use crate::cue_scheduler::{CueScheduler, CueType, PatternCueConfig, ScheduledCue};
+use crate::config::{Config, PatternPreset, SequencePreset};
+use crate::cue_scheduler::{CueScheduler, CueType, LoopSpan, PatternCueConfig, ScheduledCue, ScheduledRelease};
 use crate::effects::EffectType;
 use crate::effects_engine::{BoardTarget, EffectConfig, EffectsEngine, EngineCommand};
+use crate::fseq::SequenceLibrary;
 use crate::pattern_engine::{BoardInfo, PatternCommand, PatternEngine};
 use crate::playback_history::PlaybackHistory;
-use crate::program::Program;
+use crate::program::{Cue, CueRelease, Program, ProgramMode};
+use crate::setlist::{Advance, Setlist};
+use crate::sse::SseEvent;
+use crate::tempo_map::TempoMap;
 use crate::timing_metrics::TimingMetrics;
 
-pub type AudioPlayCallback = Arc<dyn Fn(&str) + Send + Sync>;
+/// Transport command for the program's audio track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioCommand {
+    Play,
+    Pause,
+    Resume,
+    /// Jump to this many seconds into the track
+    Seek(f64),
+}
+
+pub type AudioCallback = Arc<dyn Fn(&str, AudioCommand) + Send + Sync>;
 
 #[derive(Debug, Clone)]
 struct TargetInfo {
     boards: Vec<BoardTarget>,
     board_info_by_id: HashMap<String, BoardInfo>,
     member_ids: Vec<String>,
+    /// Every board in the target, online or not
+    all_member_ids: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -28,16 +45,357 @@ struct PresetInfo {
     color: [u8; 3],
 }
 
-#[derive(Debug, Clone)]
-struct PatternPresetInfo {
-    pattern_type: PatternType,
-    color: [u8; 3],
+
+/// Every cue of `program` as the scheduler would play it from the top, with all boards online
+pub fn offline_schedule(program: &Program, cfg: &Config, sequences: &SequenceLibrary) -> Vec<ScheduledCue> {
+    build_schedule(program, 0.0, cfg, sequences, |_| true).1
+}
+
+/// Resolve the program's targets and turn its cues from `start_time` on into scheduled cues
+fn build_schedule(
+    program: &Program,
+    start_time: f64,
+    cfg: &Config,
+    sequences: &SequenceLibrary,
+    is_online: impl Fn(&str) -> bool,
+) -> (HashMap<String, TargetInfo>, Vec<ScheduledCue>) {
+    let tempo_map = program.build_tempo_map().unwrap_or_else(|e| {
+        let bpm = program.bpm.unwrap_or(120) as f64;
+        eprintln!("⚠️ Ignoring tempo map of {}: {} - using {} BPM", program.id, e, bpm);
+        TempoMap::constant(bpm, program.grid_offset.unwrap_or(0.0))
+    });
+
+    let unique_targets: HashSet<String> = program
+        .cues
+        .iter()
+        .flat_map(|c| c.targets.iter().cloned())
+        .collect();
+
+    let mut target_map: HashMap<String, TargetInfo> = HashMap::new();
+    for target in &unique_targets {
+        let target_boards = cfg.get_target_boards(target);
+        if !target_boards.is_empty() {
+            let mut boards: Vec<BoardTarget> = Vec::new();
+            let mut board_info_by_id: HashMap<String, BoardInfo> = HashMap::new();
+            let mut member_ids: Vec<String> = Vec::new();
+            let all_member_ids: Vec<String> =
+                target_boards.iter().map(|b| b.id.clone()).collect();
+
+            for b in &target_boards {
+                if is_online(&b.ip) {
+                    boards.push(BoardTarget {
+                        ip: b.ip.clone(),
+                        universe: b.universe.unwrap_or(1),
+                        led_count: b.led_count.unwrap_or(60) as usize,
+                    });
+                    board_info_by_id.insert(
+                        b.id.clone(),
+                        BoardInfo {
+                            ip: b.ip.clone(),
+                            universe: b.universe.unwrap_or(1),
+                            led_count: b.led_count.unwrap_or(60) as usize,
+                        },
+                    );
+                    member_ids.push(b.id.clone());
+                }
+            }
+
+            if boards.is_empty() {
+                println!(
+                    "⚠️ Target '{}' has no online boards (0/{} online)",
+                    target,
+                    target_boards.len()
+                );
+                continue;
+            }
+            println!(
+                "🎯 Target '{}': {}/{} boards online",
+                target,
+                boards.len(),
+                target_boards.len()
+            );
+            target_map.insert(
+                target.clone(),
+                TargetInfo { boards, board_info_by_id, member_ids, all_member_ids },
+            );
+        }
+    }
+
+    let mut preset_map: HashMap<String, PresetInfo> = HashMap::new();
+    for preset in &cfg.effect_presets {
+        let effect_type = match preset.effect_type.parse::<EffectType>() {
+            Ok(t) => t,
+            Err(_) => continue,
+        };
+        preset_map.insert(
+            preset.name.clone(),
+            PresetInfo {
+                effect_type,
+                color: preset.color,
+            },
+        );
+    }
+
+    let pattern_preset_map: HashMap<String, PatternPreset> = cfg
+        .pattern_presets
+        .iter()
+        .map(|preset| (preset.name.clone(), preset.clone()))
+        .collect();
+
+    let sequence_preset_map: HashMap<&String, &SequencePreset> =
+        cfg.sequence_presets.iter().map(|preset| (&preset.name, preset)).collect();
+
+    let mut timed: Vec<(f64, usize, &Cue)> = Vec::new();
+    for (idx, cue) in program.cues.iter().enumerate() {
+        match Program::cue_time(cue, &tempo_map) {
+            Ok(t) => timed.push((t, idx, cue)),
+            Err(e) => eprintln!("⚠️ Skipping cue: {}", e),
+        }
+    }
+    timed.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+
+    let release_end = |cue_time: f64, cue: &Cue| {
+        cue.duration
+            .map(|d| cue_time + d.as_secs(tempo_map.bpm_at(cue_time)))
+    };
+
+    // Looks that are already showing at the start position, oldest first
+    let mut entries: Vec<(f64, usize, &Cue, &String)> = Vec::new();
+    if start_time > 0.0 {
+        let targets: HashSet<&String> = timed.iter().flat_map(|(_, _, c)| c.targets.iter()).collect();
+        for target in targets {
+            let earlier = timed
+                .iter()
+                .rev()
+                .filter(|(t, _, c)| *t < start_time && c.targets.contains(target));
+            for &(cue_time, idx, cue) in earlier {
+                match release_end(cue_time, cue) {
+                    Some(end) if end <= start_time => {
+                        // A restore brings back the look before it; other releases leave the target dark
+                        if cue.release == CueRelease::Restore {
+                            continue;
+                        }
+                    }
+                    _ => entries.push((cue_time, idx, cue, target)),
+                }
+                break;
+            }
+        }
+        entries.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        println!("⏪ Reconstructed {} looks showing at {}s", entries.len(), start_time);
+    }
+
+    for &(cue_time, idx, cue) in timed.iter().filter(|(t, _, _)| *t >= start_time) {
+        if cue.targets.is_empty() {
+            eprintln!("⚠️ Skipping cue '{}': no targets", cue.label);
+            continue;
+        }
+        entries.extend(cue.targets.iter().map(|target| (cue_time, idx, cue, target)));
+    }
+
+    let mut scheduled_cues: Vec<ScheduledCue> = Vec::new();
+    for (cue_time, _, cue, target) in entries {
+        let bpm = tempo_map.bpm_at(cue_time);
+        let preset_name = &cue.preset_name;
+        let fire_at = Duration::from_secs_f64((cue_time - start_time).max(0.0));
+        let phase = (start_time - cue_time).max(0.0);
+        let release = cue.duration.and_then(|d| {
+            let length = Duration::try_from_secs_f64(d.as_secs(bpm)).ok().filter(|l| !l.is_zero())?;
+            let at = Duration::try_from_secs_f64(cue_time - start_time + length.as_secs_f64()).ok()?;
+            Some(ScheduledRelease {
+                at,
+                action: cue.release,
+            })
+        });
+
+        if let Some(pattern_preset) = pattern_preset_map.get(preset_name) {
+            let target_info = match target_map.get(target) {
+                Some(t) => t,
+                None => {
+                    eprintln!(
+                        "⚠️ Skipping pattern cue '{}': target '{}' not found or offline",
+                        cue.label, target
+                    );
+                    continue;
+                }
+            };
+
+            if let Err(e) = pattern_preset.validate(&target_info.all_member_ids) {
+                eprintln!("⚠️ Skipping pattern cue '{}': {}", cue.label, e);
+                continue;
+            }
+
+            scheduled_cues.push(ScheduledCue {
+                fire_at,
+                phase,
+                label: cue.label.clone(),
+                cue_type: CueType::Pattern(PatternCueConfig {
+                    pattern_type: pattern_preset.pattern.clone(),
+                    custom_steps: pattern_preset.steps.clone(),
+                    envelope: pattern_preset.envelope.clone(),
+                    step_effect: pattern_preset.step_effect.clone(),
+                    palette: pattern_preset.palette(),
+                    colour_mode: pattern_preset.colour_mode,
+                    member_ids: target_info.member_ids.clone(),
+                    board_info: target_info.board_info_by_id.clone(),
+                    bpm,
+                    sync_rate: cue.sync_rate,
+                }),
+                release,
+            });
+        } else if let Some(preset) = preset_map.get(preset_name) {
+            let target_info = match target_map.get(target) {
+                Some(t) => t,
+                None => {
+                    eprintln!(
+                        "⚠️ Skipping cue '{}': target '{}' not found",
+                        cue.label, target
+                    );
+                    continue;
+                }
+            };
+
+            let effective_bpm = bpm * cue.sync_rate;
+
+            scheduled_cues.push(ScheduledCue {
+                fire_at,
+                phase,
+                label: cue.label.clone(),
+                cue_type: CueType::Effect {
+                    config: EffectConfig {
+                        effect_type: preset.effect_type,
+                        bpm: effective_bpm,
+                        color: preset.color,
+                        sync_rate: cue.sync_rate,
+                    },
+                    boards: target_info.boards.clone(),
+                },
+                release,
+            });
+        } else if let Some(preset) = sequence_preset_map.get(preset_name) {
+            let target_info = match target_map.get(target) {
+                Some(t) => t,
+                None => {
+                    eprintln!(
+                        "⚠️ Skipping sequence cue '{}': target '{}' not found or offline",
+                        cue.label, target
+                    );
+                    continue;
+                }
+            };
+
+            let clip = match sequences.clip(preset) {
+                Ok(clip) => clip,
+                Err(e) => {
+                    eprintln!("⚠️ Skipping sequence cue '{}': {}", cue.label, e);
+                    continue;
+                }
+            };
+
+            scheduled_cues.push(ScheduledCue {
+                fire_at,
+                phase,
+                label: cue.label.clone(),
+                cue_type: CueType::Sequence {
+                    clip,
+                    boards: target_info.boards.clone(),
+                },
+                release,
+            });
+        } else {
+            eprintln!(
+                "⚠️ Skipping cue '{}': preset '{}' not found in effects, patterns or sequences",
+                cue.label, preset_name
+            );
+        }
+    }
+
+    (target_map, scheduled_cues)
 }
 
 pub enum PlaybackCommand {
     Play { program: Program, start_time: f64 },
+    /// Play one entry of a setlist from its start
+    PlaySetlist { setlist: Setlist, index: usize },
     Stop,
+    /// Hold the schedule, the effect clocks and the audio where they are
+    Pause,
+    Resume,
+    /// Restart the current program from `position` seconds, keeping it paused if it was
+    Seek { position: f64 },
+    /// Repeat the current program's named loop region until it is released
+    ArmLoop {
+        name: String,
+        reply: oneshot::Sender<Result<(), String>>,
+    },
+    /// Finish the current pass of the armed loop, then play on from its end
+    ReleaseLoop {
+        reply: oneshot::Sender<Result<(), String>>,
+    },
+    /// Cue stacks: fire the next cue, or a pending one straight away
+    Go {
+        reply: oneshot::Sender<Result<CueStackPosition, String>>,
+    },
+    /// Cue stacks: fire the cue before the current one, without its wait or follow
+    Back {
+        reply: oneshot::Sender<Result<CueStackPosition, String>>,
+    },
+    /// Cue stacks: fire cue `index` straight away and run on through its follows
+    JumpToCue {
+        index: usize,
+        reply: oneshot::Sender<Result<CueStackPosition, String>>,
+    },
+    CueStackPosition {
+        reply: oneshot::Sender<Option<CueStackPosition>>,
+    },
     CuesCompleted,
+    /// The program's audio has played up to its `audio_duration`
+    AudioEnded { generation: u64 },
+    /// The transition into the next program of a chain is over
+    HandOff { program: Program, generation: u64 },
+}
+
+/// Why `PlaybackLoop::play` is starting a program
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum StartMode {
+    Fresh,
+    /// Taking over from the previous program of a chain or setlist
+    Chained,
+    /// Moving the current program to a new position
+    Seek,
+}
+
+/// How a program hands over to its `next_program_id`
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Transition {
+    /// Start the next program straight away
+    Immediate,
+    /// Black out the outputs for `transition_duration`, then start
+    Blackout,
+    /// Keep the last look for `transition_duration`, then start
+    Hold,
+}
+
+impl Transition {
+    fn parse(transition_type: &str) -> Self {
+        match transition_type {
+            "immediate" => Transition::Immediate,
+            "blackout" => Transition::Blackout,
+            "hold" => Transition::Hold,
+            other => {
+                eprintln!("⚠️ Unknown transition type '{}', using immediate", other);
+                Transition::Immediate
+            }
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            Transition::Immediate => "immediate",
+            Transition::Blackout => "blackout",
+            Transition::Hold => "hold",
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -50,10 +408,57 @@ pub struct PlaybackState {
     pub audio_track: Option<String>,
     pub active_targets: Vec<ActiveTarget>,
     pub current_session_id: Option<String>,
+    pub setlist: Option<SetlistPosition>,
+}
+
+/// Where playback is in the setlist it was started from
+#[derive(Debug, Clone, Serialize)]
+pub struct SetlistPosition {
+    pub setlist_id: String,
+    pub index: usize,
+    pub program_id: String,
+    pub count: usize,
+}
+
+/// Where a cue-stack program is
+#[derive(Debug, Clone, Serialize)]
+pub struct CueStackPosition {
+    pub program_id: String,
+    /// Last cue fired; None while standing by for the first GO
+    pub index: Option<usize>,
+    pub label: Option<String>,
+    pub count: usize,
+    /// Cue waiting out its wait or a follow; the next GO fires it straight away
+    pub pending: Option<usize>,
+}
+
+/// The cues the last GO, BACK or jump set off
+#[derive(Debug, Default)]
+struct CueStackRun {
+    /// Last cue fired before this run
+    before: Option<usize>,
+    /// Each cue of the run with when it fires, from the start of the run
+    chain: Vec<(usize, Duration)>,
+}
+
+impl CueStackRun {
+    fn position(&self, elapsed: Duration) -> Option<usize> {
+        self.chain
+            .iter()
+            .rev()
+            .find(|(_, at)| *at <= elapsed)
+            .map(|(index, _)| *index)
+            .or(self.before)
+    }
+
+    fn pending(&self, elapsed: Duration) -> Option<usize> {
+        self.chain.iter().find(|(_, at)| *at > elapsed).map(|(index, _)| *index)
+    }
 }
 
 pub struct ProgramEngine {
     command_tx: mpsc::Sender<PlaybackCommand>,
+    state: Arc<RwLock<PlaybackState>>,
 }
 
 fn send_blackout(effects_engine: &EffectsEngine, boards: Vec<BoardTarget>) {
@@ -62,8 +467,10 @@ fn send_blackout(effects_engine: &EffectsEngine, boards: Vec<BoardTarget>) {
             effect_type: EffectType::Solid,
             bpm: 0.0,
             color: [0, 0, 0],
+            sync_rate: 1.0,
         },
         boards,
+        phase: 0.0,
     });
 }
 
@@ -73,16 +480,20 @@ impl ProgramEngine {
         effects_engine: Arc<EffectsEngine>,
         pattern_engine: Arc<PatternEngine>,
         performance_mode: Arc<AtomicBool>,
-        on_audio_play: Option<AudioPlayCallback>,
+        on_audio: Option<AudioCallback>,
         connected_ips: Arc<RwLock<HashSet<String>>>,
         timing_metrics: Option<Arc<TimingMetrics>>,
         playback_history: Option<Arc<PlaybackHistory>>,
+        programs: Arc<RwLock<HashMap<String, Program>>>,
+        broadcast_tx: Arc<broadcast::Sender<SseEvent>>,
+        sequences: Arc<SequenceLibrary>,
     ) -> Self {
         let (command_tx, command_rx) = mpsc::channel(32);
         let state = Arc::new(RwLock::new(PlaybackState {
             audio_track: None,
             active_targets: Vec::new(),
             current_session_id: None,
+            setlist: None,
         }));
 
         let completion_tx = command_tx.clone();
@@ -92,23 +503,45 @@ impl ProgramEngine {
 
         let cue_scheduler = CueScheduler::new(effects_engine.clone(), pattern_engine.clone(), timing_metrics.clone(), on_cues_complete);
 
-        let state_clone = state.clone();
-        let performance_mode_clone = performance_mode.clone();
-        tokio::spawn(Self::run_loop(
-            command_rx,
+        let playback = PlaybackLoop {
+            command_tx: command_tx.clone(),
             config,
             effects_engine,
             pattern_engine,
             cue_scheduler,
-            state_clone,
-            performance_mode_clone,
-            on_audio_play,
+            state: state.clone(),
+            performance_mode,
+            on_audio,
             connected_ips,
             timing_metrics,
             playback_history,
-        ));
+            programs,
+            broadcast_tx,
+            sequences,
+            generation: 0,
+            current: None,
+            start_time: 0.0,
+            paused: false,
+            armed_loop: None,
+            awaiting_audio: false,
+            chain: Vec::new(),
+            setlist: None,
+            cue_stack: None,
+        };
+        tokio::spawn(playback.run(command_rx));
+
+        Self { command_tx, state }
+    }
 
-        Self { command_tx }
+    pub async fn play_setlist(&self, setlist: Setlist, index: usize) -> Result<(), String> {
+        self.command_tx
+            .send(PlaybackCommand::PlaySetlist { setlist, index })
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn setlist_position(&self) -> Option<SetlistPosition> {
+        self.state.read().await.setlist.clone()
     }
 
     pub async fn play(&self, program: Program, start_time: f64) -> Result<(), String> {
@@ -128,328 +561,750 @@ impl ProgramEngine {
             .map_err(|e| e.to_string())
     }
 
-    async fn run_loop(
-        mut command_rx: mpsc::Receiver<PlaybackCommand>,
-        config: Arc<tokio::sync::Mutex<Config>>,
-        effects_engine: Arc<EffectsEngine>,
-        pattern_engine: Arc<PatternEngine>,
-        cue_scheduler: CueScheduler,
-        state: Arc<RwLock<PlaybackState>>,
-        performance_mode: Arc<AtomicBool>,
-        on_audio_play: Option<AudioPlayCallback>,
-        connected_ips: Arc<RwLock<HashSet<String>>>,
-        timing_metrics: Option<Arc<TimingMetrics>>,
-        playback_history: Option<Arc<PlaybackHistory>>,
-    ) {
-        loop {
-            match command_rx.recv().await {
-                Some(PlaybackCommand::Play {
+    pub async fn pause(&self) -> Result<(), String> {
+        self.command_tx
+            .send(PlaybackCommand::Pause)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn resume(&self) -> Result<(), String> {
+        self.command_tx
+            .send(PlaybackCommand::Resume)
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn seek(&self, position: f64) -> Result<(), String> {
+        self.command_tx
+            .send(PlaybackCommand::Seek { position })
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    pub async fn arm_loop(&self, name: &str) -> Result<(), String> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(PlaybackCommand::ArmLoop {
+                name: name.to_string(),
+                reply,
+            })
+            .await
+            .map_err(|e| e.to_string())?;
+        rx.await.map_err(|e| e.to_string())?
+    }
+
+    pub async fn release_loop(&self) -> Result<(), String> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(PlaybackCommand::ReleaseLoop { reply })
+            .await
+            .map_err(|e| e.to_string())?;
+        rx.await.map_err(|e| e.to_string())?
+    }
+
+    pub async fn go(&self) -> Result<CueStackPosition, String> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(PlaybackCommand::Go { reply })
+            .await
+            .map_err(|e| e.to_string())?;
+        rx.await.map_err(|e| e.to_string())?
+    }
+
+    pub async fn back(&self) -> Result<CueStackPosition, String> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(PlaybackCommand::Back { reply })
+            .await
+            .map_err(|e| e.to_string())?;
+        rx.await.map_err(|e| e.to_string())?
+    }
+
+    pub async fn jump_to_cue(&self, index: usize) -> Result<CueStackPosition, String> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx
+            .send(PlaybackCommand::JumpToCue { index, reply })
+            .await
+            .map_err(|e| e.to_string())?;
+        rx.await.map_err(|e| e.to_string())?
+    }
+
+    pub async fn cue_stack_position(&self) -> Option<CueStackPosition> {
+        let (reply, rx) = oneshot::channel();
+        self.command_tx.send(PlaybackCommand::CueStackPosition { reply }).await.ok()?;
+        rx.await.ok()?
+    }
+}
+
+/// The engine's task: owns playback and walks auto-play chains
+struct PlaybackLoop {
+    command_tx: mpsc::Sender<PlaybackCommand>,
+    config: Arc<tokio::sync::Mutex<Config>>,
+    effects_engine: Arc<EffectsEngine>,
+    pattern_engine: Arc<PatternEngine>,
+    cue_scheduler: CueScheduler,
+    state: Arc<RwLock<PlaybackState>>,
+    performance_mode: Arc<AtomicBool>,
+    on_audio: Option<AudioCallback>,
+    connected_ips: Arc<RwLock<HashSet<String>>>,
+    timing_metrics: Option<Arc<TimingMetrics>>,
+    playback_history: Option<Arc<PlaybackHistory>>,
+    programs: Arc<RwLock<HashMap<String, Program>>>,
+    broadcast_tx: Arc<broadcast::Sender<SseEvent>>,
+    sequences: Arc<SequenceLibrary>,
+    /// Bumped on every start and stop so stale timers and hand-offs are ignored
+    generation: u64,
+    /// Program currently playing; `None` when stopped or between programs
+    current: Option<Program>,
+    /// Song position the current schedule was started from
+    start_time: f64,
+    paused: bool,
+    /// Name of the loop region the schedule is repeating
+    armed_loop: Option<String>,
+    /// The program ends when its audio does, not when its last cue fires
+    awaiting_audio: bool,
+    /// Programs played so far in the current chain, to catch cycles
+    chain: Vec<String>,
+    /// Setlist being played; its entries replace `next_program_id` chaining
+    setlist: Option<Setlist>,
+    /// Set while a cue-stack program is current
+    cue_stack: Option<CueStackRun>,
+}
+
+impl PlaybackLoop {
+    async fn run(mut self, mut command_rx: mpsc::Receiver<PlaybackCommand>) {
+        while let Some(command) = command_rx.recv().await {
+            match command {
+                PlaybackCommand::Play {
                     program,
                     start_time,
-                }) => {
+                } => {
                     println!("▶️ Program engine: Play {} @ {}s", program.id, start_time);
+                    self.setlist = None;
+                    self.state.write().await.setlist = None;
+                    self.chain = vec![program.id.clone()];
+                    self.play(program, start_time, StartMode::Fresh).await;
+                }
+                PlaybackCommand::PlaySetlist { setlist, index } => {
+                    let Some(program) = self.setlist_program(&setlist, index).await else {
+                        continue;
+                    };
+                    println!("📜 Program engine: Setlist {} [{}/{}] {}", setlist.id, index + 1, setlist.entries.len(), program.id);
+                    self.setlist = Some(setlist);
+                    self.set_setlist_position(index, &program.id).await;
+                    self.chain = vec![program.id.clone()];
+                    self.play(program, 0.0, StartMode::Fresh).await;
+                }
+                PlaybackCommand::Stop => self.stop().await,
+                PlaybackCommand::Pause => self.pause(),
+                PlaybackCommand::Resume => self.resume(),
+                PlaybackCommand::Seek { position } => {
+                    let Some(program) = self.current.clone() else {
+                        println!("⚠️ Program engine: Seek ignored, nothing is playing");
+                        continue;
+                    };
+                    if self.cue_stack.is_some() {
+                        println!("⚠️ Program engine: Seek ignored, {} is a cue stack", program.id);
+                        continue;
+                    }
+                    println!("⏩ Program engine: Seek {} to {}s", program.id, position);
+                    self.play(program, position.max(0.0), StartMode::Seek).await;
+                }
+                PlaybackCommand::ArmLoop { name, reply } => {
+                    let _ = reply.send(self.arm_loop(name));
+                }
+                PlaybackCommand::ReleaseLoop { reply } => {
+                    let _ = reply.send(self.release_loop());
+                }
+                PlaybackCommand::Go { reply } => {
+                    let _ = reply.send(self.go().await);
+                }
+                PlaybackCommand::Back { reply } => {
+                    let _ = reply.send(self.back().await);
+                }
+                PlaybackCommand::JumpToCue { index, reply } => {
+                    let _ = reply.send(self.fire_cue_stack(index, false, true).await);
+                }
+                PlaybackCommand::CueStackPosition { reply } => {
+                    let _ = reply.send(self.cue_stack_position());
+                }
+                PlaybackCommand::CuesCompleted => {
+                    // A cue stack waits for the next GO until it is stopped
+                    if self.current.is_none() || self.cue_stack.is_some() {
+                        continue;
+                    }
+                    if self.awaiting_audio {
+                        println!("✅ Program engine: All cues completed, waiting for audio to end");
+                        continue;
+                    }
+                    println!("✅ Program engine: All cues completed naturally");
+                    self.finish().await;
+                }
+                PlaybackCommand::AudioEnded { generation } => {
+                    if generation != self.generation || self.current.is_none() {
+                        continue;
+                    }
+                    println!("✅ Program engine: Audio ended");
+                    self.finish().await;
+                }
+                PlaybackCommand::HandOff { program, generation } => {
+                    if generation != self.generation {
+                        println!("⏭️ Program engine: Hand-off to {} cancelled", program.id);
+                        continue;
+                    }
+                    self.play(program, 0.0, StartMode::Chained).await;
+                }
+            }
+        }
+    }
 
-                    cue_scheduler.stop();
-                    let _ = effects_engine.send_command(EngineCommand::Stop);
-                    let _ = pattern_engine.send_command(PatternCommand::Stop);
+    /// Start `program` from `start_time`
+    ///
+    /// A chained start keeps the outputs as the transition left them; the
+    /// next program's first cues take them over. A seek stays in the current
+    /// playback session and keeps a paused program paused.
+    async fn play(&mut self, program: Program, start_time: f64, mode: StartMode) {
+        self.generation += 1;
+        self.cue_scheduler.stop();
 
-                    performance_mode.store(true, Ordering::SeqCst);
-                    println!("🎭 Performance mode: ON (WebSocket reconnection paused)");
+        if mode == StartMode::Chained {
+            println!("⏭️ Program engine: Chained into {}", program.id);
+        } else {
+            let _ = self.effects_engine.send_command(EngineCommand::Stop);
+            let _ = self.pattern_engine.send_command(PatternCommand::Stop);
 
-                    if let Some(ref metrics) = timing_metrics {
-                        metrics.reset();
-                    }
+            self.performance_mode.store(true, Ordering::SeqCst);
+            println!("🎭 Performance mode: ON (WebSocket reconnection paused)");
+        }
 
-                    let session_id = if let Some(ref history) = playback_history {
-                        let id = history.start_session(&program.id, &program.song_name);
-                        println!("📊 Started playback session: {}", id);
-                        Some(id)
-                    } else {
-                        None
-                    };
+        if mode != StartMode::Seek {
+            self.clear_pause();
 
-                    let bpm = program.bpm.unwrap_or(120) as f64;
-
-                    let (target_map, scheduled_cues, audio_sync_delay_ms) = {
-                        let cfg = config.lock().await;
-                        let audio_sync_delay_ms = cfg.loopy_pro.audio_sync_delay_ms;
-                        let online_ips = connected_ips.read().await;
-
-                        let unique_targets: HashSet<String> = program
-                            .cues
-                            .iter()
-                            .flat_map(|c| c.targets.iter().cloned())
-                            .collect();
-
-                        let mut target_map: HashMap<String, TargetInfo> = HashMap::new();
-                        for target in &unique_targets {
-                            let target_boards = cfg.get_target_boards(target);
-                            if !target_boards.is_empty() {
-                                let mut boards: Vec<BoardTarget> = Vec::new();
-                                let mut board_info_by_id: HashMap<String, BoardInfo> = HashMap::new();
-                                let mut member_ids: Vec<String> = Vec::new();
-
-                                for b in &target_boards {
-                                    if online_ips.contains(&b.ip) {
-                                        boards.push(BoardTarget {
-                                            ip: b.ip.clone(),
-                                            universe: b.universe.unwrap_or(1),
-                                            led_count: b.led_count.unwrap_or(60) as usize,
-                                        });
-                                        board_info_by_id.insert(
-                                            b.id.clone(),
-                                            BoardInfo {
-                                                ip: b.ip.clone(),
-                                                universe: b.universe.unwrap_or(1),
-                                                led_count: b.led_count.unwrap_or(60) as usize,
-                                            },
-                                        );
-                                        member_ids.push(b.id.clone());
-                                    }
-                                }
-
-                                if boards.is_empty() {
-                                    println!(
-                                        "⚠️ Target '{}' has no online boards (0/{} online)",
-                                        target,
-                                        target_boards.len()
-                                    );
-                                    continue;
-                                }
-                                println!(
-                                    "🎯 Target '{}': {}/{} boards online",
-                                    target,
-                                    boards.len(),
-                                    target_boards.len()
-                                );
-                                target_map.insert(
-                                    target.clone(),
-                                    TargetInfo { boards, board_info_by_id, member_ids },
-                                );
-                            }
-                        }
+            if let Some(ref metrics) = self.timing_metrics {
+                metrics.reset();
+            }
+        }
 
-                        let mut preset_map: HashMap<String, PresetInfo> = HashMap::new();
-                        for preset in &cfg.effect_presets {
-                            let effect_type = match preset.effect_type.parse::<EffectType>() {
-                                Ok(t) => t,
-                                Err(_) => continue,
-                            };
-                            preset_map.insert(
-                                preset.name.clone(),
-                                PresetInfo {
-                                    effect_type,
-                                    color: preset.color,
-                                },
-                            );
-                        }
+        let session_id = if mode == StartMode::Seek {
+            self.state.read().await.current_session_id.clone()
+        } else if let Some(ref history) = self.playback_history {
+            let id = history.start_session(&program.id, &program.song_name);
+            println!("📊 Started playback session: {}", id);
+            Some(id)
+        } else {
+            None
+        };
 
-                        let mut pattern_preset_map: HashMap<String, PatternPresetInfo> =
-                            HashMap::new();
-                        for preset in &cfg.pattern_presets {
-                            pattern_preset_map.insert(
-                                preset.name.clone(),
-                                PatternPresetInfo {
-                                    pattern_type: preset.pattern.clone(),
-                                    color: preset.colour,
-                                },
-                            );
-                        }
+        let (target_map, scheduled_cues, audio_sync_delay_ms) = self.schedule(&program, start_time).await;
 
-                        let mut scheduled_cues: Vec<ScheduledCue> = Vec::new();
-                        for cue in program.cues.iter().filter(|c| c.time >= start_time) {
-                            let preset_name = &cue.preset_name;
-                            let fire_at = Duration::from_secs_f64((cue.time - start_time).max(0.0));
-
-                            if cue.targets.is_empty() {
-                                eprintln!("⚠️ Skipping cue '{}': no targets", cue.label);
-                                continue;
-                            }
-
-                            for target in &cue.targets {
-                                if let Some(pattern_preset) = pattern_preset_map.get(preset_name) {
-                                    let target_info = match target_map.get(target) {
-                                        Some(t) => t,
-                                        None => {
-                                            eprintln!(
-                                                "⚠️ Skipping pattern cue '{}': target '{}' not found or offline",
-                                                cue.label, target
-                                            );
-                                            continue;
-                                        }
-                                    };
-
-                                    scheduled_cues.push(ScheduledCue {
-                                        fire_at,
-                                        label: cue.label.clone(),
-                                        cue_type: CueType::Pattern(PatternCueConfig {
-                                            pattern_type: pattern_preset.pattern_type.clone(),
-                                            color: pattern_preset.color,
-                                            member_ids: target_info.member_ids.clone(),
-                                            board_info: target_info.board_info_by_id.clone(),
-                                            bpm,
-                                            sync_rate: cue.sync_rate,
-                                        }),
-                                    });
-                                } else if let Some(preset) = preset_map.get(preset_name) {
-                                    let target_info = match target_map.get(target) {
-                                        Some(t) => t,
-                                        None => {
-                                            eprintln!(
-                                                "⚠️ Skipping cue '{}': target '{}' not found",
-                                                cue.label, target
-                                            );
-                                            continue;
-                                        }
-                                    };
-
-                                    let effective_bpm = bpm * cue.sync_rate;
-
-                                    scheduled_cues.push(ScheduledCue {
-                                        fire_at,
-                                        label: cue.label.clone(),
-                                        cue_type: CueType::Effect {
-                                            config: EffectConfig {
-                                                effect_type: preset.effect_type,
-                                                bpm: effective_bpm,
-                                                color: preset.color,
-                                            },
-                                            boards: target_info.boards.clone(),
-                                        },
-                                    });
-                                } else {
-                                    eprintln!("⚠️ Skipping cue '{}': preset '{}' not found in effects or patterns", cue.label, preset_name);
-                                }
-                            }
-                        }
+        if mode != StartMode::Chained {
+            println!(
+                "🔌 Sending Off to {} targets before playback",
+                target_map.len()
+            );
+            for (_, target_info) in &target_map {
+                send_blackout(&self.effects_engine, target_info.boards.clone());
+            }
+        }
 
-                        (target_map, scheduled_cues, audio_sync_delay_ms)
-                    };
+        println!(
+            "📍 Scheduling {} cues from {}s",
+            scheduled_cues.len(),
+            start_time
+        );
 
-                    println!(
-                        "🔌 Sending Off to {} targets before playback",
-                        target_map.len()
-                    );
-                    for (_, target_info) in &target_map {
-                        send_blackout(&effects_engine, target_info.boards.clone());
-                    }
+        let playback_start = if audio_sync_delay_ms < 0 {
+            let delay_ms = audio_sync_delay_ms.unsigned_abs();
+            println!("⏱️ Audio sync: -{}ms (delaying lights)", delay_ms);
+            Instant::now() + Duration::from_millis(delay_ms)
+        } else {
+            Instant::now()
+        };
 
-                    println!(
-                        "📍 Scheduling {} cues from {}s",
-                        scheduled_cues.len(),
-                        start_time
-                    );
+        {
+            let mut s = self.state.write().await;
+            s.audio_track = Some(program.loopy_pro_track.clone());
+            s.active_targets = target_map
+                .values()
+                .map(|t| ActiveTarget {
+                    boards: t.boards.clone(),
+                })
+                .collect();
+            s.current_session_id = session_id.clone();
+        }
 
-                    let playback_start = if audio_sync_delay_ms < 0 {
-                        let delay_ms = audio_sync_delay_ms.unsigned_abs();
-                        println!("⏱️ Audio sync: -{}ms (delaying lights)", delay_ms);
-                        Instant::now() + Duration::from_millis(delay_ms)
-                    } else {
-                        Instant::now()
-                    };
+        if program.mode == ProgramMode::CueStack {
+            println!("🎬 Cue stack {}: standing by for GO ({} cues)", program.id, program.cues.len());
+            self.start_time = 0.0;
+            self.armed_loop = None;
+            self.awaiting_audio = false;
+            self.cue_stack = Some(CueStackRun::default());
+            self.current = Some(program);
+            self.announce_cue_stack();
+            return;
+        }
+        self.cue_stack = None;
 
-                    {
-                        let mut s = state.write().await;
-                        s.audio_track = Some(program.loopy_pro_track.clone());
-                        s.active_targets = target_map
-                            .values()
-                            .map(|t| ActiveTarget {
-                                boards: t.boards.clone(),
-                            })
-                            .collect();
-                        s.current_session_id = session_id.clone();
-                    }
+        let _ = self.cue_scheduler.start(scheduled_cues, playback_start, self.paused);
 
-                    let _ = cue_scheduler.start(scheduled_cues, playback_start);
+        let track = program.loopy_pro_track.clone();
+        self.start_time = start_time;
+        self.armed_loop = None;
+        self.current = Some(program);
+        self.awaiting_audio = false;
+        if !self.paused {
+            self.arm_audio_end(start_time, Duration::from_millis(audio_sync_delay_ms.max(0) as u64));
+        }
 
-                    if audio_sync_delay_ms > 0 {
-                        println!("⏱️ Audio sync: +{}ms (delaying audio)", audio_sync_delay_ms);
-                        tokio::time::sleep(Duration::from_millis(audio_sync_delay_ms as u64)).await;
-                    }
+        if audio_sync_delay_ms > 0 {
+            println!("⏱️ Audio sync: +{}ms (delaying audio)", audio_sync_delay_ms);
+            tokio::time::sleep(Duration::from_millis(audio_sync_delay_ms as u64)).await;
+        }
 
-                    if let Some(ref callback) = on_audio_play {
-                        println!("🎵 Triggering audio playback: {}", program.loopy_pro_track);
-                        callback(&program.loopy_pro_track);
-                    }
-                }
+        if mode == StartMode::Seek {
+            self.send_audio(&track, AudioCommand::Seek(start_time));
+        } else {
+            println!("🎵 Triggering audio playback: {}", track);
+            self.send_audio(&track, AudioCommand::Play);
+        }
+    }
 
-                Some(PlaybackCommand::Stop) => {
-                    println!("⏹️ Program engine: Stop command received");
+    /// End the program when its audio runs out, `position` seconds into the song
+    fn arm_audio_end(&mut self, position: f64, delay: Duration) {
+        // A vamp has no known end; the loop release arms the timer
+        if self.armed_loop.is_some() {
+            return;
+        }
+        let Some(audio_duration) = self.current.as_ref().and_then(|p| p.audio_duration) else {
+            return;
+        };
+        let remaining = audio_duration - position;
+        if remaining <= 0.0 {
+            return;
+        }
 
-                    cue_scheduler.stop();
+        self.awaiting_audio = true;
+        let ends_in = Duration::from_secs_f64(remaining) + delay;
+        let command_tx = self.command_tx.clone();
+        let generation = self.generation;
+        tokio::spawn(async move {
+            tokio::time::sleep(ends_in).await;
+            let _ = command_tx.send(PlaybackCommand::AudioEnded { generation }).await;
+        });
+    }
 
-                    println!("  → Sending Stop to pattern engine...");
-                    let _ = pattern_engine.send_command(PatternCommand::Stop);
-                    println!("  ✓ Stop sent to pattern engine");
+    fn send_audio(&self, track: &str, command: AudioCommand) {
+        if let Some(ref callback) = self.on_audio {
+            callback(track, command);
+        }
+    }
 
-                    let (active_targets, session_id) = {
-                        let s = state.read().await;
-                        (s.active_targets.clone(), s.current_session_id.clone())
-                    };
+    fn pause(&mut self) {
+        let Some(ref program) = self.current else {
+            println!("⚠️ Program engine: Pause ignored, nothing is playing");
+            return;
+        };
+        if self.paused {
+            return;
+        }
 
-                    if let (Some(ref history), Some(ref sid), Some(ref metrics)) = (&playback_history, &session_id, &timing_metrics) {
-                        let snapshot = metrics.snapshot();
-                        history.end_session(sid, &snapshot, false);
-                        println!("📊 Ended playback session: {}", sid);
-                    }
+        let track = program.loopy_pro_track.clone();
+        println!("⏸️ Program engine: Pause {}", program.id);
 
-                    println!(
-                        "  → Sending blackout to {} targets...",
-                        active_targets.len()
-                    );
-                    for target in &active_targets {
-                        send_blackout(&effects_engine, target.boards.clone());
-                    }
-                    println!("  ✓ Blackout sent to all targets");
+        // Cancels the audio-end timer; resume arms a fresh one
+        self.generation += 1;
+        self.paused = true;
+        self.cue_scheduler.pause();
+        let _ = self.effects_engine.send_command(EngineCommand::Pause);
+        let _ = self.pattern_engine.send_command(PatternCommand::Pause);
+        if self.cue_stack.is_none() {
+            self.send_audio(&track, AudioCommand::Pause);
+        }
+    }
 
-                    let _ = effects_engine.send_command(EngineCommand::Stop);
+    fn resume(&mut self) {
+        let Some(ref program) = self.current else {
+            println!("⚠️ Program engine: Resume ignored, nothing is playing");
+            return;
+        };
+        if !self.paused {
+            return;
+        }
 
-                    performance_mode.store(false, Ordering::SeqCst);
-                    println!("🎭 Performance mode: OFF (WebSocket reconnection resumed)");
+        let track = program.loopy_pro_track.clone();
+        println!("▶️ Program engine: Resume {}", program.id);
 
-                    {
-                        let mut s = state.write().await;
-                        s.audio_track = None;
-                        s.active_targets.clear();
-                        s.current_session_id = None;
-                    }
-                }
+        self.clear_pause();
+        if self.cue_stack.is_some() {
+            return;
+        }
+        let position = self.start_time + self.cue_scheduler.elapsed().as_secs_f64();
+        self.arm_audio_end(position, Duration::ZERO);
+        self.send_audio(&track, AudioCommand::Resume);
+    }
 
-                Some(PlaybackCommand::CuesCompleted) => {
-                    println!("✅ Program engine: All cues completed naturally");
+    /// Fire the cue after the last one fired, or the pending cue without its wait
+    async fn go(&mut self) -> Result<CueStackPosition, String> {
+        let run = self.cue_stack.as_ref().ok_or("No cue stack is playing")?;
+        let elapsed = self.cue_scheduler.elapsed();
+        match run.pending(elapsed) {
+            Some(index) => self.fire_cue_stack(index, false, true).await,
+            None => {
+                let next = run.position(elapsed).map_or(0, |index| index + 1);
+                self.fire_cue_stack(next, true, true).await
+            }
+        }
+    }
 
-                    let _ = pattern_engine.send_command(PatternCommand::Stop);
+    async fn back(&mut self) -> Result<CueStackPosition, String> {
+        let run = self.cue_stack.as_ref().ok_or("No cue stack is playing")?;
+        let previous = run
+            .position(self.cue_scheduler.elapsed())
+            .and_then(|index| index.checked_sub(1))
+            .ok_or("Already at the top of the cue stack")?;
+        self.fire_cue_stack(previous, false, false).await
+    }
 
-                    let (active_targets, session_id) = {
-                        let s = state.read().await;
-                        (s.active_targets.clone(), s.current_session_id.clone())
-                    };
+    /// Replace whatever the stack has pending with cue `index` and its follows
+    async fn fire_cue_stack(&mut self, index: usize, with_wait: bool, with_follows: bool) -> Result<CueStackPosition, String> {
+        let run = self.cue_stack.as_ref().ok_or("No cue stack is playing")?;
+        let program = self.current.clone().ok_or("No cue stack is playing")?;
+        if index >= program.cues.len() {
+            return Err(format!(
+                "Cue stack {} has {} cues, no cue {}",
+                program.id,
+                program.cues.len(),
+                index + 1
+            ));
+        }
 
-                    if let (Some(ref history), Some(ref sid), Some(ref metrics)) = (&playback_history, &session_id, &timing_metrics) {
-                        let snapshot = metrics.snapshot();
-                        history.end_session(sid, &snapshot, true);
-                        println!("📊 Ended playback session (completed): {}", sid);
-                    }
+        let chain = program.cue_stack_chain(index, with_wait, with_follows);
+        let before = run.position(self.cue_scheduler.elapsed());
 
-                    for target in &active_targets {
-                        send_blackout(&effects_engine, target.boards.clone());
-                    }
+        // The chain as a timed program, so its cues resolve and fire like any other
+        let mut timed = program.clone();
+        timed.cues = chain
+            .iter()
+            .map(|&(idx, at)| Cue {
+                time: at,
+                position: None,
+                ..program.cues[idx].clone()
+            })
+            .collect();
+        let (_, scheduled_cues, _) = self.schedule(&timed, 0.0).await;
 
-                    let _ = effects_engine.send_command(EngineCommand::Stop);
+        println!(
+            "🎬 Cue stack {}: GO '{}' ({}/{}, {} follow-ons)",
+            program.id,
+            program.cues[index].label,
+            index + 1,
+            program.cues.len(),
+            chain.len() - 1
+        );
 
-                    performance_mode.store(false, Ordering::SeqCst);
-                    println!("🎭 Performance mode: OFF (WebSocket reconnection resumed)");
+        self.cue_scheduler.start(scheduled_cues, Instant::now(), self.paused)?;
+        self.cue_stack = Some(CueStackRun {
+            before,
+            chain: chain
+                .into_iter()
+                .map(|(idx, at)| (idx, Duration::from_secs_f64(at)))
+                .collect(),
+        });
 
-                    {
-                        let mut s = state.write().await;
-                        s.audio_track = None;
-                        s.active_targets.clear();
-                        s.current_session_id = None;
-                    }
+        self.announce_cue_stack().ok_or("No cue stack is playing".to_string())
+    }
+
+    fn cue_stack_position(&self) -> Option<CueStackPosition> {
+        let run = self.cue_stack.as_ref()?;
+        let program = self.current.as_ref()?;
+        let elapsed = self.cue_scheduler.elapsed();
+        let index = run.position(elapsed);
+        Some(CueStackPosition {
+            program_id: program.id.clone(),
+            index,
+            label: index.and_then(|i| program.cues.get(i)).map(|c| c.label.clone()),
+            count: program.cues.len(),
+            pending: run.pending(elapsed),
+        })
+    }
+
+    fn announce_cue_stack(&self) -> Option<CueStackPosition> {
+        let position = self.cue_stack_position()?;
+        let _ = self.broadcast_tx.send(SseEvent::CueStackPosition(position.clone()));
+        Some(position)
+    }
+
+    fn arm_loop(&mut self, name: String) -> Result<(), String> {
+        let program = self.current.as_ref().ok_or("No program is playing")?;
+        let region = program
+            .loop_region(&name)
+            .ok_or_else(|| format!("Program {} has no loop region '{}'", program.id, name))?;
+        if region.start < self.start_time {
+            return Err(format!(
+                "Loop region '{}' starts at {}s, before playback started at {}s; seek to its start first",
+                name, region.start, self.start_time
+            ));
+        }
+
+        self.cue_scheduler.arm_loop(LoopSpan {
+            start: Duration::from_secs_f64(region.start - self.start_time),
+            end: Duration::from_secs_f64(region.end - self.start_time),
+        })?;
+
+        println!("🔁 Program engine: Loop '{}' armed ({}s - {}s)", name, region.start, region.end);
+        let program_id = program.id.clone();
+
+        // Cancels the audio-end timer until the loop is released
+        self.generation += 1;
+        self.armed_loop = Some(name.clone());
+        let _ = self.broadcast_tx.send(SseEvent::LoopArmed { program_id, name });
+        Ok(())
+    }
+
+    fn release_loop(&mut self) -> Result<(), String> {
+        let name = self.armed_loop.take().ok_or("No loop is armed")?;
+        let program_id = self.current.as_ref().map(|p| p.id.clone()).unwrap_or_default();
+        self.cue_scheduler.release_loop();
+
+        println!("🔁 Program engine: Loop '{}' released", name);
+
+        if !self.paused {
+            let position = self.start_time + self.cue_scheduler.elapsed().as_secs_f64();
+            self.arm_audio_end(position, Duration::ZERO);
+        }
+        let _ = self.broadcast_tx.send(SseEvent::LoopReleased { program_id, name });
+        Ok(())
+    }
+
+    /// Restart the schedule and effect clocks if they were paused
+    fn clear_pause(&mut self) {
+        if !self.paused {
+            return;
+        }
+        self.paused = false;
+        self.cue_scheduler.resume();
+        let _ = self.effects_engine.send_command(EngineCommand::Resume);
+        let _ = self.pattern_engine.send_command(PatternCommand::Resume);
+    }
+
+    /// Resolve the program's targets and turn its cues from `start_time` on into scheduled cues
+    async fn schedule(
+        &self,
+        program: &Program,
+        start_time: f64,
+    ) -> (HashMap<String, TargetInfo>, Vec<ScheduledCue>, i64) {
+        let cfg = self.config.lock().await;
+        let online_ips = self.connected_ips.read().await;
+        let (target_map, scheduled_cues) = build_schedule(program, start_time, &cfg, &self.sequences, |ip| online_ips.contains(ip));
+        (target_map, scheduled_cues, cfg.loopy_pro.audio_sync_delay_ms)
+    }
+
+    async fn stop(&mut self) {
+        println!("⏹️ Program engine: Stop command received");
+
+        self.generation += 1;
+        self.current = None;
+        self.armed_loop = None;
+        self.cue_stack = None;
+        self.chain.clear();
+        self.cue_scheduler.stop();
+        self.clear_pause();
+        self.end_session(false).await;
+        self.release_outputs().await;
+    }
+
+    /// The current program has played out; chain into the next one or stop
+    async fn finish(&mut self) {
+        let Some(program) = self.current.take() else {
+            return;
+        };
+
+        self.cue_scheduler.stop();
+        self.end_session(true).await;
+
+        if self.setlist.is_some() {
+            match self.next_setlist_entry().await {
+                Some((next, transition, duration_ms)) => {
+                    self.hand_off(&program, next, transition, duration_ms).await
                 }
+                None => self.release_outputs().await,
+            }
+            return;
+        }
+
+        match self.next_program(&program).await {
+            Some(next) => {
+                let transition = Transition::parse(&program.transition_type);
+                self.hand_off(&program, next, transition, program.transition_duration).await
+            }
+            None => self.release_outputs().await,
+        }
+    }
+
+    /// Program of a setlist entry, if the entry and its program exist
+    async fn setlist_program(&self, setlist: &Setlist, index: usize) -> Option<Program> {
+        let Some(entry) = setlist.entries.get(index) else {
+            eprintln!("⚠️ Setlist '{}' has no entry {}", setlist.id, index + 1);
+            return None;
+        };
+        let program = self.programs.read().await.get(&entry.program_id).cloned();
+        if program.is_none() {
+            eprintln!(
+                "⚠️ Setlist '{}': program '{}' not found",
+                setlist.id, entry.program_id
+            );
+        }
+        program
+    }
+
+    /// Record and announce the setlist entry now playing
+    async fn set_setlist_position(&self, index: usize, program_id: &str) {
+        let Some(ref setlist) = self.setlist else {
+            return;
+        };
+        let position = SetlistPosition {
+            setlist_id: setlist.id.clone(),
+            index,
+            program_id: program_id.to_string(),
+            count: setlist.entries.len(),
+        };
+        self.state.write().await.setlist = Some(position.clone());
+        let _ = self.broadcast_tx.send(SseEvent::SetlistPosition(position));
+    }
+
+    /// The entry after the current one, when the current entry has a transition into it
+    async fn next_setlist_entry(&self) -> Option<(Program, Transition, u32)> {
+        let setlist = self.setlist.as_ref()?;
+        let index = self.state.read().await.setlist.as_ref()?.index;
+
+        match setlist.advance(index) {
+            Advance::Wait => {
+                println!("📜 Setlist {}: waiting for next after entry {}", setlist.id, index + 1);
+                None
+            }
+            Advance::Finished => {
+                println!("📜 Setlist {}: finished", setlist.id);
+                None
+            }
+            Advance::Next { index, transition_type, duration_ms } => {
+                let next = self.setlist_program(setlist, index).await?;
+                self.set_setlist_position(index, &next.id).await;
+                Some((next, Transition::parse(transition_type), duration_ms))
+            }
+        }
+    }
+
+    async fn end_session(&self, completed: bool) {
+        let session_id = self.state.write().await.current_session_id.take();
 
-                None => break,
+        if let (Some(ref history), Some(ref sid), Some(ref metrics)) = (&self.playback_history, &session_id, &self.timing_metrics) {
+            let snapshot = metrics.snapshot();
+            history.end_session(sid, &snapshot, completed);
+            if completed {
+                println!("📊 Ended playback session (completed): {}", sid);
+            } else {
+                println!("📊 Ended playback session: {}", sid);
             }
         }
     }
+
+    /// Black out everything playback was driving and leave performance mode
+    async fn release_outputs(&self) {
+        println!("  → Sending Stop to pattern engine...");
+        let _ = self.pattern_engine.send_command(PatternCommand::Stop);
+        println!("  ✓ Stop sent to pattern engine");
+
+        let active_targets = self.state.read().await.active_targets.clone();
+
+        println!(
+            "  → Sending blackout to {} targets...",
+            active_targets.len()
+        );
+        for target in &active_targets {
+            send_blackout(&self.effects_engine, target.boards.clone());
+        }
+        println!("  ✓ Blackout sent to all targets");
+
+        let _ = self.effects_engine.send_command(EngineCommand::Stop);
+
+        self.performance_mode.store(false, Ordering::SeqCst);
+        println!("🎭 Performance mode: OFF (WebSocket reconnection resumed)");
+
+        {
+            let mut s = self.state.write().await;
+            s.audio_track = None;
+            s.active_targets.clear();
+            s.current_session_id = None;
+        }
+    }
+
+    /// The program `program` chains into, unless the chain ends or loops back on itself
+    async fn next_program(&self, program: &Program) -> Option<Program> {
+        let next_id = program.next_program_id.as_deref().filter(|id| !id.is_empty())?;
+
+        let next = self.programs.read().await.get(next_id).cloned();
+        let reason = match next {
+            Some(next) if !self.chain.contains(&next.id) => return Some(next),
+            Some(_) => format!("cycle: '{}' already played in this chain", next_id),
+            None => format!("next program '{}' not found", next_id),
+        };
+
+        eprintln!("⚠️ Auto-play chain ended after {}: {}", program.id, reason);
+        let _ = self.broadcast_tx.send(SseEvent::ProgramChainEnded {
+            program_id: program.id.clone(),
+            reason,
+        });
+        None
+    }
+
+    /// Apply `transition` out of `from`, then start `next`
+    async fn hand_off(&mut self, from: &Program, next: Program, transition: Transition, duration_ms: u32) {
+        let duration_ms = match transition {
+            Transition::Immediate => 0,
+            Transition::Blackout | Transition::Hold => duration_ms,
+        };
+
+        println!(
+            "⏭️ Program engine: {} → {} ({}, {}ms)",
+            from.id,
+            next.id,
+            transition.as_str(),
+            duration_ms
+        );
+
+        if transition == Transition::Blackout {
+            let _ = self.pattern_engine.send_command(PatternCommand::Stop);
+            let active_targets = self.state.read().await.active_targets.clone();
+            for target in &active_targets {
+                send_blackout(&self.effects_engine, target.boards.clone());
+            }
+        }
+
+        let _ = self.broadcast_tx.send(SseEvent::ProgramHandOff {
+            from_program_id: from.id.clone(),
+            to_program_id: next.id.clone(),
+            transition: transition.as_str().to_string(),
+            transition_duration: duration_ms,
+        });
+
+        self.chain.push(next.id.clone());
+
+        if duration_ms == 0 {
+            self.play(next, 0.0, StartMode::Chained).await;
+            return;
+        }
+
+        let command_tx = self.command_tx.clone();
+        let generation = self.generation;
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(duration_ms as u64)).await;
+            let _ = command_tx
+                .send(PlaybackCommand::HandOff {
+                    program: next,
+                    generation,
+                })
+                .await;
+        });
+    }
 }
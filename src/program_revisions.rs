@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::program::{Cue, Program};
+
+/// Revisions kept per program; older ones are pruned on save
+const MAX_REVISIONS: usize = 50;
+
+/// A saved version of a program
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Revision {
+    pub revision: u64,
+    pub saved_at: String,
+    pub summary: String,
+    pub program: Program,
+}
+
+/// A revision without its program, for listings
+#[derive(Debug, Clone, Serialize)]
+pub struct RevisionInfo {
+    pub revision: u64,
+    pub saved_at: String,
+    pub summary: String,
+    pub cue_count: usize,
+}
+
+impl From<&Revision> for RevisionInfo {
+    fn from(revision: &Revision) -> Self {
+        Self {
+            revision: revision.revision,
+            saved_at: revision.saved_at.clone(),
+            summary: revision.summary.clone(),
+            cue_count: revision.program.cues.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CueChange {
+    pub before: Cue,
+    pub after: Cue,
+}
+
+/// What changed between two versions of a program
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProgramDiff {
+    pub added: Vec<Cue>,
+    pub removed: Vec<Cue>,
+    pub changed: Vec<CueChange>,
+    /// Program settings other than cues that differ, e.g. "bpm"
+    pub fields: Vec<String>,
+}
+
+impl ProgramDiff {
+    /// Cues are paired by label, in order; identical cues count as unchanged
+    pub fn between(old: &Program, new: &Program) -> Self {
+        let mut diff = ProgramDiff::default();
+
+        let mut unmatched: Vec<&Cue> = old.cues.iter().collect();
+        let mut unpaired_new: Vec<&Cue> = Vec::new();
+        for cue in &new.cues {
+            match unmatched.iter().position(|c| same(c, cue)) {
+                Some(pos) => {
+                    unmatched.remove(pos);
+                }
+                None => unpaired_new.push(cue),
+            }
+        }
+        for cue in unpaired_new {
+            match unmatched.iter().position(|c| c.label == cue.label) {
+                Some(pos) => diff.changed.push(CueChange {
+                    before: unmatched.remove(pos).clone(),
+                    after: cue.clone(),
+                }),
+                None => diff.added.push(cue.clone()),
+            }
+        }
+        diff.removed = unmatched.into_iter().cloned().collect();
+
+        let fields = |program: &Program| match serde_json::to_value(program) {
+            Ok(serde_json::Value::Object(mut map)) => {
+                map.remove("cues");
+                map.remove("version");
+                map
+            }
+            _ => serde_json::Map::new(),
+        };
+        let (old_fields, new_fields) = (fields(old), fields(new));
+        let keys: BTreeSet<&String> = old_fields.keys().chain(new_fields.keys()).collect();
+        diff.fields = keys
+            .into_iter()
+            .filter(|k| old_fields.get(*k) != new_fields.get(*k))
+            .cloned()
+            .collect();
+
+        diff
+    }
+
+    pub fn summary(&self) -> String {
+        let mut parts = Vec::new();
+        for (count, what) in [
+            (self.added.len(), "added"),
+            (self.removed.len(), "removed"),
+            (self.changed.len(), "changed"),
+        ] {
+            if count > 0 {
+                parts.push(format!("{} {} {}", count, if count == 1 { "cue" } else { "cues" }, what));
+            }
+        }
+        if !self.fields.is_empty() {
+            parts.push(format!("{} changed", self.fields.join(", ")));
+        }
+        if parts.is_empty() {
+            "No changes".to_string()
+        } else {
+            parts.join("; ")
+        }
+    }
+}
+
+fn same(a: &Cue, b: &Cue) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Record `program` as a new revision
+///
+/// `previous` is the version being replaced; it is kept as the first revision
+/// when the program has no history yet. `note` replaces the generated summary.
+pub fn record(
+    revisions_path: &Path,
+    program: &Program,
+    previous: Option<&Program>,
+    note: Option<&str>,
+) -> Result<u64, Box<dyn std::error::Error>> {
+    let dir = revisions_path.join(&program.id);
+    fs::create_dir_all(&dir)?;
+
+    let mut numbers = revision_numbers(&dir)?;
+    if numbers.is_empty() {
+        if let Some(previous) = previous {
+            write(&dir, 1, "Before first tracked save".to_string(), previous)?;
+            numbers.push(1);
+        }
+    }
+
+    let summary = match (note, previous) {
+        (Some(note), _) => note.to_string(),
+        (None, Some(previous)) => ProgramDiff::between(previous, program).summary(),
+        (None, None) => format!("Created with {} cues", program.cues.len()),
+    };
+    let revision = numbers.last().copied().unwrap_or(0) + 1;
+    write(&dir, revision, summary, program)?;
+    numbers.push(revision);
+
+    let excess = numbers.len().saturating_sub(MAX_REVISIONS);
+    for old in &numbers[..excess] {
+        if let Err(e) = fs::remove_file(dir.join(format!("{}.json", old))) {
+            warn!("Failed to prune revision {} of {}: {}", old, program.id, e);
+        }
+    }
+
+    info!("Recorded revision {} of program {}", revision, program.id);
+    Ok(revision)
+}
+
+/// Revisions of a program, oldest first
+pub fn list(revisions_path: &Path, program_id: &str) -> Result<Vec<Revision>, Box<dyn std::error::Error>> {
+    let dir = revisions_path.join(program_id);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut revisions = Vec::new();
+    for number in revision_numbers(&dir)? {
+        match load(revisions_path, program_id, number) {
+            Ok(revision) => revisions.push(revision),
+            Err(e) => warn!("Failed to read revision {} of {}: {}", number, program_id, e),
+        }
+    }
+    Ok(revisions)
+}
+
+pub fn load(revisions_path: &Path, program_id: &str, revision: u64) -> Result<Revision, Box<dyn std::error::Error>> {
+    let file_path = revisions_path.join(program_id).join(format!("{}.json", revision));
+    let json = fs::read_to_string(file_path)?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+fn write(dir: &Path, revision: u64, summary: String, program: &Program) -> Result<(), Box<dyn std::error::Error>> {
+    let entry = Revision {
+        revision,
+        saved_at: chrono::Utc::now().to_rfc3339(),
+        summary,
+        program: program.clone(),
+    };
+    let json = serde_json::to_string_pretty(&entry)?;
+    fs::write(dir.join(format!("{}.json", revision)), json)?;
+    Ok(())
+}
+
+fn revision_numbers(dir: &Path) -> Result<Vec<u64>, Box<dyn std::error::Error>> {
+    let mut numbers: Vec<u64> = fs::read_dir(dir)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                return None;
+            }
+            path.file_stem()?.to_str()?.parse().ok()
+        })
+        .collect();
+    numbers.sort_unstable();
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(cues: serde_json::Value, bpm: u16) -> Program {
+        Program {
+            bpm: Some(bpm),
+            ..Program::fixture("song", cues)
+        }
+    }
+
+    #[test]
+    fn test_diff_pairs_cues_by_label() {
+        let old = program(
+            serde_json::json!([
+                { "time": 0.0, "label": "intro", "targets": ["all"], "preset_name": "red" },
+                { "time": 8.0, "label": "verse", "targets": ["all"], "preset_name": "blue" },
+                { "time": 16.0, "label": "bridge", "targets": ["all"], "preset_name": "blue" }
+            ]),
+            120,
+        );
+        let new = program(
+            serde_json::json!([
+                { "time": 0.0, "label": "intro", "targets": ["all"], "preset_name": "red" },
+                { "time": 9.0, "label": "verse", "targets": ["all"], "preset_name": "blue" },
+                { "time": 24.0, "label": "chorus", "targets": ["all"], "preset_name": "green" }
+            ]),
+            128,
+        );
+
+        let diff = ProgramDiff::between(&old, &new);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].label, "chorus");
+        assert_eq!(diff.removed[0].label, "bridge");
+        assert_eq!(diff.changed[0].after.time, 9.0);
+        assert_eq!(diff.fields, vec!["bpm".to_string()]);
+        assert_eq!(diff.summary(), "1 cue added; 1 cue removed; 1 cue changed; bpm changed");
+        assert_eq!(ProgramDiff::between(&new, &new).summary(), "No changes");
+    }
+}
@@ -1,9 +1,13 @@
 use axum::{extract::{Path, State}, http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use tracing::{error, info};
+use tracing::{error, info, warn};
+
+use super::programs::{announce_update, next_version, record_revision};
 
 use crate::audio;
+use crate::audio_analysis::{self, TempoAnalysis};
+use crate::program::Program;
 use crate::types::{SharedState, UploadAudioRequest, UploadAudioResponse};
 
 #[derive(Serialize, Deserialize)]
@@ -29,11 +33,126 @@ pub async fn upload_audio(
 
     info!("Uploaded audio file: {}", filename);
 
+    let (analysis, program_version) = match run_analysis(&state, &filename).await {
+        Ok(analysis) => {
+            let version = store_analysis(&state, Some(&id), &filename, &analysis).await;
+            (Some(analysis), version)
+        }
+        Err(e) => {
+            warn!("Tempo analysis failed for '{}': {}", filename, e);
+            (None, None)
+        }
+    };
+
     Ok(Json(UploadAudioResponse {
         audio_file: filename,
+        analysis,
+        program_version,
     }))
 }
 
+pub async fn analyze_audio(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Json<TempoAnalysis>, (StatusCode, String)> {
+    if !state.storage_paths.is_available() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Storage not available".to_string(),
+        ));
+    }
+
+    let analysis = run_analysis(&state, &id)
+        .await
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))?;
+
+    store_analysis(&state, None, &id, &analysis).await;
+
+    Ok(Json(analysis))
+}
+
+async fn run_analysis(state: &SharedState, filename: &str) -> Result<TempoAnalysis, String> {
+    let path = audio::AudioFile::resolve_path(filename, &state.storage_paths.audio)
+        .map_err(|e| e.to_string())?;
+    if !path.exists() {
+        return Err(format!("Audio file not found: {}", filename));
+    }
+
+    let analysis = tokio::task::spawn_blocking(move || {
+        audio_analysis::analyze_file(&path).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())??;
+
+    info!(
+        "Analysed {}: {:.2} BPM, downbeat @ {:.3}s (confidence {:.2})",
+        filename, analysis.bpm, analysis.grid_offset, analysis.confidence
+    );
+
+    Ok(analysis)
+}
+
+/// Record the analysis as suggested values on every program using this audio
+///
+/// `program_id` is the program the file was just uploaded for, which may not
+/// reference it yet; returns that program's new version. Files are written
+/// from copies outside the programs lock; a program edited meanwhile keeps
+/// the edit and goes without the suggestion.
+async fn store_analysis(state: &SharedState, program_id: Option<&str>, filename: &str, analysis: &TempoAnalysis) -> Option<u64> {
+    let matching: Vec<Program> = state
+        .programs
+        .read()
+        .await
+        .values()
+        .filter(|p| Some(p.id.as_str()) == program_id || p.audio_file.as_deref() == Some(filename))
+        .cloned()
+        .collect();
+
+    let mut uploaded_for = None;
+    for previous in matching {
+        let mut program = previous.clone();
+        program.suggested_bpm = Some(analysis.bpm);
+        program.suggested_grid_offset = Some(analysis.grid_offset);
+        if program.audio_duration.is_none() {
+            program.audio_duration = Some(analysis.duration);
+        }
+        program.version = next_version(Some(&previous));
+
+        if let Err(e) = program.save_to_file(&state.storage_paths.programs) {
+            error!("Failed to save tempo analysis for program '{}': {}", program.id, e);
+            continue;
+        }
+
+        let mut programs = state.programs.write().await;
+        match programs.get(&program.id) {
+            Some(current) if current.version == previous.version => {
+                programs.insert(program.id.clone(), program.clone());
+            }
+            current => {
+                // Saved or deleted while the file was being written; put its file back
+                warn!("Program '{}' changed during tempo analysis; suggestion not stored", program.id);
+                let restored = match current {
+                    Some(current) => current.save_to_file(&state.storage_paths.programs),
+                    None => fs::remove_file(state.storage_paths.programs.join(format!("{}.json", program.id)))
+                        .map_err(|e| e.into()),
+                };
+                if let Err(e) = restored {
+                    error!("Failed to restore program '{}': {}", program.id, e);
+                }
+                continue;
+            }
+        }
+        drop(programs);
+
+        record_revision(state, &program, Some(&previous), Some("Tempo analysis"));
+        announce_update(state, &program);
+        if Some(program.id.as_str()) == program_id {
+            uploaded_for = Some(program.version);
+        }
+    }
+    uploaded_for
+}
+
 pub async fn get_audio(
     State(state): State<SharedState>,
     Path(id): Path<String>,
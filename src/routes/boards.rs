@@ -48,6 +48,8 @@ pub async fn register_board(
         groups: vec![],
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
     config.boards.push(config::BoardConfig {
         id: payload.id,
@@ -60,6 +62,7 @@ pub async fn register_board(
     if let Err(e) = config.save() {
         warn!("Failed to save boards.toml: {}", e);
     }
+    state.transport_pool.rebuild(&config);
 
     info!(board_id = %board_id, "Registered new board");
     Ok(StatusCode::CREATED)
@@ -82,6 +85,8 @@ pub async fn delete_board(
         groups: vec![],
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
     config.boards.retain(|b| b.id != board_id);
 
@@ -94,6 +99,7 @@ pub async fn delete_board(
         error!(board_id = %board_id, "Failed to save boards.toml: {}", e);
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
+    state.transport_pool.rebuild(&config);
 
     let _ = tx.send(BoardCommand::Shutdown).await;
 
@@ -128,6 +134,8 @@ pub async fn update_board(
         groups: vec![],
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
 
     let board_index = config
@@ -156,6 +164,7 @@ pub async fn update_board(
         error!(old_id = %old_id, "Failed to save boards.toml: {}", e);
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
+    state.transport_pool.rebuild(&config);
 
     let _ = tx.send(BoardCommand::Shutdown).await;
 
@@ -202,6 +211,8 @@ async fn save_board_transition(board_id: &str, transition: u8) -> Result<(), Box
         groups: vec![],
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
 
     if let Some(board_config) = config.boards.iter_mut().find(|b| b.id == board_id) {
@@ -2,7 +2,7 @@ use axum::{extract::State, http::StatusCode, Json};
 use tracing::{error, info};
 
 use crate::config;
-use crate::effects::EffectType;
+use crate::effects::{EffectParams, EffectType};
 use crate::effects_engine::{BoardTarget, EffectConfig, EngineCommand};
 use crate::types::{EffectsEngineStartRequest, SharedState};
 
@@ -38,13 +38,14 @@ pub async fn start_effects_engine(
         effect_type,
         bpm: req.bpm,
         color: preset.color,
+        sync_rate: 1.0,
     };
 
     drop(cfg);
 
     state
         .effects_engine
-        .send_command(EngineCommand::Start { config, boards })
+        .send_command(EngineCommand::Start { config, boards, phase: 0.0 })
         .map_err(|e| {
             error!("Failed to start effects engine: {}", e);
             (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
@@ -69,6 +70,23 @@ pub async fn stop_effects_engine(
     Ok(StatusCode::OK)
 }
 
+pub async fn update_effect_params(
+    State(state): State<SharedState>,
+    Json(params): Json<EffectParams>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    params.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    state
+        .effects_engine
+        .send_command(EngineCommand::SetParams(params))
+        .map_err(|e| {
+            error!("Failed to update effect params: {}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        })?;
+
+    Ok(StatusCode::OK)
+}
+
 pub async fn list_effect_presets(
     State(state): State<SharedState>,
 ) -> Json<Vec<config::EffectPreset>> {
@@ -0,0 +1,53 @@
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+/// Strong ETag for a stored version
+pub fn etag(version: u64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// `body` with the ETag of `version`
+pub fn tagged<T: Serialize>(status: StatusCode, version: u64, body: T) -> Response {
+    (status, [(header::ETAG, etag(version))], Json(body)).into_response()
+}
+
+#[derive(Serialize)]
+struct Conflict<'a, T> {
+    error: String,
+    current: &'a T,
+}
+
+/// Refusal for a write whose `If-Match` doesn't name the stored `version`
+///
+/// Without the header the write gets 428; a stale tag gets 409 with the
+/// current copy so the editor can merge and retry.
+pub fn check_if_match<T: Serialize>(headers: &HeaderMap, version: u64, current: &T) -> Option<Response> {
+    let Some(value) = headers.get(header::IF_MATCH) else {
+        return Some((
+            StatusCode::PRECONDITION_REQUIRED,
+            format!("If-Match header required (current version {})", etag(version)),
+        )
+            .into_response());
+    };
+
+    let expected = version.to_string();
+    let matches = value.to_str().is_ok_and(|tags| {
+        tags.split(',').map(str::trim).any(|tag| {
+            tag == "*" || tag.trim_start_matches("W/").trim_matches('"') == expected
+        })
+    });
+    if matches {
+        return None;
+    }
+
+    Some(tagged(
+        StatusCode::CONFLICT,
+        version,
+        Conflict {
+            error: format!("Edited elsewhere: current version is {}", version),
+            current,
+        },
+    ))
+}
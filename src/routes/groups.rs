@@ -4,7 +4,6 @@ use tracing::{error, info, warn};
 use crate::board::GroupCommand;
 use crate::config::{self, Config};
 use crate::group;
-use crate::transport;
 use crate::types::{
     CreateGroupRequest, GroupBrightnessRequest, GroupColorRequest, GroupEffectRequest,
     GroupOperationResult, GroupPresetRequest, PowerRequest, SharedState,
@@ -28,6 +27,8 @@ pub async fn reconfigure_group_universe(
         groups: vec![],
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
     let group_index = config.groups.iter().position(|g| g.id == group_id).unwrap_or(0);
     let universe = new_universe.unwrap_or((group_index + 1) as u16);
@@ -74,33 +75,12 @@ pub async fn reconfigure_group_universe(
         let _ = task.await;
     }
 
-    let group_board_ips: Vec<String> = board_ips.iter().map(|(_, ip)| ip.clone()).collect();
-
-    if !group_board_ips.is_empty() {
-        let transport_opt = match transport::E131RawTransport::new(group_board_ips.clone(), universe) {
-            Ok(t) => Some(t),
-            Err(e) => {
-                error!(
-                    group_id = %group_id,
-                    universe = %universe,
-                    "Failed to create new E1.31 transport: {}",
-                    e.to_string()
-                );
-                None
-            }
-        };
-
-        if let Some(new_transport) = transport_opt {
-            let mut transports = state.group_e131_transports.write().await;
-            transports.insert(group_id.clone(), new_transport);
-            info!(
-                group_id = %group_id,
-                universe = %universe,
-                board_count = %group_board_ips.len(),
-                "E1.31 transport updated for new universe"
-            );
-        }
-    }
+    state.transport_pool.rebuild(&config);
+    info!(
+        group_id = %group_id,
+        universe = %universe,
+        "E1.31 transport updated for new universe"
+    );
 }
 
 pub async fn configure_board_universe(
@@ -178,6 +158,8 @@ pub async fn create_group(
         groups: vec![],
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
 
     if config.groups.iter().any(|g| g.id == payload.id) {
@@ -202,13 +184,14 @@ pub async fn create_group(
         error!(group_id = %payload.id, "Failed to save boards.toml: {}", e);
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
+    state.transport_pool.rebuild(&config);
 
     info!(group_id = %payload.id, "Created group");
     Ok(StatusCode::CREATED)
 }
 
 pub async fn delete_group(
-    State(_state): State<SharedState>,
+    State(state): State<SharedState>,
     Path(group_id): Path<String>,
 ) -> Result<StatusCode, StatusCode> {
     let mut config = Config::load().unwrap_or(Config {
@@ -217,6 +200,8 @@ pub async fn delete_group(
         groups: vec![],
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
 
     if !config.groups.iter().any(|g| g.id == group_id) {
@@ -229,6 +214,7 @@ pub async fn delete_group(
         error!(group_id = %group_id, "Failed to save boards.toml: {}", e);
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
+    state.transport_pool.rebuild(&config);
 
     info!(group_id = %group_id, "Deleted group");
     Ok(StatusCode::NO_CONTENT)
@@ -256,6 +242,8 @@ pub async fn update_group(
         groups: vec![],
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
 
     if req.id != group_id {
@@ -292,6 +280,7 @@ pub async fn update_group(
         error!(group_id = %group_id, "Failed to save boards.toml: {}", e);
         return Err(StatusCode::INTERNAL_SERVER_ERROR);
     }
+    state.transport_pool.rebuild(&config);
 
     let members_changed = old_members != new_members;
     let universe_changed = old_universe != new_universe;
@@ -1,19 +1,24 @@
 mod audio;
 mod boards;
 mod effects;
+mod etag;
 pub mod groups;
 mod history;
 mod patterns;
 mod presets;
 mod programs;
+mod sequences;
 mod settings;
+mod setlists;
+mod show;
+pub mod tempo;
 mod timing;
 
 use axum::{
     extract::{DefaultBodyLimit, State},
     http::StatusCode,
     response::sse::{Event, KeepAlive, Sse},
-    routing::{delete, get, post, put},
+    routing::{delete, get, patch, post, put},
     Json, Router,
 };
 use futures::Stream;
@@ -60,25 +65,71 @@ pub fn build_api_router(state: SharedState) -> Router {
         .route("/programs/:id", delete(programs::delete_program))
         .route("/programs/:id", put(programs::update_program))
         .route("/programs/:id/play", post(programs::play_program))
+        .route("/programs/:id/suggest-cues", post(programs::suggest_cues))
+        .route("/programs/:id/import-markers", post(programs::import_markers))
+        .route("/programs/:id/fseq", get(programs::render_fseq))
         .route("/programs/stop", post(programs::stop_program))
+        .route("/programs/pause", post(programs::pause_program))
+        .route("/programs/resume", post(programs::resume_program))
+        .route("/programs/seek", post(programs::seek_program))
+        .route("/programs/validate", get(programs::validate_programs))
+        .route("/programs/:id/validate", get(programs::validate_program))
+        .route("/programs/:id/revisions", get(programs::list_revisions))
+        .route("/programs/:id/revisions/:revision/diff", get(programs::diff_revision))
+        .route("/programs/:id/revisions/:revision/restore", post(programs::restore_revision))
+        .route("/programs/loops/release", post(programs::release_loop))
+        .route("/programs/loops/:name/arm", post(programs::arm_loop))
+        .route("/programs/cue-stack/go", post(programs::cue_stack_go))
+        .route("/programs/cue-stack/back", post(programs::cue_stack_back))
+        .route("/programs/cue-stack/jump/:index", post(programs::cue_stack_jump))
+        .route("/programs/cue-stack/position", get(programs::cue_stack_position))
+        .route("/setlists", get(setlists::list_setlists).post(setlists::save_setlist))
+        .route("/setlists/position", get(setlists::get_position))
+        .route("/setlists/next", post(setlists::next_entry))
+        .route("/setlists/previous", post(setlists::previous_entry))
+        .route("/setlists/jump/:index", post(setlists::jump_to_entry))
+        .route("/setlists/:id", get(setlists::get_setlist).put(setlists::update_setlist).delete(setlists::delete_setlist))
+        .route("/setlists/:id/play", post(setlists::play_setlist))
         .route("/presets", post(presets::save_preset).get(presets::list_presets))
         .route("/presets/:id", get(presets::get_preset).put(presets::update_preset).delete(presets::delete_preset))
         .route("/audio/:id", post(audio::upload_audio).get(audio::get_audio).delete(audio::delete_audio))
         .route("/audio/:id/peaks", get(audio::get_peaks).post(audio::save_peaks))
+        .route("/audio/:id/analyze", post(audio::analyze_audio))
         .route("/osc", post(settings::send_osc))
         .route("/settings/loopy-pro", get(settings::get_loopy_pro_settings).put(settings::update_loopy_pro_settings))
+        .route("/settings/marker-mappings", get(settings::get_marker_mappings).put(settings::update_marker_mappings))
+        .route("/effects", patch(effects::update_effect_params))
         .route("/effects/start", post(effects::start_effects_engine))
         .route("/effects/stop", post(effects::stop_effects_engine))
         .route("/effects/presets", get(effects::list_effect_presets))
+        .route("/patterns", patch(patterns::update_pattern_params))
         .route("/patterns/presets", get(patterns::list_pattern_presets))
         .route("/patterns/start", post(patterns::start_pattern))
         .route("/patterns/stop", post(patterns::stop_pattern))
+        .route("/tempo/bpm", post(tempo::set_bpm))
+        .route("/tempo/tap", post(tempo::tap))
         .route("/timing/snapshot", get(timing::get_timing_snapshot))
         .route("/timing/events", get(timing::get_timing_events).delete(timing::clear_timing_events))
         .route("/timing/reset", post(timing::reset_timing_metrics))
         .route("/timing/threshold", get(timing::get_timing_threshold).put(timing::update_timing_threshold))
         .route("/history", get(history::get_history).delete(history::clear_history))
         .route("/history/:id", get(history::get_session).delete(history::delete_session))
+        .route("/sequences", get(sequences::list_sequences))
+        .route(
+            "/sequences/presets",
+            get(sequences::get_sequence_presets).put(sequences::update_sequence_presets),
+        )
+        .route(
+            "/sequences/:name",
+            post(sequences::upload_sequence)
+                .delete(sequences::delete_sequence)
+                .layer(DefaultBodyLimit::max(1024 * 1024 * 1024)),
+        )
+        .route("/show/export", get(show::export_show))
+        .route(
+            "/show/import",
+            post(show::import_show).layer(DefaultBodyLimit::max(1024 * 1024 * 1024)),
+        )
         .layer(DefaultBodyLimit::max(50 * 1024 * 1024))
         .with_state(state)
 }
@@ -174,6 +225,8 @@ pub async fn list_boards(
         loopy_pro: crate::config::LoopyProConfig::default(),
         effect_presets: vec![],
         pattern_presets: vec![],
+        marker_mappings: vec![],
+        sequence_presets: vec![],
     });
 
     let groups: Vec<GroupResponse> = config
@@ -4,7 +4,8 @@ use axum::{extract::State, http::StatusCode, Json};
 use serde::{Deserialize, Serialize};
 use tracing::info;
 
-use crate::config::PatternType;
+use crate::config::{ColourMode, PatternEnvelope, PatternStepEffect, PatternType};
+use crate::effects::EffectParams;
 use crate::pattern::generate_sequence;
 use crate::pattern_engine::{BoardInfo, PatternCommand};
 use crate::types::SharedState;
@@ -14,6 +15,13 @@ pub struct PatternPresetResponse {
     pub name: String,
     pub pattern: String,
     pub color: [u8; 3],
+    pub colours: Vec<[u8; 3]>,
+    pub colour_mode: ColourMode,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub steps: Vec<Vec<String>>,
+    pub envelope: PatternEnvelope,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub step_effect: Option<PatternStepEffect>,
 }
 
 pub async fn list_pattern_presets(
@@ -25,6 +33,11 @@ pub async fn list_pattern_presets(
             name: p.name.clone(),
             pattern: format!("{:?}", p.pattern).to_lowercase(),
             color: p.colour,
+            colours: p.palette(),
+            colour_mode: p.colour_mode,
+            steps: p.steps.clone(),
+            envelope: p.envelope.clone(),
+            step_effect: p.step_effect.clone(),
         }
     }).collect();
     Json(presets)
@@ -76,6 +89,9 @@ pub async fn start_pattern(
     drop(online_ips);
     drop(cfg);
 
+    preset.validate(&group.members)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
     let online_members: Vec<String> = group.members.iter()
         .filter(|m| boards.contains_key(*m))
         .cloned()
@@ -87,17 +103,25 @@ pub async fn start_pattern(
 
     info!("Pattern: {}/{} boards online", online_members.len(), group.members.len());
 
-    let sequence = generate_sequence(&online_members, &preset.pattern, req.bpm, req.sync_rate);
+    let sequence = generate_sequence(&online_members, &preset.pattern, &preset.steps, req.bpm, req.sync_rate);
+
+    if sequence.steps.is_empty() {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "No online boards in pattern steps".to_string()));
+    }
 
     let is_random = preset.pattern == PatternType::Random;
     let is_ping_pong = preset.pattern == PatternType::PingPong;
 
     state.pattern_engine.send_command(PatternCommand::Start {
         sequence,
-        color: preset.colour,
+        palette: preset.palette(),
+        colour_mode: preset.colour_mode,
+        envelope: preset.envelope.clone(),
+        step_effect: preset.step_effect.clone(),
         boards,
         is_random,
         is_ping_pong,
+        phase: 0.0,
     }).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
     info!("Pattern started: {} @ {} BPM -> {}", req.preset, req.bpm, req.target);
@@ -112,3 +136,14 @@ pub async fn stop_pattern(
     info!("Pattern stopped");
     Ok(StatusCode::OK)
 }
+
+pub async fn update_pattern_params(
+    State(state): State<SharedState>,
+    Json(params): Json<EffectParams>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    params.validate().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    state.pattern_engine.send_command(PatternCommand::SetParams(params))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(StatusCode::OK)
+}
@@ -1,6 +1,13 @@
-use axum::{extract::{Path, State}, http::StatusCode, Json};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use tracing::{info, warn};
 
+use super::etag;
+
 use crate::types::{PresetState, SavePresetRequest, SharedState, WledPreset};
 
 pub async fn save_preset(
@@ -76,6 +83,7 @@ pub async fn save_preset(
         description: req.description,
         state: preset_state,
         created_at: chrono::Utc::now().to_rfc3339(),
+        version: 0,
     };
 
     presets.push(preset.clone());
@@ -145,7 +153,7 @@ pub async fn list_presets(
 pub async fn get_preset(
     State(state): State<SharedState>,
     Path(wled_slot): Path<u8>,
-) -> Result<Json<WledPreset>, (StatusCode, String)> {
+) -> Result<Response, (StatusCode, String)> {
     if !state.storage_paths.is_available() {
         return Err((
             StatusCode::SERVICE_UNAVAILABLE,
@@ -165,19 +173,22 @@ pub async fn get_preset(
         .find(|p| p.wled_slot == wled_slot)
         .ok_or((StatusCode::NOT_FOUND, format!("Preset at slot {} not found", wled_slot)))?;
 
-    Ok(Json(preset))
+    Ok(etag::tagged(StatusCode::OK, preset.version, preset))
 }
 
+/// Save an edit made against the version in `If-Match`
 pub async fn update_preset(
     State(state): State<SharedState>,
     Path(wled_slot): Path<u8>,
+    headers: HeaderMap,
     Json(req): Json<SavePresetRequest>,
-) -> Result<Json<WledPreset>, (StatusCode, String)> {
+) -> Result<Response, Response> {
     if !state.storage_paths.is_available() {
         return Err((
             StatusCode::SERVICE_UNAVAILABLE,
             "Storage not available".to_string(),
-        ));
+        )
+            .into_response());
     }
 
     let mut presets = WledPreset::load_all(&state.storage_paths.presets).map_err(|e| {
@@ -185,6 +196,7 @@ pub async fn update_preset(
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Failed to load presets for update of slot {}: {}", wled_slot, e),
         )
+            .into_response()
     })?;
 
     let preset = presets
@@ -193,8 +205,14 @@ pub async fn update_preset(
         .ok_or((
             StatusCode::NOT_FOUND,
             format!("Preset at slot {} not found", wled_slot),
-        ))?;
+        )
+            .into_response())?;
+
+    if let Some(refusal) = etag::check_if_match(&headers, preset.version, &*preset) {
+        return Err(refusal);
+    }
 
+    preset.version += 1;
     preset.name = req.name;
     preset.description = req.description;
     if let Some(state) = req.state {
@@ -208,11 +226,12 @@ pub async fn update_preset(
             StatusCode::INTERNAL_SERVER_ERROR,
             format!("Failed to save presets after updating slot {}: {}", wled_slot, e),
         )
+            .into_response()
     })?;
 
     info!("Updated preset '{}' at slot {}", updated_preset.name, wled_slot);
 
-    Ok(Json(updated_preset))
+    Ok(etag::tagged(StatusCode::OK, updated_preset.version, updated_preset))
 }
 
 pub async fn delete_preset(
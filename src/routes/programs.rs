@@ -1,25 +1,60 @@
-use axum::{extract::{Path, State}, http::StatusCode, Json};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
 use tracing::info;
 
+use super::etag;
+
+use crate::audio::AudioFile;
+use crate::audio_analysis;
+use crate::cue_suggestion::{self, CueSuggestion};
+use crate::fseq;
+use crate::marker_import::{self, MarkerFormat, MarkerImport};
 use crate::program;
+use crate::program_check::{self, CheckContext, ProgramReport};
+use crate::program_engine::CueStackPosition;
+use crate::program_revisions::{self, ProgramDiff, RevisionInfo};
+use crate::sse::SseEvent;
 use crate::types::SharedState;
 
+/// Save an edit made against the version in `If-Match`
 pub async fn update_program(
     State(state): State<SharedState>,
     Path(id): Path<String>,
-    Json(program): Json<program::Program>,
-) -> Result<StatusCode, (StatusCode, String)> {
+    headers: HeaderMap,
+    Json(mut program): Json<program::Program>,
+) -> Result<Response, Response> {
     if !state.storage_paths.is_available() {
         return Err((
             StatusCode::SERVICE_UNAVAILABLE,
             "Storage not available".to_string(),
-        ));
+        )
+            .into_response());
     }
 
     if id != program.id {
-        return Err((StatusCode::BAD_REQUEST, "ID mismatch".to_string()));
+        return Err((StatusCode::BAD_REQUEST, "ID mismatch".to_string()).into_response());
     }
 
+    program
+        .validate_timing()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e).into_response())?;
+
+    // Held until the new version is in place so two saves can't both pass the check
+    let mut programs = state.programs.write().await;
+    let previous = programs.get(&id).cloned();
+    if let Some(ref current) = previous {
+        if let Some(refusal) = etag::check_if_match(&headers, current.version, current) {
+            return Err(refusal);
+        }
+    }
+    program.version = next_version(previous.as_ref());
+
+    record_revision(&state, &program, previous.as_ref(), None);
+
     program
         .save_to_file(&state.storage_paths.programs)
         .map_err(|e| {
@@ -32,26 +67,37 @@ pub async fn update_program(
                     e
                 ),
             )
+                .into_response()
         })?;
 
-    {
-        let mut programs = state.programs.write().await;
-        programs.insert(program.id.clone(), program);
-    }
+    programs.insert(program.id.clone(), program.clone());
+    drop(programs);
 
-    Ok(StatusCode::OK)
+    announce_update(&state, &program);
+    Ok(etag::tagged(StatusCode::OK, program.version, program))
+}
+
+pub(super) fn next_version(previous: Option<&program::Program>) -> u64 {
+    previous.map_or(0, |p| p.version + 1)
+}
+
+/// Tell other editors to refresh `program`
+pub(super) fn announce_update(state: &SharedState, program: &program::Program) {
+    let _ = state.broadcast_tx.send(SseEvent::ProgramUpdated {
+        program_id: program.id.clone(),
+        version: program.version,
+    });
 }
 
 pub async fn get_program(
     State(state): State<SharedState>,
     Path(id): Path<String>,
-) -> Result<Json<program::Program>, (StatusCode, String)> {
+) -> Result<Response, (StatusCode, String)> {
     let programs = state.programs.read().await;
     let program = programs
         .get(&id)
-        .cloned()
         .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Program {} not found", id)))?;
-    Ok(Json(program))
+    Ok(etag::tagged(StatusCode::OK, program.version, program))
 }
 
 pub async fn delete_program(
@@ -86,12 +132,13 @@ pub async fn delete_program(
         programs.remove(&id);
     }
 
+    let _ = state.broadcast_tx.send(SseEvent::ProgramDeleted { program_id: id });
     Ok(StatusCode::NO_CONTENT)
 }
 
 pub async fn save_program(
     State(state): State<SharedState>,
-    Json(program): Json<program::Program>,
+    Json(mut program): Json<program::Program>,
 ) -> Result<StatusCode, (StatusCode, String)> {
     if !state.storage_paths.is_available() {
         return Err((
@@ -100,14 +147,29 @@ pub async fn save_program(
         ));
     }
 
+    program
+        .validate_timing()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let mut programs = state.programs.write().await;
+    // Overwrites go through PUT so they carry the version they replace
+    if programs.contains_key(&program.id) {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Program {} already exists; update it with PUT /programs/{}", program.id, program.id),
+        ));
+    }
+    program.version = next_version(None);
+    record_revision(&state, &program, None, None);
+
     program
         .save_to_file(&state.storage_paths.programs)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
 
-    {
-        let mut programs = state.programs.write().await;
-        programs.insert(program.id.clone(), program);
-    }
+    programs.insert(program.id.clone(), program.clone());
+    drop(programs);
+
+    announce_update(&state, &program);
 
     Ok(StatusCode::CREATED)
 }
@@ -167,3 +229,451 @@ pub async fn stop_program(
         }
     }
 }
+
+pub async fn pause_program(
+    State(state): State<SharedState>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .program_engine
+        .pause()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    info!("⏸️ Paused program playback");
+    Ok(StatusCode::OK)
+}
+
+pub async fn resume_program(
+    State(state): State<SharedState>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .program_engine
+        .resume()
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    info!("▶️ Resumed program playback");
+    Ok(StatusCode::OK)
+}
+
+#[derive(serde::Deserialize)]
+pub struct SeekProgramRequest {
+    position: f64,
+}
+
+pub async fn seek_program(
+    State(state): State<SharedState>,
+    axum::extract::Query(params): axum::extract::Query<SeekProgramRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if !params.position.is_finite() || params.position < 0.0 {
+        return Err((StatusCode::BAD_REQUEST, "Seek position must be 0 or more seconds".to_string()));
+    }
+
+    info!("⏩ Seeking program playback to {}s", params.position);
+
+    state
+        .program_engine
+        .seek(params.position)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Keep `program` in its revision history; a failure here doesn't block the save
+pub(super) fn record_revision(state: &SharedState, program: &program::Program, previous: Option<&program::Program>, note: Option<&str>) {
+    if let Err(e) = program_revisions::record(&state.storage_paths.revisions, program, previous, note) {
+        eprintln!("⚠️ Failed to record revision of program {}: {}", program.id, e);
+    }
+}
+
+/// Revisions of a program, newest first
+pub async fn list_revisions(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Json<Vec<RevisionInfo>>, (StatusCode, String)> {
+    let revisions = program_revisions::list(&state.storage_paths.revisions, &id)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(revisions.iter().rev().map(RevisionInfo::from).collect()))
+}
+
+/// What changed from a revision to the current version
+pub async fn diff_revision(
+    State(state): State<SharedState>,
+    Path((id, revision)): Path<(String, u64)>,
+) -> Result<Json<ProgramDiff>, (StatusCode, String)> {
+    let stored = load_revision(&state, &id, revision)?;
+    let programs = state.programs.read().await;
+    let current = programs
+        .get(&id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Program {} not found", id)))?;
+    Ok(Json(ProgramDiff::between(&stored.program, current)))
+}
+
+/// Make a revision the current version again; the restore is itself a new revision
+pub async fn restore_revision(
+    State(state): State<SharedState>,
+    Path((id, revision)): Path<(String, u64)>,
+) -> Result<Response, (StatusCode, String)> {
+    if !state.storage_paths.is_available() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Storage not available".to_string(),
+        ));
+    }
+
+    let mut program = load_revision(&state, &id, revision)?.program;
+    let mut programs = state.programs.write().await;
+    let previous = programs.get(&id).cloned();
+    program.version = next_version(previous.as_ref());
+    record_revision(&state, &program, previous.as_ref(), Some(&format!("Restored revision {}", revision)));
+
+    program
+        .save_to_file(&state.storage_paths.programs)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    info!("↩️ Restored program {} to revision {}", id, revision);
+
+    programs.insert(program.id.clone(), program.clone());
+    drop(programs);
+
+    announce_update(&state, &program);
+    Ok(etag::tagged(StatusCode::OK, program.version, program))
+}
+
+fn load_revision(
+    state: &SharedState,
+    id: &str,
+    revision: u64,
+) -> Result<program_revisions::Revision, (StatusCode, String)> {
+    program_revisions::load(&state.storage_paths.revisions, id, revision)
+        .map_err(|_| (StatusCode::NOT_FOUND, format!("Program {} has no revision {}", id, revision)))
+}
+
+/// Pre-show check of every program
+pub async fn validate_programs(
+    State(state): State<SharedState>,
+) -> Json<Vec<ProgramReport>> {
+    let config = state.config.lock().await;
+    let online_ips = state.connected_ips.read().await;
+    let programs = state.programs.read().await;
+    let ctx = CheckContext {
+        config: &config,
+        online_ips: &online_ips,
+        programs: &programs,
+        audio_path: &state.storage_paths.audio,
+        sequences: &state.sequences,
+    };
+
+    let mut list: Vec<&program::Program> = programs.values().collect();
+    list.sort_by_key(|p| p.display_order);
+    Json(list.into_iter().map(|p| program_check::check_program(p, &ctx)).collect())
+}
+
+pub async fn validate_program(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Json<ProgramReport>, (StatusCode, String)> {
+    let config = state.config.lock().await;
+    let online_ips = state.connected_ips.read().await;
+    let programs = state.programs.read().await;
+    let program = programs
+        .get(&id)
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Program {} not found", id)))?;
+    let ctx = CheckContext {
+        config: &config,
+        online_ips: &online_ips,
+        programs: &programs,
+        audio_path: &state.storage_paths.audio,
+        sequences: &state.sequences,
+    };
+
+    Ok(Json(program_check::check_program(program, &ctx)))
+}
+
+pub async fn arm_loop(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .program_engine
+        .arm_loop(&name)
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, e))?;
+
+    info!("🔁 Armed loop region {}", name);
+    Ok(StatusCode::OK)
+}
+
+pub async fn release_loop(
+    State(state): State<SharedState>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    state
+        .program_engine
+        .release_loop()
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, e))?;
+
+    info!("🔁 Released loop region");
+    Ok(StatusCode::OK)
+}
+
+pub async fn cue_stack_go(
+    State(state): State<SharedState>,
+) -> Result<Json<CueStackPosition>, (StatusCode, String)> {
+    let position = state
+        .program_engine
+        .go()
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, e))?;
+
+    info!("🎬 GO: at '{}'", position.label.as_deref().unwrap_or("top of stack"));
+    Ok(Json(position))
+}
+
+pub async fn cue_stack_back(
+    State(state): State<SharedState>,
+) -> Result<Json<CueStackPosition>, (StatusCode, String)> {
+    let position = state
+        .program_engine
+        .back()
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, e))?;
+
+    info!("🎬 BACK: at '{}'", position.label.as_deref().unwrap_or("top of stack"));
+    Ok(Json(position))
+}
+
+pub async fn cue_stack_jump(
+    State(state): State<SharedState>,
+    Path(index): Path<usize>,
+) -> Result<Json<CueStackPosition>, (StatusCode, String)> {
+    let position = state
+        .program_engine
+        .jump_to_cue(index)
+        .await
+        .map_err(|e| (StatusCode::CONFLICT, e))?;
+
+    info!("🎬 Jumped to cue {} of {}", index + 1, position.count);
+    Ok(Json(position))
+}
+
+pub async fn cue_stack_position(
+    State(state): State<SharedState>,
+) -> Result<Json<CueStackPosition>, (StatusCode, String)> {
+    state
+        .program_engine
+        .cue_stack_position()
+        .await
+        .map(Json)
+        .ok_or_else(|| (StatusCode::CONFLICT, "No cue stack is playing".to_string()))
+}
+
+#[derive(serde::Deserialize)]
+pub struct SuggestCuesRequest {
+    #[serde(default)]
+    target: Option<String>,
+    #[serde(default = "default_min_section_secs")]
+    min_section_secs: f64,
+}
+
+fn default_min_section_secs() -> f64 {
+    8.0
+}
+
+pub async fn suggest_cues(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<SuggestCuesRequest>,
+) -> Result<Json<CueSuggestion>, (StatusCode, String)> {
+    let program = {
+        let programs = state.programs.read().await;
+        programs.get(&id).cloned()
+    };
+
+    let program = program.ok_or_else(|| (StatusCode::NOT_FOUND, format!("Program {} not found", id)))?;
+
+    let audio_file = program
+        .audio_file
+        .clone()
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, format!("Program {} has no audio file", id)))?;
+
+    let audio_path = AudioFile::resolve_path(&audio_file, &state.storage_paths.audio)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    let min_section_secs = params.min_section_secs.max(1.0);
+    let sections = tokio::task::spawn_blocking(move || {
+        audio_analysis::decode_file(&audio_path)
+            .map(|audio| audio_analysis::detect_sections(&audio, min_section_secs))
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, format!("Failed to decode {}: {}", audio_file, e)))?;
+
+    let cfg = state.config.lock().await;
+
+    let target = params
+        .target
+        .or_else(|| program.default_target_board.clone())
+        .or_else(|| cfg.groups.first().map(|g| g.id.clone()))
+        .or_else(|| cfg.boards.first().map(|b| b.id.clone()))
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "No boards or groups configured".to_string()))?;
+
+    let bpm = program.bpm.map(|b| b as f64).or(program.suggested_bpm);
+    let grid_offset = program.grid_offset.or(program.suggested_grid_offset).unwrap_or(0.0);
+
+    let suggestion = cue_suggestion::suggest_cues(&sections, &cfg, &target, bpm, grid_offset);
+
+    info!(
+        "💡 Suggested {} cues over {} sections for program {}",
+        suggestion.cues.len(),
+        suggestion.sections.len(),
+        program.id
+    );
+
+    Ok(Json(suggestion))
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImportMarkersRequest {
+    format: MarkerFormat,
+    #[serde(default)]
+    target: Option<String>,
+    /// Drop the program's existing cues instead of adding to them
+    #[serde(default)]
+    replace: bool,
+}
+
+/// Turn a DAW marker export into cues using the configured marker mappings
+pub async fn import_markers(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<ImportMarkersRequest>,
+    body: axum::body::Bytes,
+) -> Result<Response, (StatusCode, String)> {
+    if !state.storage_paths.is_available() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Storage not available".to_string(),
+        ));
+    }
+
+    let markers = marker_import::parse(params.format, &body).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let cfg = state.config.lock().await;
+    if cfg.marker_mappings.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "No marker mappings configured".to_string()));
+    }
+    let mut programs = state.programs.write().await;
+    let previous = programs
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Program {} not found", id)))?;
+
+    let target = params
+        .target
+        .or_else(|| previous.default_target_board.clone())
+        .or_else(|| cfg.groups.first().map(|g| g.id.clone()))
+        .or_else(|| cfg.boards.first().map(|b| b.id.clone()))
+        .ok_or_else(|| (StatusCode::BAD_REQUEST, "No boards or groups configured".to_string()))?;
+    let tempo_map = previous.build_tempo_map().map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    let MarkerImport { cues, unmatched } = marker_import::to_cues(&markers, &cfg.marker_mappings, &target, &tempo_map)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    drop(cfg);
+
+    let mut program = previous.clone();
+    if params.replace {
+        program.cues.clear();
+    }
+    let added = cues.len();
+    program.cues.extend(cues);
+    program.cues.sort_by(|a, b| a.time.total_cmp(&b.time));
+    program
+        .validate_timing()
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    program.version = next_version(Some(&previous));
+    record_revision(&state, &program, Some(&previous), Some(&format!("Imported {} cues from markers", added)));
+    program
+        .save_to_file(&state.storage_paths.programs)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    programs.insert(program.id.clone(), program.clone());
+    drop(programs);
+
+    info!(
+        "📍 Imported {} cues into program {} ({} markers unmatched)",
+        added,
+        program.id,
+        unmatched.len()
+    );
+
+    announce_update(&state, &program);
+    Ok(etag::tagged(
+        StatusCode::OK,
+        program.version,
+        serde_json::json!({ "program": program, "added": added, "unmatched": unmatched }),
+    ))
+}
+
+#[derive(serde::Deserialize)]
+pub struct RenderFseqRequest {
+    #[serde(default = "default_fps")]
+    fps: u32,
+    /// Seconds to render; the program's audio length when omitted
+    #[serde(default)]
+    duration: Option<f64>,
+}
+
+fn default_fps() -> u32 {
+    fseq::DEFAULT_FPS
+}
+
+/// Render a program to an FSEQ file for standalone playback on a Falcon Player
+pub async fn render_fseq(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<RenderFseqRequest>,
+) -> Result<Response, (StatusCode, String)> {
+    let program = {
+        let programs = state.programs.read().await;
+        programs.get(&id).cloned()
+    };
+    let program = program.ok_or_else(|| (StatusCode::NOT_FOUND, format!("Program {} not found", id)))?;
+    let config = state.config.lock().await.clone();
+
+    let sequences = state.sequences.clone();
+
+    let started = std::time::Instant::now();
+    let sequence = tokio::task::spawn_blocking(move || {
+        fseq::render(&program, &config, &sequences, params.fps, params.duration)
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let bytes = sequence
+        .to_fseq()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    info!(
+        "🎞️ Rendered program {} to FSEQ: {} frames x {} universes in {:.1}s",
+        id,
+        sequence.frame_count,
+        sequence.universes.len(),
+        started.elapsed().as_secs_f64()
+    );
+
+    Ok((
+        [
+            (axum::http::header::CONTENT_TYPE, "application/octet-stream".to_string()),
+            (
+                axum::http::header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.fseq\"", id),
+            ),
+        ],
+        bytes,
+    )
+        .into_response())
+}
@@ -0,0 +1,114 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::StatusCode,
+    Json,
+};
+use tracing::{error, info};
+
+use crate::config::SequencePreset;
+use crate::fseq::SequenceInfo;
+use crate::types::SharedState;
+
+pub async fn list_sequences(State(state): State<SharedState>) -> Json<Vec<SequenceInfo>> {
+    let sequences = state.sequences.clone();
+    let list = tokio::task::spawn_blocking(move || sequences.list())
+        .await
+        .unwrap_or_default();
+    Json(list)
+}
+
+/// Store a raw FSEQ file next to the audio; the body must parse as FSEQ v1 or v2
+pub async fn upload_sequence(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+    body: Bytes,
+) -> Result<Json<SequenceInfo>, (StatusCode, String)> {
+    if !state.storage_paths.is_available() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Storage not available".to_string(),
+        ));
+    }
+
+    let sequences = state.sequences.clone();
+    let upload = name.clone();
+    let sequence = tokio::task::spawn_blocking(move || sequences.save(&upload, &body))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    info!(
+        "🎞️ Uploaded sequence {}: {} frames of {} channels at {}ms",
+        name, sequence.frame_count, sequence.channel_count, sequence.step_ms
+    );
+
+    Ok(Json(sequence))
+}
+
+pub async fn delete_sequence(
+    State(state): State<SharedState>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if !state.storage_paths.is_available() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Storage not available".to_string(),
+        ));
+    }
+
+    let config = state.config.lock().await;
+    if let Some(preset) = config.sequence_presets.iter().find(|p| p.file == name) {
+        return Err((
+            StatusCode::CONFLICT,
+            format!("Sequence preset '{}' still uses {}", preset.name, name),
+        ));
+    }
+    drop(config);
+
+    state.sequences.delete(&name).map_err(|e| {
+        error!("Failed to delete sequence file '{}': {}", name, e);
+        (StatusCode::NOT_FOUND, e)
+    })?;
+
+    info!("Deleted sequence file: {}", name);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_sequence_presets(
+    State(state): State<SharedState>,
+) -> Json<Vec<SequencePreset>> {
+    let config = state.config.lock().await;
+    Json(config.sequence_presets.clone())
+}
+
+/// Replace the sequence presets; each must name an uploaded file and a frame range inside it
+pub async fn update_sequence_presets(
+    State(state): State<SharedState>,
+    Json(payload): Json<Vec<SequencePreset>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    let mut config = state.config.lock().await;
+    for preset in &payload {
+        let taken = config.effect_presets.iter().any(|p| p.name == preset.name)
+            || config.pattern_presets.iter().any(|p| p.name == preset.name);
+        if taken {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("'{}' is already an effect or pattern preset", preset.name),
+            ));
+        }
+        state
+            .sequences
+            .clip(preset)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    config.sequence_presets = payload;
+    config.save().map_err(|e| {
+        error!("Failed to save sequence presets: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+    info!("Sequence presets updated: {} entries", config.sequence_presets.len());
+    Ok(StatusCode::OK)
+}
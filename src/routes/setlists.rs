@@ -0,0 +1,210 @@
+use axum::{extract::{Path, State}, http::StatusCode, Json};
+use tracing::info;
+
+use crate::program_engine::SetlistPosition;
+use crate::setlist::Setlist;
+use crate::types::SharedState;
+
+pub async fn list_setlists(
+    State(state): State<SharedState>,
+) -> Json<Vec<Setlist>> {
+    let setlists = state.setlists.read().await;
+    let mut list: Vec<Setlist> = setlists.values().cloned().collect();
+    list.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(list)
+}
+
+pub async fn get_setlist(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<Json<Setlist>, (StatusCode, String)> {
+    let setlists = state.setlists.read().await;
+    let setlist = setlists
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Setlist {} not found", id)))?;
+    Ok(Json(setlist))
+}
+
+pub async fn save_setlist(
+    State(state): State<SharedState>,
+    Json(setlist): Json<Setlist>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    store_setlist(&state, setlist).await?;
+    Ok(StatusCode::CREATED)
+}
+
+pub async fn update_setlist(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    Json(setlist): Json<Setlist>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if id != setlist.id {
+        return Err((StatusCode::BAD_REQUEST, "ID mismatch".to_string()));
+    }
+    store_setlist(&state, setlist).await?;
+    Ok(StatusCode::OK)
+}
+
+async fn store_setlist(state: &SharedState, setlist: Setlist) -> Result<(), (StatusCode, String)> {
+    if !state.storage_paths.is_available() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Storage not available".to_string(),
+        ));
+    }
+
+    {
+        let programs = state.programs.read().await;
+        setlist
+            .validate(&programs)
+            .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+
+    setlist
+        .save_to_file(&state.storage_paths.setlists)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to save setlist '{}': {}", setlist.id, e),
+            )
+        })?;
+
+    let mut setlists = state.setlists.write().await;
+    setlists.insert(setlist.id.clone(), setlist);
+    Ok(())
+}
+
+pub async fn delete_setlist(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if !state.storage_paths.is_available() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Storage not available".to_string(),
+        ));
+    }
+
+    let setlist = {
+        let setlists = state.setlists.read().await;
+        setlists.get(&id).cloned()
+    };
+
+    let setlist = setlist.ok_or_else(|| (StatusCode::NOT_FOUND, format!("Setlist {} not found", id)))?;
+
+    setlist
+        .delete(&state.storage_paths.setlists)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("Failed to delete setlist '{}': {}", id, e),
+            )
+        })?;
+
+    {
+        let mut setlists = state.setlists.write().await;
+        setlists.remove(&id);
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(serde::Deserialize)]
+pub struct PlaySetlistRequest {
+    #[serde(default)]
+    index: usize,
+}
+
+pub async fn play_setlist(
+    State(state): State<SharedState>,
+    Path(id): Path<String>,
+    axum::extract::Query(params): axum::extract::Query<PlaySetlistRequest>,
+) -> Result<Json<SetlistPosition>, (StatusCode, String)> {
+    play_entry(&state, &id, params.index).await
+}
+
+pub async fn next_entry(
+    State(state): State<SharedState>,
+) -> Result<Json<SetlistPosition>, (StatusCode, String)> {
+    let position = current_position(&state).await?;
+    if position.index + 1 >= position.count {
+        return Err((StatusCode::CONFLICT, "Already at the last setlist entry".to_string()));
+    }
+    play_entry(&state, &position.setlist_id, position.index + 1).await
+}
+
+pub async fn previous_entry(
+    State(state): State<SharedState>,
+) -> Result<Json<SetlistPosition>, (StatusCode, String)> {
+    let position = current_position(&state).await?;
+    if position.index == 0 {
+        return Err((StatusCode::CONFLICT, "Already at the first setlist entry".to_string()));
+    }
+    play_entry(&state, &position.setlist_id, position.index - 1).await
+}
+
+pub async fn jump_to_entry(
+    State(state): State<SharedState>,
+    Path(index): Path<usize>,
+) -> Result<Json<SetlistPosition>, (StatusCode, String)> {
+    let position = current_position(&state).await?;
+    play_entry(&state, &position.setlist_id, index).await
+}
+
+pub async fn get_position(
+    State(state): State<SharedState>,
+) -> Result<Json<SetlistPosition>, (StatusCode, String)> {
+    current_position(&state).await.map(Json)
+}
+
+async fn current_position(state: &SharedState) -> Result<SetlistPosition, (StatusCode, String)> {
+    state
+        .program_engine
+        .setlist_position()
+        .await
+        .ok_or_else(|| (StatusCode::CONFLICT, "No setlist is playing".to_string()))
+}
+
+async fn play_entry(
+    state: &SharedState,
+    setlist_id: &str,
+    index: usize,
+) -> Result<Json<SetlistPosition>, (StatusCode, String)> {
+    let setlist = {
+        let setlists = state.setlists.read().await;
+        setlists.get(setlist_id).cloned()
+    };
+    let setlist = setlist.ok_or_else(|| (StatusCode::NOT_FOUND, format!("Setlist {} not found", setlist_id)))?;
+
+    let entry = setlist.entries.get(index).ok_or_else(|| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Setlist {} has {} entries, no entry {}", setlist.id, setlist.entries.len(), index),
+        )
+    })?;
+
+    if !state.programs.read().await.contains_key(&entry.program_id) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Program {} not found", entry.program_id),
+        ));
+    }
+
+    let position = SetlistPosition {
+        setlist_id: setlist.id.clone(),
+        index,
+        program_id: entry.program_id.clone(),
+        count: setlist.entries.len(),
+    };
+
+    info!("📜 Playing setlist {} entry {} ({})", setlist.id, index, entry.program_id);
+
+    state
+        .program_engine
+        .play_setlist(setlist, index)
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e))?;
+
+    Ok(Json(position))
+}
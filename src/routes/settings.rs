@@ -31,11 +31,16 @@ pub async fn send_osc(Json(payload): Json<OscRequest>) -> Result<StatusCode, Sta
     Ok(StatusCode::OK)
 }
 
-pub fn send_osc_sync(ip: &str, port: u16, address: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+pub fn send_osc_sync(
+    ip: &str,
+    port: u16,
+    address: &str,
+    args: Vec<rosc::OscType>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let socket = UdpSocket::bind("0.0.0.0:0")?;
     let packet = rosc::encoder::encode(&rosc::OscPacket::Message(rosc::OscMessage {
         addr: address.to_string(),
-        args: vec![],
+        args,
     }))?;
     let target = format!("{}:{}", ip, port);
     socket.send_to(&packet, &target)?;
@@ -63,3 +68,31 @@ pub async fn update_loopy_pro_settings(
     info!("Loopy Pro settings updated: {}:{}", config.loopy_pro.ip, config.loopy_pro.port);
     Ok(StatusCode::OK)
 }
+
+pub async fn get_marker_mappings(
+    State(state): State<SharedState>,
+) -> Json<Vec<config::MarkerMapping>> {
+    let config = state.config.lock().await;
+    Json(config.marker_mappings.clone())
+}
+
+pub async fn update_marker_mappings(
+    State(state): State<SharedState>,
+    Json(payload): Json<Vec<config::MarkerMapping>>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if let Some(bad) = payload.iter().find(|m| m.marker.is_none() && m.note.is_none()) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("Mapping for preset '{}' needs a marker name or a note", bad.preset_name),
+        ));
+    }
+
+    let mut config = state.config.lock().await;
+    config.marker_mappings = payload;
+    config.save().map_err(|e| {
+        error!("Failed to save marker mappings: {}", e);
+        (StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+    })?;
+    info!("Marker mappings updated: {} entries", config.marker_mappings.len());
+    Ok(StatusCode::OK)
+}
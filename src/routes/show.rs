@@ -0,0 +1,210 @@
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::collections::HashMap;
+use std::fs;
+use tracing::info;
+
+use super::programs::{announce_update, next_version, record_revision};
+
+use crate::show_bundle::{Bundle, Existing, ImportReport, OnConflict};
+use crate::types::{SharedState, WledPreset};
+
+#[derive(serde::Deserialize)]
+pub struct ExportRequest {
+    /// Comma-separated program ids; every program when omitted
+    programs: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct ImportRequest {
+    #[serde(default)]
+    on_conflict: OnConflict,
+    /// Comma-separated `old:new` board or group renames, e.g. `stage-left:left,wings:sides`
+    remap: Option<String>,
+}
+
+/// Zip up programs with their audio, peaks and every preset their cues use
+pub async fn export_show(
+    State(state): State<SharedState>,
+    Query(params): Query<ExportRequest>,
+) -> Result<Response, (StatusCode, String)> {
+    let wled_presets = WledPreset::load_all(&state.storage_paths.presets)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let config = state.config.lock().await;
+    let programs = state.programs.read().await;
+
+    let selected = match params.programs.as_deref().filter(|p| !p.is_empty()) {
+        Some(ids) => ids
+            .split(',')
+            .map(|id| {
+                programs
+                    .get(id.trim())
+                    .cloned()
+                    .ok_or_else(|| (StatusCode::NOT_FOUND, format!("Program {} not found", id.trim())))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => {
+            let mut all: Vec<_> = programs.values().cloned().collect();
+            all.sort_by_key(|p| p.display_order);
+            all
+        }
+    };
+
+    let bundle = Bundle::collect(selected, &config, &wled_presets, &state.storage_paths.audio)
+        .map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e.to_string()))?;
+    drop(programs);
+    drop(config);
+
+    let bytes = bundle
+        .to_zip()
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+
+    info!(
+        "📦 Exported show bundle: {} programs, {} audio files ({} bytes)",
+        bundle.programs.len(),
+        bundle.audio.len(),
+        bytes.len()
+    );
+
+    let filename = format!("show-{}.zip", chrono::Utc::now().format("%Y%m%d-%H%M%S"));
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+        ],
+        bytes,
+    )
+        .into_response())
+}
+
+/// Unpack a show bundle, settling clashes per `on_conflict`
+pub async fn import_show(
+    State(state): State<SharedState>,
+    Query(params): Query<ImportRequest>,
+    body: Bytes,
+) -> Result<Json<ImportReport>, (StatusCode, String)> {
+    if !state.storage_paths.is_available() {
+        return Err((
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Storage not available".to_string(),
+        ));
+    }
+
+    let remap = parse_remap(params.remap.as_deref().unwrap_or(""))?;
+    let mut bundle = tokio::task::spawn_blocking(move || Bundle::from_zip(&body).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?
+        .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid show bundle: {}", e)))?;
+    bundle.remap_targets(&remap);
+
+    let internal = |e: Box<dyn std::error::Error>| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+
+    // Settle clashes and write the audio against a snapshot, so the disk work
+    // doesn't hold the config and program locks
+    let known_config = state.config.lock().await.clone();
+    let known_programs = state.programs.read().await.clone();
+    let wled_presets = WledPreset::load_all(&state.storage_paths.presets).map_err(internal)?;
+    let audio_path = state.storage_paths.audio.clone();
+    let on_conflict = params.on_conflict;
+    let (mut bundle, mut report, mut wled_presets, known_programs) = tokio::task::spawn_blocking(move || {
+        let existing = Existing {
+            programs: &known_programs,
+            config: &known_config,
+            wled_presets: &wled_presets,
+            audio_path: &audio_path,
+        };
+        let report = bundle
+            .resolve(&existing, on_conflict)
+            .map_err(|e| (StatusCode::CONFLICT, e.to_string()))?;
+
+        let internal = |e: std::io::Error| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        fs::create_dir_all(&audio_path).map_err(internal)?;
+        for entry in &bundle.audio {
+            fs::write(audio_path.join(&entry.name), &entry.data).map_err(internal)?;
+            if let Some(ref peaks) = entry.peaks {
+                let peaks_path = audio_path.join(format!("{}.peaks.json", entry.name));
+                fs::write(peaks_path, peaks).map_err(internal)?;
+            }
+        }
+        Ok::<_, (StatusCode, String)>((bundle, report, wled_presets, known_programs))
+    })
+    .await
+    .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    if !bundle.effect_presets.is_empty() || !bundle.pattern_presets.is_empty() || !bundle.sequence_presets.is_empty() {
+        let mut config = state.config.lock().await;
+        for preset in bundle.effect_presets.drain(..) {
+            config.effect_presets.retain(|p| p.name != preset.name);
+            config.effect_presets.push(preset);
+        }
+        for preset in bundle.pattern_presets.drain(..) {
+            config.pattern_presets.retain(|p| p.name != preset.name);
+            config.pattern_presets.push(preset);
+        }
+        for preset in bundle.sequence_presets.drain(..) {
+            config.sequence_presets.retain(|p| p.name != preset.name);
+            config.sequence_presets.push(preset);
+        }
+        config.save().map_err(internal)?;
+    }
+
+    if !bundle.wled_presets.is_empty() {
+        for preset in bundle.wled_presets.drain(..) {
+            wled_presets.retain(|p| p.id != preset.id);
+            wled_presets.push(preset);
+        }
+        WledPreset::save_all(&wled_presets, &state.storage_paths.presets).map_err(internal)?;
+    }
+
+    let mut programs = state.programs.write().await;
+    let mut imported = Vec::new();
+    for mut program in bundle.programs.drain(..) {
+        let previous = programs.get(&program.id).cloned();
+        if previous.is_some() && !known_programs.contains_key(&program.id) && on_conflict != OnConflict::Replace {
+            // Created while the bundle was being unpacked
+            report.programs.retain(|id| *id != program.id);
+            report.skipped_programs.push(program.id);
+            continue;
+        }
+        program.version = next_version(previous.as_ref());
+        record_revision(&state, &program, previous.as_ref(), Some("Imported from show bundle"));
+        program
+            .save_to_file(&state.storage_paths.programs)
+            .map_err(internal)?;
+        programs.insert(program.id.clone(), program.clone());
+        imported.push(program);
+    }
+    drop(programs);
+
+    for program in &imported {
+        announce_update(&state, program);
+    }
+
+    info!(
+        "📦 Imported show bundle: {} programs, {} skipped, {} audio files",
+        report.programs.len(),
+        report.skipped_programs.len(),
+        report.audio_files.len()
+    );
+
+    Ok(Json(report))
+}
+
+fn parse_remap(remap: &str) -> Result<HashMap<String, String>, (StatusCode, String)> {
+    remap
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once(':') {
+            Some((from, to)) if !from.trim().is_empty() && !to.trim().is_empty() => {
+                Ok((from.trim().to_string(), to.trim().to_string()))
+            }
+            _ => Err((StatusCode::BAD_REQUEST, format!("Invalid remap '{}', expected old:new", pair))),
+        })
+        .collect()
+}
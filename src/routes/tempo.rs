@@ -0,0 +1,59 @@
+use std::time::Instant;
+
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::effects_engine::EngineCommand;
+use crate::pattern_engine::PatternCommand;
+use crate::types::SharedState;
+
+#[derive(Deserialize)]
+pub struct SetBpmRequest {
+    pub bpm: f64,
+}
+
+pub async fn set_bpm(
+    State(state): State<SharedState>,
+    Json(req): Json<SetBpmRequest>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    if !(req.bpm > 0.0 && req.bpm <= 999.0) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid BPM: {}", req.bpm)));
+    }
+
+    send_set_bpm(&state, req.bpm)?;
+
+    info!("Tempo set to {} BPM", req.bpm);
+    Ok(StatusCode::OK)
+}
+
+pub async fn tap(
+    State(state): State<SharedState>,
+) -> Result<StatusCode, (StatusCode, String)> {
+    send_tap(&state, Instant::now())?;
+    Ok(StatusCode::OK)
+}
+
+pub fn send_set_bpm(state: &SharedState, bpm: f64) -> Result<(), (StatusCode, String)> {
+    state
+        .effects_engine
+        .send_command(EngineCommand::SetBpm { bpm })
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state
+        .pattern_engine
+        .send_command(PatternCommand::SetBpm { bpm })
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(())
+}
+
+pub fn send_tap(state: &SharedState, at: Instant) -> Result<(), (StatusCode, String)> {
+    state
+        .effects_engine
+        .send_command(EngineCommand::Tap { at })
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    state
+        .pattern_engine
+        .send_command(PatternCommand::Tap { at })
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use tracing::{info, warn};
+
+use crate::program::Program;
+
+/// An ordered running order of programs for one show
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Setlist {
+    pub id: String,
+    pub name: String,
+    pub entries: Vec<SetlistEntry>,
+    #[serde(default)]
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SetlistEntry {
+    pub program_id: String,
+    /// How this entry hands over to the next one ("immediate", "blackout" or "hold");
+    /// without one the setlist waits for a `next` after the song
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transition_type: Option<String>,
+    #[serde(default)]
+    pub transition_duration: u32,  // Duration in milliseconds
+}
+
+/// What follows when a setlist entry plays out
+#[derive(Debug, PartialEq)]
+pub enum Advance<'a> {
+    /// The entry has no transition; the setlist waits for a `next`
+    Wait,
+    /// That was the last entry
+    Finished,
+    /// Hand over to entry `index` using the finished entry's transition
+    Next {
+        index: usize,
+        transition_type: &'a str,
+        duration_ms: u32,
+    },
+}
+
+impl Setlist {
+    /// Check ids and entries against the loaded programs
+    pub fn validate(&self, programs: &HashMap<String, Program>) -> Result<(), String> {
+        if self.id.is_empty() || self.id.contains(['/', '\\', '.']) {
+            return Err(format!("Invalid setlist id '{}'", self.id));
+        }
+        for (idx, entry) in self.entries.iter().enumerate() {
+            if !programs.contains_key(&entry.program_id) {
+                return Err(format!(
+                    "Setlist '{}': entry {} references unknown program '{}'",
+                    self.id,
+                    idx + 1,
+                    entry.program_id
+                ));
+            }
+            if let Some(transition_type) = &entry.transition_type {
+                if !matches!(transition_type.as_str(), "immediate" | "blackout" | "hold") {
+                    return Err(format!(
+                        "Setlist '{}': entry {} has unknown transition type '{}'",
+                        self.id,
+                        idx + 1,
+                        transition_type
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Where the setlist goes once entry `index` has played out
+    pub fn advance(&self, index: usize) -> Advance<'_> {
+        let Some(entry) = self.entries.get(index) else {
+            return Advance::Finished;
+        };
+        let Some(transition_type) = &entry.transition_type else {
+            return Advance::Wait;
+        };
+        if index + 1 >= self.entries.len() {
+            return Advance::Finished;
+        }
+        Advance::Next {
+            index: index + 1,
+            transition_type,
+            duration_ms: entry.transition_duration,
+        }
+    }
+
+    pub fn save_to_file(&self, setlists_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        fs::create_dir_all(setlists_path)?;
+        let file_path = setlists_path.join(format!("{}.json", self.id));
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(file_path, json)?;
+        Ok(())
+    }
+
+    pub fn load_all(setlists_path: &Path) -> Result<Vec<Setlist>, Box<dyn std::error::Error>> {
+        let mut setlists = Vec::new();
+
+        if !setlists_path.exists() {
+            return Ok(setlists);
+        }
+
+        for entry in fs::read_dir(setlists_path)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) == Some("json") {
+                match fs::read_to_string(&path) {
+                    Ok(json) => match serde_json::from_str::<Setlist>(&json) {
+                        Ok(setlist) => {
+                            info!("Loaded setlist: {}", setlist.id);
+                            setlists.push(setlist);
+                        }
+                        Err(e) => {
+                            warn!("Failed to parse setlist {}: {}", path.display(), e);
+                        }
+                    },
+                    Err(e) => {
+                        warn!("Failed to read setlist file {}: {}", path.display(), e);
+                    }
+                }
+            }
+        }
+
+        Ok(setlists)
+    }
+
+    pub fn delete(&self, setlists_path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let setlist_file = setlists_path.join(format!("{}.json", self.id));
+        if setlist_file.exists() {
+            fs::remove_file(setlist_file)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(program_id: &str, transition_type: Option<&str>, transition_duration: u32) -> SetlistEntry {
+        SetlistEntry {
+            program_id: program_id.to_string(),
+            transition_type: transition_type.map(str::to_string),
+            transition_duration,
+        }
+    }
+
+    fn setlist(id: &str, entries: Vec<SetlistEntry>) -> Setlist {
+        Setlist {
+            id: id.to_string(),
+            name: id.to_string(),
+            entries,
+            created_at: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let programs = HashMap::from([
+            ("intro".to_string(), Program::fixture("intro", serde_json::json!([]))),
+            ("song".to_string(), Program::fixture("song", serde_json::json!([]))),
+        ]);
+        let good = setlist("friday", vec![entry("intro", Some("blackout"), 500), entry("song", None, 0)]);
+        assert!(good.validate(&programs).is_ok());
+
+        for id in ["", "../friday", "fri/day", "fri\\day", "friday.json"] {
+            assert!(setlist(id, vec![]).validate(&programs).is_err(), "accepted id '{}'", id);
+        }
+
+        let unknown_program = setlist("friday", vec![entry("intro", None, 0), entry("encore", None, 0)]);
+        let err = unknown_program.validate(&programs).unwrap_err();
+        assert!(err.contains("entry 2") && err.contains("'encore'"), "{}", err);
+
+        let unknown_transition = setlist("friday", vec![entry("intro", Some("crossfade"), 0)]);
+        assert!(unknown_transition.validate(&programs).unwrap_err().contains("'crossfade'"));
+    }
+
+    #[test]
+    fn test_advance() {
+        let setlist = setlist(
+            "friday",
+            vec![
+                entry("intro", Some("blackout"), 500),
+                entry("song", None, 0),
+                entry("encore", Some("hold"), 0),
+            ],
+        );
+
+        assert_eq!(
+            setlist.advance(0),
+            Advance::Next { index: 1, transition_type: "blackout", duration_ms: 500 }
+        );
+        // No transition: hold on the entry until someone sends next
+        assert_eq!(setlist.advance(1), Advance::Wait);
+        // A transition on the last entry has nothing to hand over to
+        assert_eq!(setlist.advance(2), Advance::Finished);
+        assert_eq!(setlist.advance(3), Advance::Finished);
+    }
+}
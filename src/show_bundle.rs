@@ -0,0 +1,669 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::audio::AudioFile;
+use crate::config::{Config, EffectPreset, PatternPreset, SequencePreset};
+use crate::program::Program;
+use crate::types::WledPreset;
+
+const FORMAT_VERSION: u32 = 1;
+const MANIFEST: &str = "manifest.json";
+const EFFECT_PRESETS: &str = "presets/effect_presets.json";
+const PATTERN_PRESETS: &str = "presets/pattern_presets.json";
+const WLED_PRESETS: &str = "presets/wled_presets.json";
+const SEQUENCE_PRESETS: &str = "presets/sequence_presets.json";
+/// Most a bundle may unpack to, twice the import upload limit
+const MAX_UNPACKED_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Table of contents of a show bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: u32,
+    pub created_at: String,
+    pub programs: Vec<String>,
+    pub audio_files: Vec<String>,
+    pub effect_presets: Vec<String>,
+    pub pattern_presets: Vec<String>,
+    pub wled_presets: Vec<String>,
+    #[serde(default)]
+    pub sequence_presets: Vec<String>,
+    /// Boards and groups the cues target, for remapping on import
+    pub targets: Vec<String>,
+}
+
+/// A file from the audio directory: a program's audio or a sequence preset's FSEQ file
+pub struct AudioEntry {
+    pub name: String,
+    pub data: Vec<u8>,
+    pub peaks: Option<Vec<u8>>,
+}
+
+/// Programs with everything they need to play on another machine
+pub struct Bundle {
+    pub manifest: Manifest,
+    pub programs: Vec<Program>,
+    pub audio: Vec<AudioEntry>,
+    pub effect_presets: Vec<EffectPreset>,
+    pub pattern_presets: Vec<PatternPreset>,
+    pub wled_presets: Vec<WledPreset>,
+    pub sequence_presets: Vec<SequencePreset>,
+}
+
+/// What to do when an imported program or preset already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnConflict {
+    /// Keep what is already here
+    #[default]
+    Skip,
+    Replace,
+    /// Import under a new id or name
+    Rename,
+}
+
+/// What an import brought in
+#[derive(Debug, Default, Serialize)]
+pub struct ImportReport {
+    pub programs: Vec<String>,
+    pub skipped_programs: Vec<String>,
+    pub renamed_programs: BTreeMap<String, String>,
+    pub renamed_presets: BTreeMap<String, String>,
+    pub audio_files: Vec<String>,
+    pub effect_presets: Vec<String>,
+    pub pattern_presets: Vec<String>,
+    pub wled_presets: Vec<String>,
+    pub sequence_presets: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// What the importing machine already has
+pub struct Existing<'a> {
+    pub programs: &'a HashMap<String, Program>,
+    pub config: &'a Config,
+    pub wled_presets: &'a [WledPreset],
+    pub audio_path: &'a Path,
+}
+
+impl Bundle {
+    /// Gather `programs` with their audio, the presets their cues use and the
+    /// FSEQ files behind any sequence presets
+    pub fn collect(
+        programs: Vec<Program>,
+        config: &Config,
+        wled_presets: &[WledPreset],
+        audio_path: &Path,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let preset_names: HashSet<&str> = programs
+            .iter()
+            .flat_map(|p| p.cues.iter().map(|c| c.preset_name.as_str()))
+            .collect();
+
+        let effect_presets: Vec<EffectPreset> = config
+            .effect_presets
+            .iter()
+            .filter(|p| preset_names.contains(p.name.as_str()))
+            .cloned()
+            .collect();
+        let pattern_presets: Vec<PatternPreset> = config
+            .pattern_presets
+            .iter()
+            .filter(|p| preset_names.contains(p.name.as_str()))
+            .cloned()
+            .collect();
+        let wled_presets: Vec<WledPreset> = wled_presets
+            .iter()
+            .filter(|p| preset_names.contains(p.name.as_str()))
+            .cloned()
+            .collect();
+        let sequence_presets: Vec<SequencePreset> = config
+            .sequence_presets
+            .iter()
+            .filter(|p| preset_names.contains(p.name.as_str()))
+            .cloned()
+            .collect();
+
+        let mut audio = Vec::new();
+        let mut seen = HashSet::new();
+        let files = programs
+            .iter()
+            .filter_map(|p| p.audio_file.as_deref())
+            .chain(sequence_presets.iter().map(|p| p.file.as_str()));
+        for file in files {
+            if !seen.insert(file) {
+                continue;
+            }
+            let path = AudioFile::resolve_path(file, audio_path)?;
+            if !path.exists() {
+                return Err(format!("Audio file '{}' not found", file).into());
+            }
+            let peaks_path = audio_path.join(format!("{}.peaks.json", file));
+            audio.push(AudioEntry {
+                name: file.to_string(),
+                data: fs::read(&path)?,
+                peaks: fs::read(peaks_path).ok(),
+            });
+        }
+
+        let mut targets: Vec<String> = programs
+            .iter()
+            .flat_map(|p| p.cues.iter().flat_map(|c| c.targets.iter().cloned()))
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        targets.sort();
+
+        let manifest = Manifest {
+            format: FORMAT_VERSION,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            programs: programs.iter().map(|p| p.id.clone()).collect(),
+            audio_files: audio.iter().map(|a| a.name.clone()).collect(),
+            effect_presets: effect_presets.iter().map(|p| p.name.clone()).collect(),
+            pattern_presets: pattern_presets.iter().map(|p| p.name.clone()).collect(),
+            wled_presets: wled_presets.iter().map(|p| p.name.clone()).collect(),
+            sequence_presets: sequence_presets.iter().map(|p| p.name.clone()).collect(),
+            targets,
+        };
+
+        Ok(Self {
+            manifest,
+            programs,
+            audio,
+            effect_presets,
+            pattern_presets,
+            wled_presets,
+            sequence_presets,
+        })
+    }
+
+    pub fn to_zip(&self) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+        let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        // Audio is compressed already
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+        write_json(&mut zip, deflated, MANIFEST, serde_json::to_string_pretty(&self.manifest)?)?;
+        for program in &self.programs {
+            write_json(
+                &mut zip,
+                deflated,
+                &format!("programs/{}.json", program.id),
+                serde_json::to_string_pretty(program)?,
+            )?;
+        }
+        write_json(&mut zip, deflated, EFFECT_PRESETS, serde_json::to_string_pretty(&self.effect_presets)?)?;
+        write_json(&mut zip, deflated, PATTERN_PRESETS, serde_json::to_string_pretty(&self.pattern_presets)?)?;
+        write_json(&mut zip, deflated, WLED_PRESETS, serde_json::to_string_pretty(&self.wled_presets)?)?;
+        write_json(&mut zip, deflated, SEQUENCE_PRESETS, serde_json::to_string_pretty(&self.sequence_presets)?)?;
+
+        for entry in &self.audio {
+            zip.start_file(format!("audio/{}", entry.name), stored)?;
+            zip.write_all(&entry.data)?;
+            if let Some(ref peaks) = entry.peaks {
+                zip.start_file(format!("audio/{}.peaks.json", entry.name), deflated)?;
+                zip.write_all(peaks)?;
+            }
+        }
+
+        Ok(zip.finish()?.into_inner())
+    }
+
+    pub fn from_zip(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+        let mut budget = MAX_UNPACKED_BYTES;
+        for idx in 0..archive.len() {
+            let mut file = archive.by_index(idx)?;
+            if file.is_dir() {
+                continue;
+            }
+            if file.enclosed_name().is_none() {
+                return Err(format!("Unsafe path in bundle: {}", file.name()).into());
+            }
+            // The header's size is only a claim; stop reading once the budget runs out
+            let mut data = Vec::new();
+            file.by_ref().take(budget + 1).read_to_end(&mut data)?;
+            if data.len() as u64 > budget {
+                return Err(format!("Bundle unpacks to more than {} MB", MAX_UNPACKED_BYTES / (1024 * 1024)).into());
+            }
+            budget -= data.len() as u64;
+            files.insert(file.name().to_string(), data);
+        }
+
+        let manifest: Manifest = serde_json::from_slice(files.get(MANIFEST).ok_or("Bundle has no manifest.json")?)?;
+        if manifest.format > FORMAT_VERSION {
+            return Err(format!("Bundle format {} is newer than this server supports", manifest.format).into());
+        }
+
+        let mut programs = Vec::new();
+        for id in &manifest.programs {
+            if id.is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+                return Err(format!("Invalid program id '{}' in bundle", id).into());
+            }
+            let json = files
+                .get(&format!("programs/{}.json", id))
+                .ok_or_else(|| format!("Bundle is missing program '{}'", id))?;
+            let program: Program = serde_json::from_slice(json)?;
+            if program.id != *id {
+                return Err(format!("Bundle program '{}' has id '{}'", id, program.id).into());
+            }
+            programs.push(program);
+        }
+
+        let mut audio = Vec::new();
+        for name in &manifest.audio_files {
+            AudioFile::resolve_path(name, Path::new(""))?;
+            let data = files
+                .remove(&format!("audio/{}", name))
+                .ok_or_else(|| format!("Bundle is missing audio file '{}'", name))?;
+            audio.push(AudioEntry {
+                name: name.clone(),
+                data,
+                peaks: files.remove(&format!("audio/{}.peaks.json", name)),
+            });
+        }
+
+        let presets = |name: &str| files.get(name).map(|json| json.as_slice()).unwrap_or(b"[]");
+        Ok(Self {
+            effect_presets: serde_json::from_slice(presets(EFFECT_PRESETS))?,
+            pattern_presets: serde_json::from_slice(presets(PATTERN_PRESETS))?,
+            wled_presets: serde_json::from_slice(presets(WLED_PRESETS))?,
+            sequence_presets: serde_json::from_slice(presets(SEQUENCE_PRESETS))?,
+            manifest,
+            programs,
+            audio,
+        })
+    }
+
+    /// Point cues, default targets and custom pattern steps at this rig's boards and groups
+    pub fn remap_targets(&mut self, remap: &HashMap<String, String>) {
+        let map = |name: &mut String| {
+            if let Some(to) = remap.get(name.as_str()) {
+                *name = to.clone();
+            }
+        };
+        for program in &mut self.programs {
+            if let Some(board) = program.default_target_board.as_mut() {
+                map(board);
+            }
+            for cue in &mut program.cues {
+                cue.targets.iter_mut().for_each(map);
+            }
+        }
+        for preset in &mut self.pattern_presets {
+            preset.steps.iter_mut().flatten().for_each(map);
+        }
+    }
+
+    /// Settle id, name and slot clashes with what `existing` already has
+    ///
+    /// Drops whatever is skipped and rewrites references to anything renamed,
+    /// so the bundle can then be written out as it stands.
+    pub fn resolve(&mut self, existing: &Existing, on_conflict: OnConflict) -> Result<ImportReport, Box<dyn std::error::Error>> {
+        let mut report = ImportReport::default();
+
+        // Presets share one namespace: a cue names either kind
+        let mut taken: HashSet<String> = existing
+            .config
+            .effect_presets
+            .iter()
+            .map(|p| p.name.clone())
+            .chain(existing.config.pattern_presets.iter().map(|p| p.name.clone()))
+            .chain(existing.config.sequence_presets.iter().map(|p| p.name.clone()))
+            .collect();
+        let mut preset_renames: HashMap<String, String> = HashMap::new();
+
+        let effect_presets = std::mem::take(&mut self.effect_presets);
+        for mut preset in effect_presets {
+            let current = existing.config.effect_presets.iter().find(|p| p.name == preset.name);
+            if current.is_some_and(|c| same(c, &preset)) {
+                continue;
+            }
+            if taken.contains(&preset.name) {
+                match on_conflict {
+                    OnConflict::Skip => {
+                        report.warnings.push(format!("Kept existing preset '{}'", preset.name));
+                        continue;
+                    }
+                    OnConflict::Replace => {}
+                    OnConflict::Rename => {
+                        let name = unique(&preset.name, |n| taken.contains(n), " (imported)");
+                        preset_renames.insert(preset.name.clone(), name.clone());
+                        preset.name = name;
+                    }
+                }
+            }
+            taken.insert(preset.name.clone());
+            report.effect_presets.push(preset.name.clone());
+            self.effect_presets.push(preset);
+        }
+
+        let pattern_presets = std::mem::take(&mut self.pattern_presets);
+        for mut preset in pattern_presets {
+            let current = existing.config.pattern_presets.iter().find(|p| p.name == preset.name);
+            if current.is_some_and(|c| same(c, &preset)) {
+                continue;
+            }
+            if taken.contains(&preset.name) {
+                match on_conflict {
+                    OnConflict::Skip => {
+                        report.warnings.push(format!("Kept existing preset '{}'", preset.name));
+                        continue;
+                    }
+                    OnConflict::Replace => {}
+                    OnConflict::Rename => {
+                        let name = unique(&preset.name, |n| taken.contains(n), " (imported)");
+                        preset_renames.insert(preset.name.clone(), name.clone());
+                        preset.name = name;
+                    }
+                }
+            }
+            taken.insert(preset.name.clone());
+            report.pattern_presets.push(preset.name.clone());
+            self.pattern_presets.push(preset);
+        }
+
+        let sequence_presets = std::mem::take(&mut self.sequence_presets);
+        for mut preset in sequence_presets {
+            let current = existing.config.sequence_presets.iter().find(|p| p.name == preset.name);
+            if current.is_some_and(|c| same(c, &preset)) {
+                continue;
+            }
+            if taken.contains(&preset.name) {
+                match on_conflict {
+                    OnConflict::Skip => {
+                        report.warnings.push(format!("Kept existing preset '{}'", preset.name));
+                        continue;
+                    }
+                    OnConflict::Replace => {}
+                    OnConflict::Rename => {
+                        let name = unique(&preset.name, |n| taken.contains(n), " (imported)");
+                        preset_renames.insert(preset.name.clone(), name.clone());
+                        preset.name = name;
+                    }
+                }
+            }
+            taken.insert(preset.name.clone());
+            report.sequence_presets.push(preset.name.clone());
+            self.sequence_presets.push(preset);
+        }
+
+        let mut slots: HashSet<u8> = existing.wled_presets.iter().map(|p| p.wled_slot).collect();
+        let wled_presets = std::mem::take(&mut self.wled_presets);
+        for mut preset in wled_presets {
+            let current = existing.wled_presets.iter().find(|p| p.name == preset.name);
+            if let Some(current) = current {
+                match on_conflict {
+                    OnConflict::Skip => continue,
+                    OnConflict::Replace => {
+                        // Takes over the existing preset's slot and id
+                        preset.wled_slot = current.wled_slot;
+                        preset.id = current.id.clone();
+                        preset.version = current.version + 1;
+                    }
+                    OnConflict::Rename => {
+                        let name = unique(&preset.name, |n| existing.wled_presets.iter().any(|p| p.name == n), " (imported)");
+                        preset_renames.insert(preset.name.clone(), name.clone());
+                        preset.name = name;
+                    }
+                }
+            }
+            if current.is_none() || on_conflict == OnConflict::Rename {
+                if slots.contains(&preset.wled_slot) {
+                    preset.wled_slot = (1..=250u8)
+                        .find(|s| !slots.contains(s))
+                        .ok_or("No free WLED preset slots (1-250)")?;
+                }
+                preset.id = uuid::Uuid::new_v4().to_string();
+                preset.version = 0;
+            }
+            slots.insert(preset.wled_slot);
+            report.wled_presets.push(preset.name.clone());
+            self.wled_presets.push(preset);
+        }
+
+        let mut program_renames: HashMap<String, String> = HashMap::new();
+        let mut imported_ids: HashSet<String> = HashSet::new();
+        let programs = std::mem::take(&mut self.programs);
+        for mut program in programs {
+            if existing.programs.contains_key(&program.id) {
+                match on_conflict {
+                    OnConflict::Skip => {
+                        report.skipped_programs.push(program.id);
+                        continue;
+                    }
+                    OnConflict::Replace => {}
+                    OnConflict::Rename => {
+                        let id = unique(&program.id, |id| existing.programs.contains_key(id) || imported_ids.contains(id), "-imported");
+                        program_renames.insert(program.id.clone(), id.clone());
+                        program.id = id;
+                    }
+                }
+            }
+            for cue in &mut program.cues {
+                if let Some(name) = preset_renames.get(&cue.preset_name) {
+                    cue.preset_name = name.clone();
+                }
+            }
+            imported_ids.insert(program.id.clone());
+            self.programs.push(program);
+        }
+
+        for program in &mut self.programs {
+            if let Some(next) = program.next_program_id.as_ref().and_then(|id| program_renames.get(id)) {
+                program.next_program_id = Some(next.clone());
+            }
+        }
+
+        // Audio only for the programs and sequence presets still coming in; a
+        // clash with different content gets a new name
+        let mut audio_renames: HashMap<String, String> = HashMap::new();
+        let wanted: HashSet<String> = self
+            .programs
+            .iter()
+            .filter_map(|p| p.audio_file.clone())
+            .chain(self.sequence_presets.iter().map(|p| p.file.clone()))
+            .collect();
+        let audio = std::mem::take(&mut self.audio);
+        for mut entry in audio.into_iter().filter(|a| wanted.contains(&a.name)) {
+            let path = AudioFile::resolve_path(&entry.name, existing.audio_path)?;
+            if path.exists() {
+                if fs::read(&path).is_ok_and(|data| data == entry.data) {
+                    continue;
+                }
+                let name = unique_file_name(&entry.name, existing.audio_path);
+                audio_renames.insert(entry.name.clone(), name.clone());
+                entry.name = name;
+            }
+            report.audio_files.push(entry.name.clone());
+            self.audio.push(entry);
+        }
+        for program in &mut self.programs {
+            if let Some(name) = program.audio_file.as_ref().and_then(|f| audio_renames.get(f)) {
+                program.audio_file = Some(name.clone());
+            }
+        }
+        for preset in &mut self.sequence_presets {
+            if let Some(name) = audio_renames.get(&preset.file) {
+                preset.file = name.clone();
+            }
+        }
+
+        for program in &self.programs {
+            for target in program.cues.iter().flat_map(|c| c.targets.iter()) {
+                if existing.config.get_target_boards(target).is_empty() {
+                    let warning = format!("Program '{}' targets unknown board or group '{}'", program.id, target);
+                    if !report.warnings.contains(&warning) {
+                        report.warnings.push(warning);
+                    }
+                }
+            }
+        }
+
+        report.programs = self.programs.iter().map(|p| p.id.clone()).collect();
+        report.renamed_programs = program_renames.into_iter().collect();
+        report.renamed_presets = preset_renames.into_iter().collect();
+        Ok(report)
+    }
+}
+
+fn write_json(
+    zip: &mut ZipWriter<Cursor<Vec<u8>>>,
+    options: SimpleFileOptions,
+    name: &str,
+    json: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    zip.start_file(name, options)?;
+    zip.write_all(json.as_bytes())?;
+    Ok(())
+}
+
+fn same<T: Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// `base` with `suffix`, numbered until `taken` lets it through
+fn unique(base: &str, taken: impl Fn(&str) -> bool, suffix: &str) -> String {
+    let mut candidate = format!("{}{}", base, suffix);
+    let mut n = 2;
+    while taken(&candidate) {
+        candidate = format!("{}{}-{}", base, suffix, n);
+        n += 1;
+    }
+    candidate
+}
+
+fn unique_file_name(name: &str, dir: &Path) -> String {
+    let (stem, ext) = match name.rsplit_once('.') {
+        Some((stem, ext)) => (stem, format!(".{}", ext)),
+        None => (name, String::new()),
+    };
+    let mut n = 2;
+    loop {
+        let candidate = format!("{}-{}{}", stem, n, ext);
+        if !dir.join(&candidate).exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        serde_json::from_value(serde_json::json!({
+            "boards": [{ "id": "left", "ip": "10.0.0.1" }],
+            "effect_presets": [{ "name": "red", "effect_type": "solid", "color": [255, 0, 0] }]
+        }))
+        .unwrap()
+    }
+
+    fn program(id: &str, preset: &str, next: Option<&str>) -> Program {
+        let cues = serde_json::json!([{ "time": 0.0, "label": "go", "targets": ["stage-left"], "preset_name": preset }]);
+        Program {
+            next_program_id: next.map(str::to_string),
+            ..Program::fixture(id, cues)
+        }
+    }
+
+    #[test]
+    fn test_zip_round_trip() {
+        let config = config();
+        let bundle = Bundle::collect(vec![program("song", "red", None)], &config, &[], Path::new("audio")).unwrap();
+        let bytes = bundle.to_zip().unwrap();
+
+        let read = Bundle::from_zip(&bytes).unwrap();
+        assert_eq!(read.manifest.programs, vec!["song".to_string()]);
+        assert_eq!(read.manifest.targets, vec!["stage-left".to_string()]);
+        assert_eq!(read.effect_presets.len(), 1);
+        assert_eq!(read.programs[0].cues[0].preset_name, "red");
+    }
+
+    #[test]
+    fn test_sequence_presets_travel_with_their_files() {
+        let root = std::env::temp_dir().join(format!("show-bundle-test-{}", std::process::id()));
+        let (here, there) = (root.join("here"), root.join("there"));
+        fs::create_dir_all(&here).unwrap();
+        fs::create_dir_all(&there).unwrap();
+        fs::write(here.join("intro.fseq"), b"PSEQ here").unwrap();
+        // The importing machine has a different file under the same name
+        fs::write(there.join("intro.fseq"), b"PSEQ there").unwrap();
+
+        let mut source = config();
+        source.sequence_presets = vec![SequencePreset {
+            name: "intro-seq".to_string(),
+            file: "intro.fseq".to_string(),
+            start_frame: 0,
+            end_frame: None,
+        }];
+        let bundle = Bundle::collect(vec![program("song", "intro-seq", None)], &source, &[], &here).unwrap();
+        let mut read = Bundle::from_zip(&bundle.to_zip().unwrap()).unwrap();
+        assert_eq!(read.manifest.sequence_presets, vec!["intro-seq".to_string()]);
+        assert_eq!(read.sequence_presets[0].file, "intro.fseq");
+        assert_eq!(read.audio[0].data, b"PSEQ here");
+
+        let target = config();
+        let existing = Existing {
+            programs: &HashMap::new(),
+            config: &target,
+            wled_presets: &[],
+            audio_path: &there,
+        };
+        let report = read.resolve(&existing, OnConflict::Skip).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(report.sequence_presets, vec!["intro-seq".to_string()]);
+        assert_eq!(report.audio_files, vec!["intro-2.fseq".to_string()]);
+        assert_eq!(read.sequence_presets[0].file, "intro-2.fseq");
+    }
+
+    #[test]
+    fn test_rejects_unsafe_program_ids() {
+        let config = config();
+        let mut bundle = Bundle::collect(vec![program("song", "red", None)], &config, &[], Path::new("audio")).unwrap();
+        bundle.manifest.programs = vec!["../song".to_string()];
+        assert!(Bundle::from_zip(&bundle.to_zip().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_rename_rewrites_references() {
+        let here = config();
+        let mut bundle = Bundle {
+            manifest: Bundle::collect(vec![], &here, &[], Path::new("audio")).unwrap().manifest,
+            programs: vec![program("intro", "red", Some("song")), program("song", "red", None)],
+            audio: vec![],
+            effect_presets: vec![EffectPreset {
+                name: "red".to_string(),
+                effect_type: "pulse".to_string(),
+                color: [200, 0, 0],
+            }],
+            pattern_presets: vec![],
+            wled_presets: vec![],
+            sequence_presets: vec![],
+        };
+        bundle.remap_targets(&HashMap::from([("stage-left".to_string(), "left".to_string())]));
+
+        let programs = HashMap::from([("song".to_string(), program("song", "red", None))]);
+        let existing = Existing {
+            programs: &programs,
+            config: &here,
+            wled_presets: &[],
+            audio_path: Path::new("audio"),
+        };
+        let report = bundle.resolve(&existing, OnConflict::Rename).unwrap();
+
+        assert_eq!(report.programs, vec!["intro".to_string(), "song-imported".to_string()]);
+        assert_eq!(bundle.programs[0].next_program_id.as_deref(), Some("song-imported"));
+        assert_eq!(bundle.effect_presets[0].name, "red (imported)");
+        assert_eq!(bundle.programs[1].cues[0].preset_name, "red (imported)");
+        assert_eq!(bundle.programs[1].cues[0].targets, vec!["left".to_string()]);
+        assert!(report.warnings.is_empty());
+    }
+}
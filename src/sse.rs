@@ -1,4 +1,5 @@
 use crate::board::BoardState;
+use crate::program_engine::{CueStackPosition, SetlistPosition};
 use serde::Serialize;
 
 #[derive(Debug, Clone, Serialize)]
@@ -8,4 +9,25 @@ pub enum SseEvent {
     StateUpdate { board_id: String, state: BoardState },
     #[serde(rename = "connection_status")]
     ConnectionStatus { board_id: String, connected: bool },
+    #[serde(rename = "program_handoff")]
+    ProgramHandOff {
+        from_program_id: String,
+        to_program_id: String,
+        transition: String,
+        transition_duration: u32,
+    },
+    #[serde(rename = "program_chain_ended")]
+    ProgramChainEnded { program_id: String, reason: String },
+    #[serde(rename = "program_updated")]
+    ProgramUpdated { program_id: String, version: u64 },
+    #[serde(rename = "program_deleted")]
+    ProgramDeleted { program_id: String },
+    #[serde(rename = "loop_armed")]
+    LoopArmed { program_id: String, name: String },
+    #[serde(rename = "loop_released")]
+    LoopReleased { program_id: String, name: String },
+    #[serde(rename = "setlist_position")]
+    SetlistPosition(SetlistPosition),
+    #[serde(rename = "cue_stack_position")]
+    CueStackPosition(CueStackPosition),
 }
@@ -0,0 +1,198 @@
+use std::time::{Duration, Instant};
+
+const TAP_RESET: Duration = Duration::from_secs(2);
+const MAX_TAPS: usize = 8;
+const MIN_TAP_BPM: f64 = 30.0;
+const MAX_TAP_BPM: f64 = 300.0;
+
+/// Monotonic clock whose speed can change without a jump in position
+///
+/// Position is measured in seconds at the tempo the running effect or pattern
+/// was built for, so a rate of 2.0 plays it at double time. The rate is the
+/// tempo change times a separate live speed multiplier.
+#[derive(Debug, Clone)]
+pub struct BeatClock {
+    anchor: Instant,
+    anchor_pos: f64,
+    tempo: f64,
+    speed: f64,
+    nudge: f64,
+    nudge_window: f64,
+    frozen: bool,
+}
+
+impl BeatClock {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            anchor: now,
+            anchor_pos: 0.0,
+            tempo: 1.0,
+            speed: 1.0,
+            nudge: 0.0,
+            nudge_window: 0.0,
+            frozen: false,
+        }
+    }
+
+    /// Clock that reads `position` at `now`
+    pub fn starting_at(now: Instant, position: f64) -> Self {
+        Self {
+            anchor_pos: position,
+            ..Self::new(now)
+        }
+    }
+
+    pub fn rate(&self) -> f64 {
+        if self.frozen {
+            return 0.0;
+        }
+        self.tempo * self.speed
+    }
+
+    pub fn position(&self, now: Instant) -> f64 {
+        let dt = now.saturating_duration_since(self.anchor).as_secs_f64();
+        let nudge = if self.nudge_window > 0.0 {
+            self.nudge * (dt / self.nudge_window).min(1.0)
+        } else {
+            0.0
+        };
+        self.anchor_pos + dt * self.rate() + nudge
+    }
+
+    fn reanchor(&mut self, now: Instant) {
+        self.anchor_pos = self.position(now);
+        self.anchor = now;
+        self.nudge = 0.0;
+        self.nudge_window = 0.0;
+    }
+
+    /// Change tempo from `now` on, keeping the current position
+    pub fn set_rate(&mut self, now: Instant, rate: f64) {
+        self.reanchor(now);
+        self.tempo = rate;
+    }
+
+    /// Change the live speed multiplier from `now` on, keeping the current position
+    pub fn set_speed(&mut self, now: Instant, speed: f64) {
+        self.reanchor(now);
+        self.speed = speed;
+    }
+
+    /// Stop or restart the clock at `now`; a frozen clock keeps its position
+    pub fn set_frozen(&mut self, now: Instant, frozen: bool) {
+        if frozen == self.frozen {
+            return;
+        }
+        self.reanchor(now);
+        self.frozen = frozen;
+    }
+
+    /// Pull the phase so that `at` lands on a multiple of `beat_len`
+    ///
+    /// The correction is spread over one beat rather than applied as a jump,
+    /// so the position never runs backwards.
+    pub fn align_to_beat(&mut self, now: Instant, at: Instant, beat_len: f64) {
+        if beat_len <= 0.0 || self.rate() <= 0.0 {
+            return;
+        }
+        let at_pos = self.position(at);
+        let correction = (at_pos / beat_len).round() * beat_len - at_pos;
+
+        self.reanchor(now);
+        self.nudge = correction;
+        self.nudge_window = beat_len / self.rate();
+    }
+}
+
+/// Tap tempo: averages the intervals of recent taps
+#[derive(Debug, Default)]
+pub struct TapTempo {
+    taps: Vec<Instant>,
+}
+
+impl TapTempo {
+    /// Register a tap, returning the tapped BPM once there are two or more taps
+    pub fn tap(&mut self, at: Instant) -> Option<f64> {
+        if let Some(last) = self.taps.last() {
+            if at.saturating_duration_since(*last) > TAP_RESET {
+                self.taps.clear();
+            }
+        }
+
+        self.taps.push(at);
+        if self.taps.len() > MAX_TAPS {
+            self.taps.remove(0);
+        }
+
+        if self.taps.len() < 2 {
+            return None;
+        }
+
+        let first = self.taps[0];
+        let last = self.taps[self.taps.len() - 1];
+        let interval = last.saturating_duration_since(first).as_secs_f64() / (self.taps.len() - 1) as f64;
+        if interval <= 0.0 {
+            return None;
+        }
+
+        Some((60.0 / interval).clamp(MIN_TAP_BPM, MAX_TAP_BPM))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rate_change_keeps_position() {
+        let t0 = Instant::now();
+        let mut clock = BeatClock::new(t0);
+        let t1 = t0 + Duration::from_secs(2);
+        clock.set_rate(t1, 2.0);
+        assert!((clock.position(t1) - 2.0).abs() < 1e-9);
+        assert!((clock.position(t1 + Duration::from_secs(1)) - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_frozen_clock_holds_position() {
+        let t0 = Instant::now();
+        let mut clock = BeatClock::new(t0);
+        let t1 = t0 + Duration::from_secs(1);
+        clock.set_frozen(t1, true);
+        assert!((clock.position(t1 + Duration::from_secs(5)) - 1.0).abs() < 1e-9);
+
+        let t2 = t1 + Duration::from_secs(5);
+        clock.set_frozen(t2, false);
+        assert!((clock.position(t2 + Duration::from_secs(1)) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_align_never_runs_backwards() {
+        let t0 = Instant::now();
+        let mut clock = BeatClock::new(t0);
+        let tap = t0 + Duration::from_millis(1400);
+        clock.align_to_beat(tap, tap, 0.5);
+
+        let mut last = clock.position(tap);
+        for ms in (0..1000).step_by(10) {
+            let pos = clock.position(tap + Duration::from_millis(ms));
+            assert!(pos >= last);
+            last = pos;
+        }
+        // After the nudge window the tap sits on a beat
+        let settled = clock.position(tap + Duration::from_millis(500));
+        assert!((settled - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_tap_tempo_averages_and_resets() {
+        let t0 = Instant::now();
+        let mut taps = TapTempo::default();
+        assert_eq!(taps.tap(t0), None);
+        let bpm = taps.tap(t0 + Duration::from_millis(500)).unwrap();
+        assert!((bpm - 120.0).abs() < 1e-6);
+        let bpm = taps.tap(t0 + Duration::from_millis(1000)).unwrap();
+        assert!((bpm - 120.0).abs() < 1e-6);
+        assert_eq!(taps.tap(t0 + Duration::from_secs(5)), None);
+    }
+}
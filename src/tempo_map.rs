@@ -0,0 +1,209 @@
+use serde::{Deserialize, Serialize};
+
+/// Resolution of `BeatPosition::tick`
+pub const TICKS_PER_BEAT: u32 = 480;
+
+const DEFAULT_BEATS_PER_BAR: u32 = 4;
+
+/// A tempo and/or time-signature change, in effect from `bar` on
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct TempoChange {
+    /// First bar (1-based) at this tempo
+    pub bar: u32,
+    pub bpm: f64,
+    /// Time-signature numerator; `bpm` counts these beats
+    #[serde(default = "default_beats_per_bar")]
+    pub beats_per_bar: u32,
+}
+
+fn default_beats_per_bar() -> u32 {
+    DEFAULT_BEATS_PER_BAR
+}
+
+/// Musical position of a cue: bar and beat are 1-based, ticks are 1/480 beat
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct BeatPosition {
+    pub bar: u32,
+    pub beat: u32,
+    #[serde(default)]
+    pub tick: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Segment {
+    bar: u32,
+    start: f64,
+    bpm: f64,
+    beats_per_bar: u32,
+}
+
+impl Segment {
+    fn beat_secs(&self) -> f64 {
+        60.0 / self.bpm
+    }
+}
+
+/// Tempo changes of a song laid out in time
+///
+/// Bar 1 beat 1 sits at the program's `grid_offset`; bars before the first
+/// change play at the program's own BPM in 4/4.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TempoMap {
+    segments: Vec<Segment>,
+}
+
+impl TempoMap {
+    pub fn new(changes: &[TempoChange], default_bpm: f64, offset: f64) -> Result<Self, String> {
+        let mut changes = changes.to_vec();
+        changes.sort_by_key(|c| c.bar);
+
+        for (idx, change) in changes.iter().enumerate() {
+            if change.bar == 0 {
+                return Err("Tempo change bars start at 1".to_string());
+            }
+            if !(change.bpm.is_finite() && change.bpm > 0.0) {
+                return Err(format!("Tempo change at bar {} needs a positive BPM", change.bar));
+            }
+            if change.beats_per_bar == 0 {
+                return Err(format!("Tempo change at bar {} needs at least 1 beat per bar", change.bar));
+            }
+            if idx > 0 && changes[idx - 1].bar == change.bar {
+                return Err(format!("Two tempo changes at bar {}", change.bar));
+            }
+        }
+
+        if changes.first().is_none_or(|c| c.bar > 1) {
+            if !(default_bpm.is_finite() && default_bpm > 0.0) {
+                return Err("Program BPM must be positive".to_string());
+            }
+            changes.insert(
+                0,
+                TempoChange {
+                    bar: 1,
+                    bpm: default_bpm,
+                    beats_per_bar: DEFAULT_BEATS_PER_BAR,
+                },
+            );
+        }
+
+        let mut segments: Vec<Segment> = Vec::with_capacity(changes.len());
+        for change in changes {
+            let start = match segments.last() {
+                Some(prev) => {
+                    let beats = (change.bar - prev.bar) as f64 * prev.beats_per_bar as f64;
+                    prev.start + beats * prev.beat_secs()
+                }
+                None => offset,
+            };
+            segments.push(Segment {
+                bar: change.bar,
+                start,
+                bpm: change.bpm,
+                beats_per_bar: change.beats_per_bar,
+            });
+        }
+
+        Ok(Self { segments })
+    }
+
+    /// A single tempo in 4/4 from `offset`
+    pub fn constant(bpm: f64, offset: f64) -> Self {
+        Self {
+            segments: vec![Segment {
+                bar: 1,
+                start: offset,
+                bpm,
+                beats_per_bar: DEFAULT_BEATS_PER_BAR,
+            }],
+        }
+    }
+
+    /// Song time in seconds of a musical position
+    pub fn seconds_at(&self, position: &BeatPosition) -> Result<f64, String> {
+        if position.bar == 0 || position.beat == 0 {
+            return Err(format!(
+                "Bar and beat are 1-based, got {}.{}",
+                position.bar, position.beat
+            ));
+        }
+        if position.tick >= TICKS_PER_BEAT {
+            return Err(format!("Tick must be below {}, got {}", TICKS_PER_BEAT, position.tick));
+        }
+
+        let segment = self.segment_for_bar(position.bar);
+        if position.beat > segment.beats_per_bar {
+            return Err(format!(
+                "Bar {} has {} beats, no beat {}",
+                position.bar, segment.beats_per_bar, position.beat
+            ));
+        }
+
+        let beats = (position.bar - segment.bar) as f64 * segment.beats_per_bar as f64
+            + (position.beat - 1) as f64
+            + position.tick as f64 / TICKS_PER_BEAT as f64;
+        Ok(segment.start + beats * segment.beat_secs())
+    }
+
+    /// Tempo in effect at `seconds` into the song
+    pub fn bpm_at(&self, seconds: f64) -> f64 {
+        self.segments
+            .iter()
+            .rev()
+            .find(|s| s.start <= seconds)
+            .unwrap_or(&self.segments[0])
+            .bpm
+    }
+
+    fn segment_for_bar(&self, bar: u32) -> &Segment {
+        self.segments
+            .iter()
+            .rev()
+            .find(|s| s.bar <= bar)
+            .unwrap_or(&self.segments[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(bar: u32, beat: u32, tick: u32) -> BeatPosition {
+        BeatPosition { bar, beat, tick }
+    }
+
+    #[test]
+    fn test_constant_tempo_from_offset() {
+        let map = TempoMap::new(&[], 120.0, 1.0).unwrap();
+        assert_eq!(map.seconds_at(&pos(1, 1, 0)).unwrap(), 1.0);
+        assert_eq!(map.seconds_at(&pos(2, 1, 0)).unwrap(), 3.0);
+        assert_eq!(map.seconds_at(&pos(2, 3, 240)).unwrap(), 4.25);
+    }
+
+    #[test]
+    fn test_tempo_and_metre_changes() {
+        let changes = [
+            TempoChange { bar: 1, bpm: 120.0, beats_per_bar: 4 },
+            TempoChange { bar: 3, bpm: 60.0, beats_per_bar: 3 },
+        ];
+        let map = TempoMap::new(&changes, 100.0, 0.0).unwrap();
+
+        // Two bars of 4/4 at 120 BPM, then 3/4 at 60 BPM
+        assert_eq!(map.seconds_at(&pos(3, 1, 0)).unwrap(), 4.0);
+        assert_eq!(map.seconds_at(&pos(4, 2, 0)).unwrap(), 8.0);
+        assert_eq!(map.bpm_at(3.9), 120.0);
+        assert_eq!(map.bpm_at(4.0), 60.0);
+        assert!(map.seconds_at(&pos(4, 4, 0)).is_err());
+    }
+
+    #[test]
+    fn test_invalid_changes_rejected() {
+        let zero_bpm = [TempoChange { bar: 1, bpm: 0.0, beats_per_bar: 4 }];
+        assert!(TempoMap::new(&zero_bpm, 120.0, 0.0).is_err());
+
+        let duplicate = [
+            TempoChange { bar: 2, bpm: 90.0, beats_per_bar: 4 },
+            TempoChange { bar: 2, bpm: 100.0, beats_per_bar: 4 },
+        ];
+        assert!(TempoMap::new(&duplicate, 120.0, 0.0).is_err());
+    }
+}
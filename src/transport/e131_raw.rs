@@ -86,6 +86,11 @@ impl E131RawTransport {
         p
     }
 
+    /// Broadcast address a transport for `board_ip` would send to
+    pub fn broadcast_addr_for(board_ip: &str) -> Result<SocketAddr, Box<dyn Error>> {
+        Self::derive_broadcast_addr(&[board_ip.to_string()])
+    }
+
     fn derive_broadcast_addr(board_ips: &[String]) -> Result<SocketAddr, Box<dyn Error>> {
         if let Some(first_ip) = board_ips.first() {
             let ip_part = first_ip.split(':').next().unwrap_or(first_ip);
@@ -173,19 +178,4 @@ impl E131RawTransport {
     pub fn send_blackout(&mut self) -> Result<(), Box<dyn Error>> {
         self.send_raw_leds(128, 0, 0, 0)
     }
-
-    pub fn send_led_buffer(&mut self, led_data: &[[u8; 3]]) -> Result<(), Box<dyn Error>> {
-        let mut dmx_data = [0u8; 512];
-        let count = led_data.len().min(128);
-
-        for i in 0..count {
-            let offset = i * 4;
-            dmx_data[offset] = led_data[i][0];
-            dmx_data[offset + 1] = led_data[i][1];
-            dmx_data[offset + 2] = led_data[i][2];
-            dmx_data[offset + 3] = 0;
-        }
-
-        self.send_dmx_packet(&dmx_data)
-    }
 }
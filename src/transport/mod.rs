@@ -1,3 +1,5 @@
 pub mod e131_raw;
+pub mod pool;
 
 pub use e131_raw::E131RawTransport;
+pub use pool::{OutputKey, SharedTransport, TransportPool};
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use super::E131RawTransport;
+use crate::config::Config;
+use crate::timing_metrics::TimingMetrics;
+
+pub type SharedTransport = Arc<Mutex<E131RawTransport>>;
+
+/// Destination of one E1.31 stream
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OutputKey {
+    pub addr: SocketAddr,
+    pub universe: u16,
+}
+
+impl OutputKey {
+    pub fn for_board(ip: &str, universe: u16) -> Result<Self, Box<dyn Error>> {
+        Ok(Self {
+            addr: E131RawTransport::broadcast_addr_for(ip)?,
+            universe,
+        })
+    }
+}
+
+#[derive(Default)]
+struct PoolInner {
+    outputs: HashMap<OutputKey, SharedTransport>,
+    groups: HashMap<String, OutputKey>,
+    timing_metrics: Option<Arc<TimingMetrics>>,
+}
+
+/// Long-lived E1.31 outputs shared by the compositor and group commands
+///
+/// There is one transport per destination and universe, so sequence numbers
+/// carry on across cue changes instead of restarting at 0.
+#[derive(Clone, Default)]
+pub struct TransportPool {
+    inner: Arc<Mutex<PoolInner>>,
+}
+
+impl TransportPool {
+    pub fn new(timing_metrics: Option<Arc<TimingMetrics>>) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(PoolInner {
+                timing_metrics,
+                ..PoolInner::default()
+            })),
+        }
+    }
+
+    /// Transport for a board, created on first use
+    pub fn get(&self, ip: &str, universe: u16) -> Result<(OutputKey, SharedTransport), Box<dyn Error>> {
+        let key = OutputKey::for_board(ip, universe)?;
+        let mut inner = self.inner.lock().map_err(|e| e.to_string())?;
+        let transport = inner.get_or_create(key, ip)?;
+        Ok((key, transport))
+    }
+
+    /// Transport a group's E1.31 commands go to
+    pub fn group(&self, group_id: &str) -> Option<SharedTransport> {
+        let inner = self.inner.lock().ok()?;
+        let key = inner.groups.get(group_id)?;
+        inner.outputs.get(key).cloned()
+    }
+
+    /// Match the pool to the boards and groups in `config`
+    ///
+    /// Outputs that are still in use keep their transport (and sequence
+    /// number); outputs nobody references any more are dropped.
+    pub fn rebuild(&self, config: &Config) {
+        let Ok(mut inner) = self.inner.lock() else {
+            return;
+        };
+
+        let mut wanted: HashMap<OutputKey, String> = HashMap::new();
+        for board in &config.boards {
+            if let Some(universe) = board.universe {
+                if let Ok(key) = OutputKey::for_board(&board.ip, universe) {
+                    wanted.entry(key).or_insert_with(|| board.ip.clone());
+                }
+            }
+        }
+
+        let mut groups = HashMap::new();
+        for (universe_index, group) in config.groups.iter().enumerate() {
+            let Some(ip) = group
+                .members
+                .iter()
+                .find_map(|id| config.find_board(id).map(|b| b.ip.clone()))
+            else {
+                warn!(group_id = %group.id, "No boards found for group - will use WebSocket only");
+                continue;
+            };
+            let universe = group.universe.unwrap_or((universe_index + 1) as u16);
+            match OutputKey::for_board(&ip, universe) {
+                Ok(key) => {
+                    groups.insert(group.id.clone(), key);
+                    wanted.entry(key).or_insert(ip);
+                }
+                Err(e) => warn!(group_id = %group.id, "Invalid group board address: {}", e),
+            }
+        }
+
+        inner.outputs.retain(|key, _| wanted.contains_key(key));
+        for (key, ip) in &wanted {
+            if let Err(e) = inner.get_or_create(*key, ip) {
+                warn!(ip = %ip, universe = key.universe, "Failed to initialize E1.31 transport: {}", e);
+            }
+        }
+        inner.groups = groups;
+
+        info!(
+            outputs = inner.outputs.len(),
+            groups = inner.groups.len(),
+            "E1.31 transport pool rebuilt"
+        );
+    }
+}
+
+impl PoolInner {
+    fn get_or_create(&mut self, key: OutputKey, ip: &str) -> Result<SharedTransport, Box<dyn Error>> {
+        if let Some(transport) = self.outputs.get(&key) {
+            return Ok(transport.clone());
+        }
+
+        let mut transport = E131RawTransport::new(vec![ip.to_string()], key.universe)?;
+        if let Some(ref metrics) = self.timing_metrics {
+            transport.set_timing_metrics(metrics.clone());
+        }
+        let transport = Arc::new(Mutex::new(transport));
+        self.outputs.insert(key, transport.clone());
+        Ok(transport)
+    }
+}
@@ -117,6 +117,11 @@ pub struct UploadAudioRequest {
 #[derive(Serialize)]
 pub struct UploadAudioResponse {
     pub audio_file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub analysis: Option<crate::audio_analysis::TempoAnalysis>,
+    /// Version of the program after the analysis was stored on it; send it as `If-Match` on the next save
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub program_version: Option<u64>,
 }
 
 // Preset request structs
@@ -137,6 +142,8 @@ pub struct WledPreset {
     pub description: Option<String>,
     pub state: PresetState,
     pub created_at: String,
+    #[serde(default)]
+    pub version: u64,  // Bumped on every update; sent as the ETag
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -163,16 +170,18 @@ pub struct AppState {
     pub boards: Arc<RwLock<HashMap<String, BoardEntry>>>,
     pub broadcast_tx: Arc<broadcast::Sender<SseEvent>>,
     pub storage_paths: Arc<crate::config::StoragePaths>,
-    pub group_e131_transports: Arc<RwLock<HashMap<String, crate::transport::E131RawTransport>>>,
+    pub transport_pool: crate::transport::TransportPool,
     pub config: Arc<Mutex<crate::config::Config>>,
     pub effects_engine: Arc<crate::effects_engine::EffectsEngine>,
     pub pattern_engine: Arc<crate::pattern_engine::PatternEngine>,
     pub programs: Arc<RwLock<HashMap<String, crate::program::Program>>>,
+    pub setlists: Arc<RwLock<HashMap<String, crate::setlist::Setlist>>>,
     pub program_engine: Arc<crate::program_engine::ProgramEngine>,
     pub connected_ips: Arc<RwLock<HashSet<String>>>,
     pub performance_mode: Arc<AtomicBool>,
     pub timing_metrics: Arc<crate::timing_metrics::TimingMetrics>,
     pub playback_history: Arc<crate::playback_history::PlaybackHistory>,
+    pub sequences: Arc<crate::fseq::SequenceLibrary>,
 }
 
 pub type SharedState = Arc<AppState>;